@@ -0,0 +1,72 @@
+use bumpalo::Bump;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use jsonata_rs::{JsonAta, Value};
+
+/// Generate a document of roughly `orders` * 400 bytes, with the kind of nesting, strings,
+/// escapes and numbers found in typical input.
+fn document(orders: usize) -> String {
+    let orders = (0..orders)
+        .map(|i| {
+            let items = (0..3)
+                .map(|j| {
+                    format!(
+                        r#"{{"sku": "SKU-{i}-{j}", "description": "Item \"{j}\" of order {i}\n", "quantity": {}, "price": {}.{:02}, "tags": ["a", "b", "c"]}}"#,
+                        j + 1,
+                        i % 1000,
+                        j * 7 % 100
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(
+                r#"{{"id": {i}, "customer": {{"name": "Customer {i}", "email": "customer{i}@example.com", "vip": {}}}, "items": [{items}], "note": null}}"#,
+                i % 3 == 0
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    format!(r#"{{"orders": [{orders}]}}"#)
+}
+
+fn json_input(c: &mut Criterion) {
+    let mut group = c.benchmark_group("json_input");
+    group.sample_size(10);
+
+    for orders in [2_500, 10_000] {
+        let input = document(orders);
+        let size = format!("{}KB", input.len() / 1024);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("json", &size), &input, |b, input| {
+            b.iter(|| {
+                let arena = Bump::new();
+                Value::from_json_str(&arena, input).unwrap();
+            })
+        });
+
+        // How input was parsed before, by evaluating it as a JSONata expression
+        group.bench_with_input(BenchmarkId::new("expression", &size), &input, |b, input| {
+            b.iter(|| {
+                let arena = Bump::new();
+                JsonAta::new(input, &arena)
+                    .unwrap()
+                    .evaluate(None, None)
+                    .unwrap();
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("serde_json", &size), &input, |b, input| {
+            b.iter(|| {
+                let arena = Bump::new();
+                let value: serde_json::Value = serde_json::from_str(input).unwrap();
+                Value::from_json(&arena, &value);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, json_input);
+criterion_main!(benches);
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use bumpalo::Bump;
+
+use crate::evaluator::frame::Frame;
+use crate::Value;
+
+/// Variables to bind for a single evaluation.
+///
+/// The bindings are put in a frame of their own for each evaluation they're passed to, so they
+/// never outlive it, and no evaluation sees the variables of another.
+pub struct Bindings<'a> {
+    arena: &'a Bump,
+    values: Vec<(String, &'a Value<'a>)>,
+}
+
+impl<'a> Bindings<'a> {
+    pub fn new(arena: &'a Bump) -> Self {
+        Self {
+            arena,
+            values: Vec::new(),
+        }
+    }
+
+    /// Bind a variable, which is referred to as `$name` in the expression. Binding the same name
+    /// again replaces the earlier value.
+    pub fn bind(mut self, name: &str, value: impl IntoBinding<'a>) -> Self {
+        let value = value.into_binding(self.arena);
+        match self.values.iter_mut().find(|(bound, _)| bound == name) {
+            Some(binding) => binding.1 = value,
+            None => self.values.push((name.to_string(), value)),
+        }
+        self
+    }
+
+    pub(crate) fn from_json(arena: &'a Bump, bindings: &HashMap<&str, &serde_json::Value>) -> Self {
+        bindings
+            .iter()
+            .fold(Self::new(arena), |bindings, (name, value)| {
+                bindings.bind(name, *value)
+            })
+    }
+
+    /// Create the frame for one evaluation, holding these bindings.
+    pub(crate) fn frame(&self, parent: &Frame<'a>) -> Frame<'a> {
+        let frame = Frame::new_with_parent(parent);
+        for (name, value) in self.values.iter() {
+            frame.bind(name, value);
+        }
+        frame
+    }
+}
+
+/// A value which can be bound to a variable with [`Bindings::bind`].
+pub trait IntoBinding<'a> {
+    fn into_binding(self, arena: &'a Bump) -> &'a Value<'a>;
+}
+
+impl<'a> IntoBinding<'a> for &'a Value<'a> {
+    fn into_binding(self, _arena: &'a Bump) -> &'a Value<'a> {
+        self
+    }
+}
+
+impl<'a> IntoBinding<'a> for &'a mut Value<'a> {
+    fn into_binding(self, _arena: &'a Bump) -> &'a Value<'a> {
+        self
+    }
+}
+
+impl<'a> IntoBinding<'a> for &serde_json::Value {
+    fn into_binding(self, arena: &'a Bump) -> &'a Value<'a> {
+        Value::from_json(arena, self)
+    }
+}
+
+impl<'a> IntoBinding<'a> for serde_json::Value {
+    fn into_binding(self, arena: &'a Bump) -> &'a Value<'a> {
+        Value::from_json(arena, &self)
+    }
+}
+
+impl<'a> IntoBinding<'a> for bool {
+    fn into_binding(self, _arena: &'a Bump) -> &'a Value<'a> {
+        Value::bool(self)
+    }
+}
+
+impl<'a> IntoBinding<'a> for &str {
+    fn into_binding(self, arena: &'a Bump) -> &'a Value<'a> {
+        Value::string(arena, self)
+    }
+}
+
+impl<'a> IntoBinding<'a> for String {
+    fn into_binding(self, arena: &'a Bump) -> &'a Value<'a> {
+        Value::string(arena, &self)
+    }
+}
+
+macro_rules! number_binding {
+    ($($t:ty),*) => {
+        $(
+            impl<'a> IntoBinding<'a> for $t {
+                fn into_binding(self, arena: &'a Bump) -> &'a Value<'a> {
+                    Value::number(arena, self as f64)
+                }
+            }
+        )*
+    };
+}
+
+number_binding!(f64, f32, i64, i32, i16, i8, u64, u32, u16, u8, usize, isize);
@@ -0,0 +1,569 @@
+//! Number formatting using the picture strings of XPath F&O 3.1
+//! [section 4.7](https://www.w3.org/TR/xpath-functions-31/#formatting-numbers), as used by
+//! `$formatNumber`.
+//!
+//! This follows the analysis and formatting steps of the reference implementation, including
+//! which of the D3080-D3093 errors is raised when a picture breaks more than one rule.
+
+use crate::Error;
+
+/// The properties of a decimal format, which can be overridden by the options passed to
+/// `$formatNumber`.
+#[derive(Debug, Clone)]
+pub struct DecimalFormat {
+    pub decimal_separator: char,
+    pub grouping_separator: char,
+    pub exponent_separator: char,
+    pub infinity: String,
+    pub minus_sign: String,
+    pub nan: String,
+    pub percent: String,
+    pub per_mille: String,
+    pub zero_digit: char,
+    pub digit: char,
+    pub pattern_separator: char,
+}
+
+impl Default for DecimalFormat {
+    fn default() -> Self {
+        Self {
+            decimal_separator: '.',
+            grouping_separator: ',',
+            exponent_separator: 'e',
+            infinity: "Infinity".to_string(),
+            minus_sign: "-".to_string(),
+            nan: "NaN".to_string(),
+            percent: "%".to_string(),
+            per_mille: "\u{2030}".to_string(),
+            zero_digit: '0',
+            digit: '#',
+            pattern_separator: ';',
+        }
+    }
+}
+
+impl DecimalFormat {
+    /// Override one of the properties by the name it has in the options object. Returns false
+    /// if a property that must be a single character was given anything else. Unknown names
+    /// are ignored.
+    pub fn set(&mut self, name: &str, value: &str) -> bool {
+        let mut chars = value.chars();
+        let single = match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        };
+
+        let property = match name {
+            "decimal-separator" => &mut self.decimal_separator,
+            "grouping-separator" => &mut self.grouping_separator,
+            "exponent-separator" => &mut self.exponent_separator,
+            "zero-digit" => &mut self.zero_digit,
+            "digit" => &mut self.digit,
+            "pattern-separator" => &mut self.pattern_separator,
+            _ => {
+                match name {
+                    "infinity" => self.infinity = value.to_string(),
+                    "minus-sign" => self.minus_sign = value.to_string(),
+                    "NaN" => self.nan = value.to_string(),
+                    "percent" => self.percent = value.to_string(),
+                    "per-mille" => self.per_mille = value.to_string(),
+                    _ => {}
+                }
+                return true;
+            }
+        };
+
+        match single {
+            Some(c) => {
+                *property = c;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The value of a member of the decimal digit family, i.e. the ten characters starting at
+    /// the zero digit.
+    fn digit_value(&self, c: char) -> Option<u32> {
+        let value = (c as u32).wrapping_sub(self.zero_digit as u32);
+        (value < 10).then_some(value)
+    }
+
+    fn is_decimal_digit(&self, c: char) -> bool {
+        self.digit_value(c).is_some()
+    }
+
+    fn is_digit(&self, c: char) -> bool {
+        self.is_decimal_digit(c) || c == self.digit
+    }
+
+    fn is_active(&self, c: char) -> bool {
+        self.is_digit(c)
+            || c == self.decimal_separator
+            || c == self.exponent_separator
+            || c == self.grouping_separator
+            || c == self.pattern_separator
+    }
+
+    /// Convert ASCII digits to the decimal digit family.
+    fn to_digits(&self, s: &str) -> Vec<char> {
+        s.chars()
+            .map(|c| match c.to_digit(10) {
+                Some(d) => char::from_u32(self.zero_digit as u32 + d).unwrap_or(c),
+                None => c,
+            })
+            .collect()
+    }
+}
+
+/// A sub-picture, split into its parts.
+struct Parts {
+    picture: String,
+    prefix: String,
+    suffix: String,
+    active: Vec<char>,
+    mantissa: Vec<char>,
+    exponent: Option<Vec<char>>,
+    integer: Vec<char>,
+    fractional: Vec<char>,
+}
+
+/// The variables derived from analysing a sub-picture, which drive the formatting.
+#[derive(Clone)]
+struct Analysis {
+    picture: String,
+    prefix: String,
+    suffix: String,
+    integer_grouping_positions: Vec<usize>,
+    regular_grouping: usize,
+    minimum_integer_size: usize,
+    scaling_factor: usize,
+    fractional_grouping_positions: Vec<usize>,
+    minimum_fractional_size: usize,
+    maximum_fractional_size: usize,
+    minimum_exponent_size: usize,
+}
+
+/// Format a number according to a picture string, e.g. `#,##0.00`.
+pub fn format_number(
+    value: f64,
+    picture: &str,
+    format: &DecimalFormat,
+    char_index: usize,
+) -> Result<String, Error> {
+    let sub_pictures: Vec<&str> = picture.split(format.pattern_separator).collect();
+    if sub_pictures.len() > 2 {
+        return Err(Error::D3080TooManySubPictures(char_index));
+    }
+
+    let parts: Vec<Parts> = sub_pictures
+        .iter()
+        .map(|sub_picture| split_parts(sub_picture, format))
+        .collect();
+
+    for part in &parts {
+        validate(part, format, char_index)?;
+    }
+
+    let mut variables: Vec<Analysis> = parts.iter().map(|part| analyse(part, format)).collect();
+
+    if variables.len() == 1 {
+        let mut negative = variables[0].clone();
+        negative.prefix = format!("{}{}", format.minus_sign, negative.prefix);
+        variables.push(negative);
+    }
+
+    let pic = if value >= 0.0 || value.is_nan() {
+        &variables[0]
+    } else {
+        &variables[1]
+    };
+
+    if value.is_nan() {
+        return Ok(format.nan.clone());
+    }
+
+    if value.is_infinite() {
+        return Ok(format!("{}{}{}", pic.prefix, format.infinity, pic.suffix));
+    }
+
+    let adjusted = if contains(&pic.picture, &format.percent) {
+        value * 100.0
+    } else if contains(&pic.picture, &format.per_mille) {
+        value * 1000.0
+    } else {
+        value
+    };
+
+    // The sign is taken care of by the sub-picture, so the rest works on the magnitude
+    let mut mantissa = adjusted.abs();
+    let mut exponent = None;
+
+    if pic.minimum_exponent_size > 0 {
+        let max_mantissa = 10f64.powi(pic.scaling_factor as i32);
+        let min_mantissa = 10f64.powi(pic.scaling_factor as i32 - 1);
+        let mut e: i32 = 0;
+        if mantissa != 0.0 {
+            while mantissa < min_mantissa {
+                mantissa *= 10.0;
+                e -= 1;
+            }
+            while mantissa > max_mantissa {
+                mantissa /= 10.0;
+                e += 1;
+            }
+        }
+        exponent = Some(e);
+    }
+
+    let rounded = round_half_even(mantissa, pic.maximum_fractional_size);
+    let digits = format!("{:.*}", pic.maximum_fractional_size, rounded);
+
+    let mut s = format.to_digits(&digits);
+    match s.iter().position(|c| *c == '.') {
+        Some(pos) => s[pos] = format.decimal_separator,
+        None => s.push(format.decimal_separator),
+    }
+
+    let leading = s.iter().take_while(|c| **c == format.zero_digit).count();
+    s.drain(..leading);
+    while s.last() == Some(&format.zero_digit) {
+        s.pop();
+    }
+
+    let decimal_pos = decimal_position(&s, format);
+    let pad_left = pic.minimum_integer_size.saturating_sub(decimal_pos);
+    let pad_right = pic
+        .minimum_fractional_size
+        .saturating_sub(s.len() - decimal_pos - 1);
+    s.splice(0..0, std::iter::repeat_n(format.zero_digit, pad_left));
+    s.extend(std::iter::repeat_n(format.zero_digit, pad_right));
+
+    let mut decimal_pos = decimal_position(&s, format);
+    if let Some(group_count) = decimal_pos
+        .saturating_sub(1)
+        .checked_div(pic.regular_grouping)
+    {
+        for group in 1..=group_count {
+            s.insert(
+                decimal_pos - group * pic.regular_grouping,
+                format.grouping_separator,
+            );
+        }
+    } else {
+        for pos in &pic.integer_grouping_positions {
+            if *pos < decimal_pos {
+                s.insert(decimal_pos - pos, format.grouping_separator);
+                decimal_pos += 1;
+            }
+        }
+    }
+
+    let decimal_pos = decimal_position(&s, format);
+    for pos in &pic.fractional_grouping_positions {
+        let at = pos + decimal_pos + 1;
+        if at < s.len() {
+            s.insert(at, format.grouping_separator);
+        }
+    }
+
+    if pic.minimum_fractional_size == 0 && decimal_position(&s, format) == s.len() - 1 {
+        s.pop();
+    }
+
+    let mut result = pic.prefix.clone();
+    result.extend(s);
+
+    if let Some(exponent) = exponent {
+        let mut exponent_digits = format.to_digits(&exponent.abs().to_string());
+        let pad = pic
+            .minimum_exponent_size
+            .saturating_sub(exponent_digits.len());
+        exponent_digits.splice(0..0, std::iter::repeat_n(format.zero_digit, pad));
+
+        result.push(format.exponent_separator);
+        if exponent < 0 {
+            result.push_str(&format.minus_sign);
+        }
+        result.extend(exponent_digits);
+    }
+
+    result.push_str(&pic.suffix);
+
+    Ok(result)
+}
+
+fn split_parts(sub_picture: &str, format: &DecimalFormat) -> Parts {
+    let chars: Vec<char> = sub_picture.chars().collect();
+    let is_boundary = |c: &char| format.is_active(*c) && *c != format.exponent_separator;
+
+    let prefix_len = chars.iter().position(is_boundary).unwrap_or(chars.len());
+    let suffix_start = chars
+        .iter()
+        .rposition(is_boundary)
+        .map_or(chars.len(), |pos| pos + 1)
+        .max(prefix_len);
+
+    let active = chars[prefix_len..suffix_start].to_vec();
+
+    let (mantissa, exponent) = match active.iter().position(|c| *c == format.exponent_separator) {
+        Some(pos) => (active[..pos].to_vec(), Some(active[pos + 1..].to_vec())),
+        None => (active.clone(), None),
+    };
+
+    let (integer, fractional) = match mantissa.iter().position(|c| *c == format.decimal_separator) {
+        Some(pos) => (mantissa[..pos].to_vec(), mantissa[pos + 1..].to_vec()),
+        None => (mantissa.clone(), Vec::new()),
+    };
+
+    Parts {
+        picture: sub_picture.to_string(),
+        prefix: chars[..prefix_len].iter().collect(),
+        suffix: chars[suffix_start..].iter().collect(),
+        active,
+        mantissa,
+        exponent,
+        integer,
+        fractional,
+    }
+}
+
+/// Check a sub-picture against the rules in F&O 4.7.3. Like the reference implementation,
+/// when more than one rule is broken the error for the last one is raised.
+fn validate(parts: &Parts, format: &DecimalFormat, char_index: usize) -> Result<(), Error> {
+    let picture: Vec<char> = parts.picture.chars().collect();
+    let mut error = None;
+
+    let decimal_pos = picture.iter().position(|c| *c == format.decimal_separator);
+    if picture
+        .iter()
+        .filter(|c| **c == format.decimal_separator)
+        .count()
+        > 1
+    {
+        error = Some(Error::D3081MultipleDecimalSeparators(char_index));
+    }
+
+    let has_percent = contains(&parts.picture, &format.percent);
+    let has_per_mille = contains(&parts.picture, &format.per_mille);
+
+    if has_percent && parts.picture.matches(format.percent.as_str()).count() > 1 {
+        error = Some(Error::D3082MultiplePercents(char_index));
+    }
+
+    if has_per_mille && parts.picture.matches(format.per_mille.as_str()).count() > 1 {
+        error = Some(Error::D3083MultiplePerMilles(char_index));
+    }
+
+    if has_percent && has_per_mille {
+        error = Some(Error::D3084PercentAndPerMille(char_index));
+    }
+
+    if !parts.mantissa.iter().any(|c| format.is_digit(*c)) {
+        error = Some(Error::D3085NoMantissaDigits(char_index));
+    }
+
+    if parts.active.iter().any(|c| !format.is_active(*c)) {
+        error = Some(Error::D3086PassiveBetweenActive(char_index));
+    }
+
+    match decimal_pos {
+        Some(pos) => {
+            let before = pos.checked_sub(1).and_then(|p| picture.get(p));
+            let after = picture.get(pos + 1);
+            if before == Some(&format.grouping_separator)
+                || after == Some(&format.grouping_separator)
+            {
+                error = Some(Error::D3087GroupingAdjacentToDecimal(char_index));
+            }
+        }
+        None => {
+            if parts.integer.last() == Some(&format.grouping_separator) {
+                error = Some(Error::D3088GroupingAtEndOfInteger(char_index));
+            }
+        }
+    }
+
+    if picture
+        .windows(2)
+        .any(|w| w[0] == format.grouping_separator && w[1] == format.grouping_separator)
+    {
+        error = Some(Error::D3089AdjacentGroupings(char_index));
+    }
+
+    if let Some(pos) = parts.integer.iter().position(|c| *c == format.digit) {
+        if parts.integer[..pos]
+            .iter()
+            .any(|c| format.is_decimal_digit(*c))
+        {
+            error = Some(Error::D3090DigitBeforeOptionalDigit(char_index));
+        }
+    }
+
+    if let Some(pos) = parts.fractional.iter().rposition(|c| *c == format.digit) {
+        if parts.fractional[pos..]
+            .iter()
+            .any(|c| format.is_decimal_digit(*c))
+        {
+            error = Some(Error::D3091OptionalDigitBeforeDigit(char_index));
+        }
+    }
+
+    if let Some(ref exponent) = parts.exponent {
+        if !exponent.is_empty() && (has_percent || has_per_mille) {
+            error = Some(Error::D3092ExponentWithPercent(char_index));
+        }
+
+        if exponent.is_empty() || exponent.iter().any(|c| !format.is_decimal_digit(*c)) {
+            error = Some(Error::D3093InvalidExponent(char_index));
+        }
+    }
+
+    match error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// Derive the formatting variables from a sub-picture, as in F&O 4.7.4.
+fn analyse(parts: &Parts, format: &DecimalFormat) -> Analysis {
+    // For each grouping separator, the number of digits between it and the decimal separator
+    let grouping_positions = |part: &[char], to_left: bool| -> Vec<usize> {
+        part.iter()
+            .enumerate()
+            .filter(|(_, c)| **c == format.grouping_separator)
+            .map(|(pos, _)| {
+                let side = if to_left { &part[..pos] } else { &part[pos..] };
+                side.iter().filter(|c| format.is_digit(**c)).count()
+            })
+            .collect()
+    };
+
+    let integer_grouping_positions = grouping_positions(&parts.integer, false);
+    let regular_grouping = regular_grouping(&integer_grouping_positions);
+    let fractional_grouping_positions = grouping_positions(&parts.fractional, true);
+
+    let count_decimal_digits =
+        |part: &[char]| part.iter().filter(|c| format.is_decimal_digit(**c)).count();
+
+    let mut minimum_integer_size = count_decimal_digits(&parts.integer);
+    let scaling_factor = minimum_integer_size;
+    let mut minimum_fractional_size = count_decimal_digits(&parts.fractional);
+    let mut maximum_fractional_size = parts
+        .fractional
+        .iter()
+        .filter(|c| format.is_digit(**c))
+        .count();
+
+    let exponent_present = parts.exponent.is_some();
+
+    if minimum_integer_size == 0 && maximum_fractional_size == 0 {
+        if exponent_present {
+            minimum_fractional_size = 1;
+            maximum_fractional_size = 1;
+        } else {
+            minimum_integer_size = 1;
+        }
+    }
+
+    if exponent_present && minimum_integer_size == 0 && parts.integer.contains(&format.digit) {
+        minimum_integer_size = 1;
+    }
+
+    if minimum_integer_size == 0 && minimum_fractional_size == 0 {
+        minimum_fractional_size = 1;
+    }
+
+    let minimum_exponent_size = parts.exponent.as_deref().map_or(0, count_decimal_digits);
+
+    Analysis {
+        picture: parts.picture.clone(),
+        prefix: parts.prefix.clone(),
+        suffix: parts.suffix.clone(),
+        integer_grouping_positions,
+        regular_grouping,
+        minimum_integer_size,
+        scaling_factor,
+        fractional_grouping_positions,
+        minimum_fractional_size,
+        maximum_fractional_size,
+        minimum_exponent_size,
+    }
+}
+
+/// If the grouping separators are all the same distance apart, returns that distance.
+fn regular_grouping(positions: &[usize]) -> usize {
+    fn gcd(a: usize, b: usize) -> usize {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+
+    let Some(factor) = positions.iter().copied().reduce(gcd) else {
+        return 0;
+    };
+
+    if factor > 0 && (1..=positions.len()).all(|index| positions.contains(&(index * factor))) {
+        factor
+    } else {
+        0
+    }
+}
+
+fn contains(picture: &str, property: &str) -> bool {
+    !property.is_empty() && picture.contains(property)
+}
+
+fn decimal_position(s: &[char], format: &DecimalFormat) -> usize {
+    s.iter()
+        .position(|c| *c == format.decimal_separator)
+        .unwrap_or(s.len())
+}
+
+/// Round half to even at the given number of decimal places. As in `$round`, the decimal point
+/// is shifted in the string representation to avoid floating point errors.
+fn round_half_even(value: f64, precision: usize) -> f64 {
+    let shifted = format!("{}e{}", value, precision)
+        .parse::<f64>()
+        .unwrap_or(value);
+    let rounded = shifted.round_ties_even();
+    format!("{}e-{}", rounded, precision)
+        .parse::<f64>()
+        .unwrap_or(rounded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(value: f64, picture: &str) -> String {
+        format_number(value, picture, &DecimalFormat::default(), 0).unwrap()
+    }
+
+    #[test]
+    fn grouping() {
+        assert_eq!(format(1234567.891, "#,##0.00"), "1,234,567.89");
+        assert_eq!(format(-1234.5, "#,##0.00"), "-1,234.50");
+        assert_eq!(format(0.0, "#,##0.00"), "0.00");
+    }
+
+    #[test]
+    fn negative_sub_picture() {
+        assert_eq!(format(-1234.5, "#,##0.00;(#,##0.00)"), "(1,234.50)");
+    }
+
+    #[test]
+    fn custom_separators() {
+        let mut decimal_format = DecimalFormat::default();
+        assert!(decimal_format.set("decimal-separator", ","));
+        assert!(decimal_format.set("grouping-separator", "."));
+        assert!(!decimal_format.set("digit", "##"));
+
+        assert_eq!(
+            format_number(1234567.891, "#.##0,00", &decimal_format, 0).unwrap(),
+            "1.234.567,89"
+        );
+    }
+}
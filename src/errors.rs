@@ -16,6 +16,7 @@ pub enum Error {
     S0202UnexpectedToken(usize, String, String),
     S0204UnknownOperator(usize, String),
     S0203ExpectedTokenBeforeEnd(usize, String),
+    S0207UnexpectedEndOfExpression(usize),
     S0208InvalidFunctionParam(usize, String),
     S0209InvalidPredicate(usize),
     S0210MultipleGroupBy(usize),
@@ -25,10 +26,13 @@ pub enum Error {
     S0214ExpectedVarRight(usize, String),
     S0215BindingAfterPredicates(usize),
     S0216BindingAfterSort(usize),
+    S0217CannotDeriveParent(usize),
     S0301EmptyRegex(usize),
     S0302UnterminatedRegex(usize),
     // This variant is not present in the JS implementation
     S0303InvalidRegex(usize, String),
+    S0401TypeParamsNotSupported(usize, String),
+    S0402ChoiceGroupParameterized(usize, String),
 
     // Runtime errors
     D1001NumberOfOutRange(f64),
@@ -46,20 +50,47 @@ pub enum Error {
     D3060SqrtNegative(usize, String),
     D3061PowUnrepresentable(usize, String, String),
     D3070InvalidDefaultSort(usize),
+    D3080TooManySubPictures(usize),
+    D3081MultipleDecimalSeparators(usize),
+    D3082MultiplePercents(usize),
+    D3083MultiplePerMilles(usize),
+    D3084PercentAndPerMille(usize),
+    D3085NoMantissaDigits(usize),
+    D3086PassiveBetweenActive(usize),
+    D3087GroupingAdjacentToDecimal(usize),
+    D3088GroupingAtEndOfInteger(usize),
+    D3089AdjacentGroupings(usize),
+    D3090DigitBeforeOptionalDigit(usize),
+    D3091OptionalDigitBeforeDigit(usize),
+    D3092ExponentWithPercent(usize),
+    D3093InvalidExponent(usize),
+    D3100RadixOutOfRange(usize, isize),
+    D3120SyntaxErrorInEval(usize, String),
+    D3121DynamicErrorInEval(usize, String),
+    D3130SequenceNotSupported(usize, String),
+    D3131MixedDecimalGroups(usize),
     D3141Assert(String),
     D3137Error(String),
     D3138Error(String),
     D3139Error(String),
+    D3140MalformedUrl(usize, String, String),
+    D3132UnknownComponentSpecifier(String),
     D3133PictureStringNameModifierError(String),
     D3134TooManyTzDigits(String),
     D3135PictureStringNoClosingBracketError(String),
+    D3136PictureStringMissingComponents(String),
+    D3142PictureStringWidthTooLarge(String),
 
     // Type errors
     T0410ArgumentNotValid(usize, usize, String),
+    T0411ContextValueNotCompatible(usize, usize, String),
     T0412ArgumentMustBeArrayOfType(usize, usize, String, String),
     T1003NonStringKey(usize, String),
     T1005InvokedNonFunctionSuggest(usize, String),
     T1006InvokedNonFunction(usize),
+    T1007PartiallyAppliedNonFunctionSuggest(usize, String),
+    T1008PartiallyAppliedNonFunction(usize),
+    T1010MatcherInvalidResult(usize, String),
     T2001LeftSideNotNumber(usize, String),
     T2002RightSideNotNumber(usize, String),
     T2003LeftSideNotInteger(usize),
@@ -76,6 +107,48 @@ pub enum Error {
     // Expression timebox/depth errors
     U1001StackOverflow,
     U1001Timeout,
+    U1002RegexBudgetExceeded(usize, String),
+
+    // Input errors
+    I0101UnserializableInput(String),
+    I0201UnexpectedCharacter(InputPosition, String, char),
+    I0202UnexpectedEndOfInput(InputPosition, String),
+    I0203InvalidEscape(InputPosition),
+    I0204InvalidUnicodeEscape(InputPosition),
+    I0205ControlCharacterInString(InputPosition),
+    I0206InvalidNumber(InputPosition),
+    I0207NumberOutOfRange(InputPosition, String),
+}
+
+/// A position in JSON input, with lines and columns counted from 1. Columns are counted in
+/// characters, while the offset is in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputPosition {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl InputPosition {
+    pub(crate) fn new(input: &str, offset: usize) -> Self {
+        let before = &input[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl fmt::Display for InputPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {} (byte {})",
+            self.line, self.column, self.offset
+        )
+    }
 }
 
 impl error::Error for Error {}
@@ -87,6 +160,7 @@ impl Error {
      * Sxxxx    - Static errors (compile time)
      * Txxxx    - Type errors
      * Dxxxx    - Dynamic errors (evaluate time)
+     * Ixxxx    - Input errors (01xx creating values from Rust, 02xx parsing JSON)
      *  01xx    - tokenizer
      *  02xx    - parser
      *  03xx    - regex parser
@@ -108,6 +182,7 @@ impl Error {
             Error::S0202UnexpectedToken(..) => "S0202",
             Error::S0203ExpectedTokenBeforeEnd(..) => "S0203",
             Error::S0204UnknownOperator(..) => "S0204",
+            Error::S0207UnexpectedEndOfExpression(..) => "S0207",
             Error::S0208InvalidFunctionParam(..) => "S0208",
             Error::S0209InvalidPredicate(..) => "S0209",
             Error::S0210MultipleGroupBy(..) => "S0210",
@@ -117,9 +192,12 @@ impl Error {
             Error::S0214ExpectedVarRight(..) => "S0214",
             Error::S0215BindingAfterPredicates(..) => "S0215",
             Error::S0216BindingAfterSort(..) => "S0216",
+            Error::S0217CannotDeriveParent(..) => "S0217",
             Error::S0301EmptyRegex(..) => "S0301",
             Error::S0302UnterminatedRegex(..) => "S0302",
             Error::S0303InvalidRegex(..) => "S0303",
+            Error::S0401TypeParamsNotSupported(..) => "S0401",
+            Error::S0402ChoiceGroupParameterized(..) => "S0402",
 
             // Runtime errors
             Error::D1001NumberOfOutRange(..) => "D1001",
@@ -137,20 +215,47 @@ impl Error {
             Error::D3060SqrtNegative(..) => "D3060",
             Error::D3061PowUnrepresentable(..) => "D3061",
             Error::D3070InvalidDefaultSort(..) => "D3070",
+            Error::D3080TooManySubPictures(..) => "D3080",
+            Error::D3081MultipleDecimalSeparators(..) => "D3081",
+            Error::D3082MultiplePercents(..) => "D3082",
+            Error::D3083MultiplePerMilles(..) => "D3083",
+            Error::D3084PercentAndPerMille(..) => "D3084",
+            Error::D3085NoMantissaDigits(..) => "D3085",
+            Error::D3086PassiveBetweenActive(..) => "D3086",
+            Error::D3087GroupingAdjacentToDecimal(..) => "D3087",
+            Error::D3088GroupingAtEndOfInteger(..) => "D3088",
+            Error::D3089AdjacentGroupings(..) => "D3089",
+            Error::D3090DigitBeforeOptionalDigit(..) => "D3090",
+            Error::D3091OptionalDigitBeforeDigit(..) => "D3091",
+            Error::D3092ExponentWithPercent(..) => "D3092",
+            Error::D3093InvalidExponent(..) => "D3093",
+            Error::D3100RadixOutOfRange(..) => "D3100",
+            Error::D3120SyntaxErrorInEval(..) => "D3120",
+            Error::D3121DynamicErrorInEval(..) => "D3121",
+            Error::D3130SequenceNotSupported(..) => "D3130",
+            Error::D3131MixedDecimalGroups(..) => "D3131",
+            Error::D3132UnknownComponentSpecifier(..) => "D3132",
             Error::D3133PictureStringNameModifierError(..) => "D3133",
             Error::D3134TooManyTzDigits(..) => "D3134",
             Error::D3135PictureStringNoClosingBracketError(..) => "D3135",
+            Error::D3136PictureStringMissingComponents(..) => "D3136",
+            Error::D3142PictureStringWidthTooLarge(..) => "D3142",
             Error::D3141Assert(..) => "D3141",
             Error::D3137Error(..) => "D3137",
             Error::D3138Error(..) => "D3138",
             Error::D3139Error(..) => "D3139",
+            Error::D3140MalformedUrl(..) => "D3140",
 
             // Type errors
             Error::T0410ArgumentNotValid(..) => "T0410",
+            Error::T0411ContextValueNotCompatible(..) => "T0411",
             Error::T0412ArgumentMustBeArrayOfType(..) => "T0412",
             Error::T1003NonStringKey(..) => "T1003",
             Error::T1005InvokedNonFunctionSuggest(..) => "T1005",
             Error::T1006InvokedNonFunction(..) => "T1006",
+            Error::T1007PartiallyAppliedNonFunctionSuggest(..) => "T1007",
+            Error::T1008PartiallyAppliedNonFunction(..) => "T1008",
+            Error::T1010MatcherInvalidResult(..) => "T1010",
             Error::T2001LeftSideNotNumber(..) => "T2001",
             Error::T2002RightSideNotNumber(..) => "T2002",
             Error::T2003LeftSideNotInteger(..) => "T2003",
@@ -167,6 +272,17 @@ impl Error {
             // Expression timebox/depth errors
             Error::U1001StackOverflow => "U1001",
             Error::U1001Timeout => "U1001",
+            Error::U1002RegexBudgetExceeded(..) => "U1002",
+
+            // Input errors
+            Error::I0101UnserializableInput(..) => "I0101",
+            Error::I0201UnexpectedCharacter(..) => "I0201",
+            Error::I0202UnexpectedEndOfInput(..) => "I0202",
+            Error::I0203InvalidEscape(..) => "I0203",
+            Error::I0204InvalidUnicodeEscape(..) => "I0204",
+            Error::I0205ControlCharacterInString(..) => "I0205",
+            Error::I0206InvalidNumber(..) => "I0206",
+            Error::I0207NumberOutOfRange(..) => "I0207",
         }
     }
 }
@@ -202,6 +318,8 @@ impl fmt::Display for Error {
                 write!(f, "{}: Expected `{}` before end of expression", p, t),
             S0204UnknownOperator(ref p, ref t) =>
                 write!(f, "{}: Unknown operator: `{}`", p, t),
+            S0207UnexpectedEndOfExpression(ref p) =>
+                write!(f, "{}: Unexpected end of expression", p),
             S0208InvalidFunctionParam(ref p, ref k) =>
                 write!(f, "{}: Parameter `{}` of function definition must be a variable name (start with $)", p, k),
             S0209InvalidPredicate(ref p) =>
@@ -220,6 +338,8 @@ impl fmt::Display for Error {
                 write!(f, "{}: A context variable binding must precede any predicates on a step", p),
             S0216BindingAfterSort(ref p) =>
                 write!(f, "{}: A context variable binding must precede the 'order-by' clause on a step", p),
+            S0217CannotDeriveParent(ref p) =>
+                write!(f, "{}: The object representing the 'parent' cannot be derived from this expression", p),
             S0301EmptyRegex(ref p) =>
                 write!(f, "{}: Empty regular expressions are not allowed", p),
             S0302UnterminatedRegex(ref p) =>
@@ -227,6 +347,10 @@ impl fmt::Display for Error {
             S0303InvalidRegex(ref p, ref message) =>
                 // The error message from `regress::Regex` a "regex parse error: " prefix, so don't be redundant here.
                 write!(f, "{}: {}", p, message),
+            S0401TypeParamsNotSupported(ref p, ref t) =>
+                write!(f, "{}: Type parameters can only be applied to functions and arrays, not {}", p, t),
+            S0402ChoiceGroupParameterized(ref p, ref c) =>
+                write!(f, "{}: Choice groups containing parameterized types are not supported: ({})", p, c),
 
             // Runtime errors
             D1001NumberOfOutRange(ref n) => write!(f, "Number out of range: {}", n),
@@ -257,12 +381,56 @@ impl fmt::Display for Error {
                 write!(f, "{}: The power function has resulted in a value that cannot be represented as a JSON number: base={}, exponent={}", p, b, e),
             D3070InvalidDefaultSort(ref p) =>
                 write!(f, "{}: The single argument form of the sort function can only be applied to an array of strings or an array of numbers.  Use the second argument to specify a comparison function", p),
+            D3080TooManySubPictures(ref p) =>
+                write!(f, "{}: The picture string must only contain a maximum of two sub-pictures", p),
+            D3081MultipleDecimalSeparators(ref p) =>
+                write!(f, "{}: The sub-picture must not contain more than one instance of the 'decimal-separator' character", p),
+            D3082MultiplePercents(ref p) =>
+                write!(f, "{}: The sub-picture must not contain more than one instance of the 'percent' character", p),
+            D3083MultiplePerMilles(ref p) =>
+                write!(f, "{}: The sub-picture must not contain more than one instance of the 'per-mille' character", p),
+            D3084PercentAndPerMille(ref p) =>
+                write!(f, "{}: The sub-picture must not contain both a 'percent' and a 'per-mille' character", p),
+            D3085NoMantissaDigits(ref p) =>
+                write!(f, "{}: The mantissa part of a sub-picture must contain at least one character that is either an 'optional digit character' or a member of the 'decimal digit family'", p),
+            D3086PassiveBetweenActive(ref p) =>
+                write!(f, "{}: The sub-picture must not contain a passive character that is preceded by an active character and that is followed by another active character", p),
+            D3087GroupingAdjacentToDecimal(ref p) =>
+                write!(f, "{}: The sub-picture must not contain a 'grouping-separator' character that appears adjacent to a 'decimal-separator' character", p),
+            D3088GroupingAtEndOfInteger(ref p) =>
+                write!(f, "{}: The sub-picture must not contain a 'grouping-separator' at the end of the integer part", p),
+            D3089AdjacentGroupings(ref p) =>
+                write!(f, "{}: The sub-picture must not contain two adjacent instances of the 'grouping-separator' character", p),
+            D3090DigitBeforeOptionalDigit(ref p) =>
+                write!(f, "{}: The integer part of the sub-picture must not contain a member of the 'decimal digit family' that is followed by an instance of the 'optional digit character'", p),
+            D3091OptionalDigitBeforeDigit(ref p) =>
+                write!(f, "{}: The fractional part of the sub-picture must not contain an instance of the 'optional digit character' that is followed by a member of the 'decimal digit family'", p),
+            D3092ExponentWithPercent(ref p) =>
+                write!(f, "{}: A sub-picture that contains a 'percent' or 'per-mille' character must not contain a character treated as an 'exponent-separator'", p),
+            D3093InvalidExponent(ref p) =>
+                write!(f, "{}: The exponent part of the sub-picture must comprise only of one or more characters that are members of the 'decimal digit family'", p),
+            D3100RadixOutOfRange(ref p, ref r) =>
+                write!(f, "{}: The radix of the formatBase function must be between 2 and 36.  It was given {}", p, r),
+            D3120SyntaxErrorInEval(ref p, ref m) =>
+                write!(f, "{}: Syntax error in expression passed to function eval: {}", p, m),
+            D3121DynamicErrorInEval(ref p, ref m) =>
+                write!(f, "{}: Dynamic error evaluating the expression passed to function eval: {}", p, m),
+            D3130SequenceNotSupported(ref p, ref v) =>
+                write!(f, "{}: Formatting or parsing an integer as a sequence starting with {} is not supported by this implementation", p, v),
+            D3131MixedDecimalGroups(ref p) =>
+                write!(f, "{}: In a decimal digit pattern, all digits must be from the same decimal group", p),
+            D3132UnknownComponentSpecifier(ref m) =>
+                write!(f, "Unknown component specifier {} in date/time picture string", m),
             D3133PictureStringNameModifierError(ref m) =>
                 write!(f, "{}: The 'name' modifier can only be applied to months and days in the date/time picture string, not Y", m),
             D3134TooManyTzDigits(ref m) =>
                 write!(f, "{}: The timezone integer format specifier cannot have more than four digits", m),
             D3135PictureStringNoClosingBracketError(ref m) =>
                 write!(f, "{}: No matching closing bracket ']' in date/time picture string", m),
+            D3136PictureStringMissingComponents(ref m) =>
+                write!(f, "{}: The date/time picture string is missing specifiers required to parse the timestamp", m),
+            D3142PictureStringWidthTooLarge(ref w) =>
+                write!(f, "The width {} in the date/time picture string is more than the maximum of {}", w, crate::datetime::MAX_WIDTH),
             D3141Assert(ref m) =>
                 write!(f, "{}", m),
             D3137Error(ref m) =>
@@ -271,9 +439,13 @@ impl fmt::Display for Error {
                 write!(f, "{}: The $single() function expected exactly 1 matching result.  Instead it matched more.", m),
             D3139Error(ref m) =>
                 write!(f, "{}: The $single() function expected exactly 1 matching result.  Instead it matched 0.", m),
+            D3140MalformedUrl(ref p, ref n, ref v) =>
+                write!(f, "{}: Malformed URL passed to ${}(): {:?}", p, n, v),
             // Type errors
             T0410ArgumentNotValid(ref p, ref i, ref t) =>
                 write!(f, "{}: Argument {} of function {} does not match function signature", p, i, t),
+            T0411ContextValueNotCompatible(ref p, ref i, ref t) =>
+                write!(f, "{}: Context value is not a compatible type with argument {} of function {}", p, i, t),
             T0412ArgumentMustBeArrayOfType(ref p, ref i, ref t, ref ty) =>
                 write!(f, "{}: Argument {} of function {} must be an array of {}", p, i, t, ty),
             T1003NonStringKey(ref p, ref v) =>
@@ -282,6 +454,12 @@ impl fmt::Display for Error {
                 write!(f, "{}: Attempted to invoke a non-function. Did you mean ${}?", p, t),
             T1006InvokedNonFunction(ref p) =>
                 write!(f, "{}: Attempted to invoke a non-function", p),
+            T1007PartiallyAppliedNonFunctionSuggest(ref p, ref t) =>
+                write!(f, "{}: Attempted to partially apply a non-function. Did you mean ${}?", p, t),
+            T1008PartiallyAppliedNonFunction(ref p) =>
+                write!(f, "{}: Attempted to partially apply a non-function", p),
+            T1010MatcherInvalidResult(ref p, ref t) =>
+                write!(f, "{}: The matcher function argument passed to function {} does not return the correct object structure", p, t),
             T2001LeftSideNotNumber(ref p, ref o) =>
                 write!( f, "{}: The left side of the `{}` operator must evaluate to a number", p, o),
             T2002RightSideNotNumber(ref p, ref o) =>
@@ -310,26 +488,38 @@ impl fmt::Display for Error {
             U1001StackOverflow =>
                 write!(f, "Stack overflow error: Check for non-terminating recursive function.  Consider rewriting as tail-recursive."),
             U1001Timeout =>
-                write!(f, "Expression evaluation timeout: Check for infinite loop")
+                write!(f, "Expression evaluation timeout: Check for infinite loop"),
+            U1002RegexBudgetExceeded(ref p, ref l) =>
+                write!(f, "{}: Regular expression matching exceeded the {} allowed for this evaluation", p, l),
+
+            // Input errors
+            I0101UnserializableInput(ref m) =>
+                write!(f, "The input could not be converted to a value: {}", m),
+            I0201UnexpectedCharacter(ref p, ref e, ref c) =>
+                write!(f, "{}: Unexpected {:?} in the input, expected {}", p, c, e),
+            I0202UnexpectedEndOfInput(ref p, ref e) =>
+                write!(f, "{}: Expected {} before the end of the input", p, e),
+            I0203InvalidEscape(ref p) =>
+                write!(f, "{}: Invalid escape sequence in the input", p),
+            I0204InvalidUnicodeEscape(ref p) =>
+                write!(f, "{}: The escape sequence \\u must be followed by 4 hex digits, and surrogates must be paired", p),
+            I0205ControlCharacterInString(ref p) =>
+                write!(f, "{}: Control characters must be escaped in strings in the input", p),
+            I0206InvalidNumber(ref p) =>
+                write!(f, "{}: Invalid number in the input", p),
+            I0207NumberOutOfRange(ref p, ref n) =>
+                write!(f, "{}: Number out of range: {}", p, n),
         }
     }
 }
 
 // "S0205": "Unexpected token: {{token}}",
 // "S0206": "Unknown expression type: {{token}}",
-// "S0207": "Unexpected end of expression",
-// "S0217": "The object representing the 'parent' cannot be derived from this expression",
 
 // "S0301": "Empty regular expressions are not allowed",
 // "S0302": "No terminating / in regular expression",
-// "S0402": "Choice groups containing parameterized types are not supported",
-// "S0401": "Type parameters can only be applied to functions and arrays",
 // "S0500": "Attempted to evaluate an expression containing syntax error(s)",
-// "T0411": "Context value is not a compatible type with argument {{index}} of function {{token}}",
 // "D1004": "Regular expression matches zero length string",
-// "T1007": "Attempted to partially apply a non-function. Did you mean ${{{token}}}?",
-// "T1008": "Attempted to partially apply a non-function",
-// // "T1010": "The matcher function argument passed to function {{token}} does not return the correct object structure",
 // "D2005": "The left side of := must be a variable name (start with $)",  // defunct - replaced by S0212 parser error
 // define_error!(
 //     D2014,
@@ -342,31 +532,9 @@ impl fmt::Display for Error {
 // "D3020": "Third argument of split function must evaluate to a positive number",
 // "D3040": "Third argument of match function must evaluate to a positive number",
 // "D3050": "The second argument of reduce function must be a function with at least two arguments",
-// "D3080": "The picture string must only contain a maximum of two sub-pictures",
-// "D3081": "The sub-picture must not contain more than one instance of the 'decimal-separator' character",
-// "D3082": "The sub-picture must not contain more than one instance of the 'percent' character",
-// "D3083": "The sub-picture must not contain more than one instance of the 'per-mille' character",
-// "D3084": "The sub-picture must not contain both a 'percent' and a 'per-mille' character",
-// "D3085": "The mantissa part of a sub-picture must contain at least one character that is either an 'optional digit character' or a member of the 'decimal digit family'",
-// "D3086": "The sub-picture must not contain a passive character that is preceded by an active character and that is followed by another active character",
-// "D3087": "The sub-picture must not contain a 'grouping-separator' character that appears adjacent to a 'decimal-separator' character",
-// "D3088": "The sub-picture must not contain a 'grouping-separator' at the end of the integer part",
-// "D3089": "The sub-picture must not contain two adjacent instances of the 'grouping-separator' character",
-// "D3090": "The integer part of the sub-picture must not contain a member of the 'decimal digit family' that is followed by an instance of the 'optional digit character'",
-// "D3091": "The fractional part of the sub-picture must not contain an instance of the 'optional digit character' that is followed by a member of the 'decimal digit family'",
-// "D3092": "A sub-picture that contains a 'percent' or 'per-mille' character must not contain a character treated as an 'exponent-separator'",
-// "D3093": "The exponent part of the sub-picture must comprise only of one or more characters that are members of the 'decimal digit family'",
-// "D3100": "The radix of the formatBase function must be between 2 and 36.  It was given {{value}}",
 // "D3110": "The argument of the toMillis function must be an ISO 8601 formatted timestamp. Given {{value}}",
-// "D3120": "Syntax error in expression passed to function eval: {{value}}",
-// "D3121": "Dynamic error evaluating the expression passed to function eval: {{value}}",
-// "D3130": "Formatting or parsing an integer as a sequence starting with {{value}} is not supported by this implementation",
-// "D3131": "In a decimal digit pattern, all digits must be from the same decimal group",
-// "D3132": "Unknown component specifier {{value}} in date/time picture string",
 // "D3133": "The 'name' modifier can only be applied to months and days in the date/time picture string, not {{value}}",
 // "D3134": "The timezone integer format specifier cannot have more than four digits",
 // "D3135": "No matching closing bracket ']' in date/time picture string",
-// "D3136": "The date/time picture string is missing specifiers required to parse the timestamp",
 // "D3138": "The $single() function expected exactly 1 matching result.  Instead it matched more.",
 // "D3139": "The $single() function expected exactly 1 matching result.  Instead it matched 0.",
-// "D3140": "Malformed URL passed to ${{{functionName}}}(): {{value}}",
@@ -1,30 +1,87 @@
 pub mod frame;
 pub mod functions;
+pub mod matcher;
+pub mod regex_cache;
+pub mod signature;
 pub mod value;
 
 use frame::Frame;
 use functions::*;
+use regex_cache::RegexCache;
 use value::{ArrayFlags, Value};
 
 use bumpalo::collections::Vec as BumpVec;
 use bumpalo::Bump;
 use std::cell::RefCell;
-use std::collections::{hash_map, HashMap};
+use std::collections::{hash_map, HashMap, HashSet};
 use std::time::Instant;
 
 use super::parser::ast::*;
+use crate::language::{Language, Languages};
 use crate::{Error, Result};
 
+/// Limits on the work done by regular expressions in a single evaluation.
+///
+/// When there's a step limit, searches run on a backtracking matcher which counts its steps, so
+/// that a pathological search is stopped part of the way through. Any time limit is also checked
+/// as the steps are counted. Without a step limit, searches run on `regress`, which is faster but
+/// can't be stopped until a search finishes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RegexBudget {
+    /// The maximum number of steps taken by regular expression searches across the whole
+    /// evaluation. A step is one instruction of the backtracking matcher, such as matching a
+    /// character or trying an alternative.
+    pub max_steps: Option<usize>,
+    /// The maximum length in bytes of a string a regular expression is matched against.
+    pub max_input_length: Option<usize>,
+}
+
+/// Limits on a single evaluation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EvaluationOptions {
+    /// The maximum depth of the evaluation, beyond which it fails with a `U1001` error.
+    pub max_depth: Option<usize>,
+    /// The time limit of the evaluation in milliseconds, after which it fails with a `U1001`
+    /// error.
+    pub time_limit: Option<usize>,
+    /// The limits on regular expressions in the evaluation.
+    pub regex_budget: RegexBudget,
+}
+
+/// Statistics about the work done in an evaluation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvaluationStats {
+    /// The number of regular expressions compiled from strings which were found in the cache.
+    pub regex_cache_hits: usize,
+    /// The number of regular expressions compiled from strings which had to be compiled.
+    pub regex_cache_misses: usize,
+}
+
 struct EvaluatorInternal {
     depth: usize,
     started_at: Option<Instant>,
     max_depth: Option<usize>,
     time_limit: Option<usize>,
+    regex_budget: RegexBudget,
+    regex_steps: usize,
+}
+
+impl EvaluatorInternal {
+    fn check_timeout(&self) -> Result<()> {
+        if let (Some(started_at), Some(time_limit)) = (self.started_at, self.time_limit) {
+            if started_at.elapsed().as_millis() >= time_limit as u128 {
+                return Err(Error::U1001Timeout);
+            }
+        }
+        Ok(())
+    }
 }
 
 pub struct Evaluator<'a> {
     chain_ast: Option<Ast>,
     arena: &'a Bump,
+    languages: Languages,
+    regex_cache: RefCell<RegexCache<'a>>,
     internal: RefCell<EvaluatorInternal>,
 }
 
@@ -38,15 +95,34 @@ impl<'a> Evaluator<'a> {
         Evaluator {
             chain_ast,
             arena,
+            languages: Languages::default(),
+            regex_cache: RefCell::new(RegexCache::new(arena)),
             internal: RefCell::new(EvaluatorInternal {
                 depth: 0,
                 started_at: None,
                 max_depth,
                 time_limit,
+                regex_budget: RegexBudget::default(),
+                regex_steps: 0,
             }),
         }
     }
 
+    pub fn with_languages(mut self, languages: Languages) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    pub fn with_regex_budget(self, regex_budget: RegexBudget) -> Self {
+        self.internal.borrow_mut().regex_budget = regex_budget;
+        self
+    }
+
+    /// The language with a tag such as `es`, used for the names and words in dates.
+    pub fn language(&self, tag: &str) -> &dyn Language {
+        self.languages.get(tag)
+    }
+
     fn fn_context<'e>(
         &'e self,
         name: &'a str,
@@ -71,12 +147,8 @@ impl<'a> Evaluator<'a> {
         } else {
             internal.depth - 1
         };
-        if let Some(started_at) = internal.started_at {
-            if let Some(time_limit) = internal.time_limit {
-                if started_at.elapsed().as_millis() >= time_limit as u128 {
-                    return Err(Error::U1001Timeout);
-                }
-            }
+        if internal.started_at.is_some() {
+            internal.check_timeout()?;
         } else {
             internal.started_at = Some(Instant::now());
         }
@@ -88,6 +160,59 @@ impl<'a> Evaluator<'a> {
         Ok(())
     }
 
+    /// Compile a regular expression given as a string, reusing it if the same pattern was
+    /// compiled with the same flags earlier in the evaluation.
+    pub fn compile_regex(
+        &self,
+        pattern: &str,
+        case_insensitive: bool,
+        multi_line: bool,
+    ) -> std::result::Result<&'a RegexLiteral, regress::Error> {
+        self.regex_cache
+            .borrow_mut()
+            .get_or_compile(pattern, case_insensitive, multi_line)
+    }
+
+    pub fn stats(&self) -> EvaluationStats {
+        let regex_cache = self.regex_cache.borrow();
+        EvaluationStats {
+            regex_cache_hits: regex_cache.hits(),
+            regex_cache_misses: regex_cache.misses(),
+        }
+    }
+
+    /// Check that a regular expression may be matched against an input of `len` bytes.
+    pub fn check_regex_input(&self, char_index: usize, len: usize) -> Result<()> {
+        let internal = self.internal.borrow();
+        match internal.regex_budget.max_input_length {
+            Some(max_input_length) if len > max_input_length => Err(
+                Error::U1002RegexBudgetExceeded(char_index, "input length".to_string()),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether regular expression searches have to be able to stop part of the way through,
+    /// because the evaluation has a regex step budget.
+    pub fn bounds_regex(&self) -> bool {
+        self.internal.borrow().regex_budget.max_steps.is_some()
+    }
+
+    /// Charge `steps` taken by a regular expression search to the evaluation's regex budget.
+    pub fn charge_regex_steps(&self, char_index: usize, steps: usize) -> Result<()> {
+        let mut internal = self.internal.borrow_mut();
+        internal.regex_steps = internal.regex_steps.saturating_add(steps);
+        if let Some(max_steps) = internal.regex_budget.max_steps {
+            if internal.regex_steps > max_steps {
+                return Err(Error::U1002RegexBudgetExceeded(
+                    char_index,
+                    "number of steps".to_string(),
+                ));
+            }
+        }
+        internal.check_timeout()
+    }
+
     pub fn evaluate(
         &self,
         node: &Ast,
@@ -127,6 +252,7 @@ impl<'a> Evaluator<'a> {
             } => self.evaluate_function(input, proc, args, is_partial, frame, None)?,
             AstKind::Wildcard => self.evaluate_wildcard(input)?,
             AstKind::Descendent => self.evaluate_descendants(input)?,
+            AstKind::Parent(ref label) => frame.lookup(label).unwrap_or_else(Value::undefined),
             AstKind::Transform {
                 ref pattern,
                 ref update,
@@ -270,7 +396,10 @@ impl<'a> Evaluator<'a> {
             pub index: usize,
         }
 
-        let mut groups: HashMap<String, Group> = HashMap::new();
+        // The groups are kept in the order their keys were first seen, which is the order of the
+        // keys in the result
+        let mut groups: Vec<(String, Group)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
         let reduce = input.has_flags(ArrayFlags::TUPLE_STREAM);
 
         let input = if input.is_array() && input.is_empty() {
@@ -304,9 +433,9 @@ impl<'a> Evaluator<'a> {
 
                 let key = key.as_str();
 
-                match groups.entry(key.to_string()) {
-                    hash_map::Entry::Occupied(mut entry) => {
-                        let group = entry.get_mut();
+                match positions.entry(key.to_string()) {
+                    hash_map::Entry::Occupied(entry) => {
+                        let group = &mut groups[*entry.get()].1;
                         if group.index != index {
                             return Err(Error::D1009MultipleKeys(char_index, key.to_string()));
                         }
@@ -316,7 +445,8 @@ impl<'a> Evaluator<'a> {
                         )?;
                     }
                     hash_map::Entry::Vacant(entry) => {
-                        entry.insert(Group { data: item, index });
+                        entry.insert(groups.len());
+                        groups.push((key.to_string(), Group { data: item, index }));
                     }
                 };
             }
@@ -324,8 +454,7 @@ impl<'a> Evaluator<'a> {
 
         let result = Value::object(self.arena);
 
-        for key in groups.keys() {
-            let group = groups.get(key).unwrap();
+        for (key, group) in groups.iter() {
             let value = if reduce {
                 let tuple = self.reduce_tuple_stream(char_index, group.data, input, frame)?;
                 let context = tuple.get_entry("@");
@@ -558,47 +687,8 @@ impl<'a> Evaluator<'a> {
             )),
 
             BinaryOp::Apply => {
-                if let AstKind::Function {
-                    ref proc,
-                    ref args,
-                    is_partial,
-                    ..
-                } = rhs_ast.kind
-                {
-                    // Function invocation with lhs as the first argument
-                    Ok(self.evaluate_function(input, proc, args, is_partial, frame, Some(lhs))?)
-                } else {
-                    let rhs = self.evaluate(rhs_ast, input, frame)?;
-
-                    if !rhs.is_function() {
-                        return Err(Error::T2006RightSideNotFunction(rhs_ast.char_index));
-                    }
-
-                    if lhs.is_function() {
-                        // Apply function chaining
-                        let chain = self.evaluate(
-                            self.chain_ast.as_ref().unwrap(),
-                            Value::undefined(),
-                            frame,
-                        )?;
-
-                        Ok(self.apply_function(
-                            lhs_ast.char_index,
-                            Value::undefined(),
-                            chain,
-                            &[lhs, rhs],
-                            frame,
-                        )?)
-                    } else {
-                        Ok(self.apply_function(
-                            rhs_ast.char_index,
-                            Value::undefined(),
-                            rhs,
-                            &[lhs],
-                            frame,
-                        )?)
-                    }
-                }
+                let result = self.evaluate_apply(lhs, lhs_ast, rhs_ast, input, frame)?;
+                self.trampoline_evaluate_value(result)
             }
 
             BinaryOp::In => {
@@ -686,7 +776,10 @@ impl<'a> Evaluator<'a> {
                 break;
             }
 
-            input = result
+            // Steps that bind the focus keep the same context for the next step
+            if step.focus.is_none() {
+                input = result
+            }
         }
 
         if is_tuple_stream {
@@ -873,6 +966,9 @@ impl<'a> Evaluator<'a> {
                             output_tuple
                                 .insert(index_var, Value::number(self.arena, binding_index as f64));
                         }
+                        for label in step.ancestors.iter() {
+                            output_tuple.insert(label, &tuple["@"]);
+                        }
                     }
                     result.push(output_tuple);
                 }
@@ -906,27 +1002,41 @@ impl<'a> Evaluator<'a> {
             ));
         }
 
-        let unsorted = input.members().collect::<Vec<&'a Value<'a>>>();
+        let members = input.members().collect::<Vec<&'a Value<'a>>>();
         let is_tuple_sort = input.has_flags(ArrayFlags::TUPLE_STREAM);
 
-        let comp = |a: &'a Value<'a>, b: &'a Value<'a>| {
+        // The sort keys are evaluated the first time they're compared, and then reused for the
+        // rest of the sort
+        let keys: RefCell<Vec<Option<&'a Value<'a>>>> =
+            RefCell::new(vec![None; members.len() * sort_terms.len()]);
+        let key = |index: usize, term: usize| -> Result<&'a Value<'a>> {
+            if let Some(key) = keys.borrow()[index * sort_terms.len() + term] {
+                return Ok(key);
+            }
+            let member = members[index];
+            let key = if is_tuple_sort {
+                let tuple_frame = Frame::from_tuple(frame, member);
+                self.evaluate(&sort_terms[term].0, &member["@"], &tuple_frame)?
+            } else {
+                self.evaluate(&sort_terms[term].0, member, frame)?
+            };
+            keys.borrow_mut()[index * sort_terms.len() + term] = Some(key);
+            Ok(key)
+        };
+
+        let comp = |a: usize, b: usize| {
             let mut result = 0;
 
-            for (sort_term, descending) in sort_terms {
-                let aa = if is_tuple_sort {
-                    let tuple_frame = Frame::from_tuple(frame, a);
-                    self.evaluate(sort_term, &a["@"], &tuple_frame)?
-                } else {
-                    self.evaluate(sort_term, a, frame)?
-                };
+            for (term, (_, descending)) in sort_terms.iter().enumerate() {
+                // Later terms only break ties in the earlier ones
+                if result != 0 {
+                    break;
+                }
 
-                let bb = if is_tuple_sort {
-                    let tuple_frame = Frame::from_tuple(frame, b);
-                    self.evaluate(sort_term, &b["@"], &tuple_frame)?
-                } else {
-                    self.evaluate(sort_term, b, frame)?
-                };
+                let aa = key(a, term)?;
+                let bb = key(b, term)?;
 
+                // Undefined keys sort last, whatever the direction
                 if aa.is_undefined() {
                     result = if bb.is_undefined() { 0 } else { 1 };
                     continue;
@@ -963,8 +1073,8 @@ impl<'a> Evaluator<'a> {
                     _ => {
                         return Err(Error::T2007CompareTypeMismatch(
                             char_index,
-                            a.to_string(),
-                            b.to_string(),
+                            aa.to_string(),
+                            bb.to_string(),
                         ));
                     }
                 };
@@ -977,9 +1087,10 @@ impl<'a> Evaluator<'a> {
             Ok(result == 1)
         };
 
-        let sorted = merge_sort(unsorted, &comp)?;
-        let result = Value::array_with_capacity(self.arena, sorted.len(), input.get_flags());
-        sorted.iter().for_each(|member| result.push(member));
+        let mut order = (0..members.len()).collect::<Vec<usize>>();
+        merge_sort(&mut order, &comp)?;
+        let result = Value::array_with_capacity(self.arena, order.len(), input.get_flags());
+        order.iter().for_each(|&index| result.push(members[index]));
 
         Ok(result)
     }
@@ -1156,12 +1267,71 @@ impl<'a> Evaluator<'a> {
         result_sequence
     }
 
+    /// Evaluate `lhs ~> rhs`, where `lhs` has already been evaluated. The result may be a thunk
+    /// for a tail call, which needs to be trampolined.
+    fn evaluate_apply(
+        &self,
+        lhs: &'a Value<'a>,
+        lhs_ast: &Ast,
+        rhs_ast: &Ast,
+        input: &'a Value<'a>,
+        frame: &Frame<'a>,
+    ) -> Result<&'a Value<'a>> {
+        if let AstKind::Function {
+            ref proc,
+            ref args,
+            is_partial,
+            ..
+        } = rhs_ast.kind
+        {
+            // Function invocation with lhs as the first argument
+            return self.evaluate_call(input, proc, args, is_partial, frame, Some(lhs));
+        }
+
+        let rhs = self.evaluate(rhs_ast, input, frame)?;
+
+        if !rhs.is_function() {
+            return Err(Error::T2006RightSideNotFunction(rhs_ast.char_index));
+        }
+
+        if lhs.is_function() {
+            // Apply function chaining
+            let chain =
+                self.evaluate(self.chain_ast.as_ref().unwrap(), Value::undefined(), frame)?;
+
+            self.call_function(
+                lhs_ast.char_index,
+                Value::undefined(),
+                chain,
+                &[lhs, rhs],
+                frame,
+            )
+        } else {
+            self.call_function(rhs_ast.char_index, Value::undefined(), rhs, &[lhs], frame)
+        }
+    }
+
     fn evaluate_function(
         &self,
         input: &'a Value<'a>,
         proc: &Ast,
         args: &[Ast],
-        _is_partial: bool,
+        is_partial: bool,
+        frame: &Frame<'a>,
+        context: Option<&'a Value<'a>>,
+    ) -> Result<&'a Value<'a>> {
+        let result = self.evaluate_call(input, proc, args, is_partial, frame, context)?;
+        self.trampoline_evaluate_value(result)
+    }
+
+    /// Evaluate a function call. The result may be a thunk for a tail call made by the function,
+    /// which needs to be trampolined.
+    fn evaluate_call(
+        &self,
+        input: &'a Value<'a>,
+        proc: &Ast,
+        args: &[Ast],
+        is_partial: bool,
         frame: &Frame<'a>,
         context: Option<&'a Value<'a>>,
     ) -> Result<&'a Value<'a>> {
@@ -1172,15 +1342,32 @@ impl<'a> Evaluator<'a> {
             if let AstKind::Path(ref steps) = proc.kind {
                 if let AstKind::Name(ref name) = steps[0].kind {
                     if frame.lookup(name).is_some() {
-                        return Err(Error::T1005InvokedNonFunctionSuggest(
-                            proc.char_index,
-                            name.clone(),
-                        ));
+                        return Err(if is_partial {
+                            Error::T1007PartiallyAppliedNonFunctionSuggest(
+                                proc.char_index,
+                                name.clone(),
+                            )
+                        } else {
+                            Error::T1005InvokedNonFunctionSuggest(proc.char_index, name.clone())
+                        });
                     }
                 }
             }
         }
 
+        if is_partial {
+            let partial =
+                self.partially_apply_function(proc.char_index, input, evaluated_proc, args, frame)?;
+
+            // When chained with `~>`, the lhs is the first argument to the new function
+            return match context {
+                Some(context) => {
+                    self.call_function(proc.char_index, input, partial, &[context], frame)
+                }
+                None => Ok(partial),
+            };
+        }
+
         let mut evaluated_args = Vec::with_capacity(args.len());
 
         if let Some(context) = context {
@@ -1192,66 +1379,155 @@ impl<'a> Evaluator<'a> {
             evaluated_args.push(arg);
         }
 
-        let result = self.apply_function(
+        self.call_function(
             proc.char_index,
             input,
             evaluated_proc,
             &evaluated_args,
             frame,
-        )?;
-
-        let result = self.trampoline_evaluate_value(result, input, frame)?;
-
-        Ok(result)
+        )
     }
 
-    /// Iteratively evaluate a function until a non-function value is returned.
-    fn trampoline_evaluate_value(
+    /// Partially apply a function, returning a new lambda which takes the `?` placeholders as
+    /// its parameters, and has the rest of the arguments bound in its frame.
+    ///
+    /// Native functions are first wrapped in a lambda that passes all of its parameters on.
+    fn partially_apply_function(
         &self,
-        mut result: &'a Value<'a>,
+        char_index: usize,
         input: &'a Value<'a>,
+        proc: &'a Value<'a>,
+        args: &[Ast],
         frame: &Frame<'a>,
     ) -> Result<&'a Value<'a>> {
-        // Trampoline loop for tail-call optimization
-        // TODO: This loop needs help
+        let (params, body, lambda_input, lambda_frame) = match proc {
+            Value::Lambda {
+                ref ast,
+                input: lambda_input,
+                frame: ref lambda_frame,
+            } => {
+                if let AstKind::Lambda {
+                    ref args, ref body, ..
+                } = ast.kind
+                {
+                    (
+                        args.clone(),
+                        body.clone(),
+                        *lambda_input,
+                        Frame::new_with_parent(lambda_frame),
+                    )
+                } else {
+                    unreachable!()
+                }
+            }
+            Value::NativeFn {
+                ref name, arity, ..
+            } => {
+                let lambda_frame = Frame::new_with_parent(frame);
+                lambda_frame.bind("!fn", proc);
+
+                let params: Vec<Ast> = (0..*arity.max(&args.len()))
+                    .map(|index| Ast::new(AstKind::Var(format!("!{}", index)), char_index))
+                    .collect();
+
+                let body = Ast::new(
+                    AstKind::Function {
+                        name: name.clone(),
+                        proc: Box::new(Ast::new(AstKind::Var("!fn".to_string()), char_index)),
+                        args: params.clone(),
+                        is_partial: false,
+                    },
+                    char_index,
+                );
+
+                (params, Box::new(body), input, lambda_frame)
+            }
+            _ => return Err(Error::T1008PartiallyAppliedNonFunction(char_index)),
+        };
+
+        let mut unbound_params = Vec::new();
+
+        for (index, param) in params.into_iter().enumerate() {
+            let value = match args.get(index) {
+                Some(Ast {
+                    kind: AstKind::PartialArg,
+                    ..
+                }) => {
+                    unbound_params.push(param);
+                    continue;
+                }
+                Some(arg) => self.evaluate(arg, input, frame)?,
+                None => Value::undefined(),
+            };
+
+            if let AstKind::Var(ref name) = param.kind {
+                lambda_frame.bind(name, value);
+            } else {
+                unreachable!()
+            }
+        }
+
+        let lambda = Ast::new(
+            AstKind::Lambda {
+                name: String::new(),
+                args: unbound_params,
+                body,
+                signature: None,
+                thunk: false,
+            },
+            char_index,
+        );
+
+        Ok(Value::lambda(
+            self.arena,
+            &lambda,
+            lambda_input,
+            lambda_frame,
+        ))
+    }
+
+    /// Evaluate the tail calls returned as thunks by functions until a value which isn't a thunk
+    /// is returned.
+    ///
+    /// Each tail call is made from here rather than from inside the function which made it, so
+    /// that recursion in tail position runs in constant native stack and evaluation depth.
+    fn trampoline_evaluate_value(&self, mut result: &'a Value<'a>) -> Result<&'a Value<'a>> {
         while let Value::Lambda {
             ref ast,
             input: lambda_input,
             frame: ref lambda_frame,
-            ..
         } = result
         {
-            if let AstKind::Lambda {
+            let AstKind::Lambda {
                 ref body,
                 thunk: true,
                 ..
             } = ast.kind
-            {
-                if let AstKind::Function {
-                    ref proc, ref args, ..
-                } = body.kind
-                {
-                    let next = self.evaluate(proc, lambda_input, lambda_frame)?;
-                    let mut evaluated_args = Vec::with_capacity(args.len());
-
-                    for arg in args {
-                        let arg = self.evaluate(arg, lambda_input, lambda_frame)?;
-                        evaluated_args.push(arg);
-                    }
+            else {
+                break;
+            };
 
-                    result =
-                        self.apply_function(proc.char_index, input, next, &evaluated_args, frame)?;
-                } else {
-                    unreachable!()
+            result = match body.kind {
+                AstKind::Function {
+                    ref proc,
+                    ref args,
+                    is_partial,
+                    ..
+                } => {
+                    self.evaluate_call(lambda_input, proc, args, is_partial, lambda_frame, None)?
                 }
-            } else {
-                break;
-            }
+                AstKind::Binary(BinaryOp::Apply, ref lhs_ast, ref rhs_ast) => {
+                    let lhs = self.evaluate(lhs_ast, lambda_input, lambda_frame)?;
+                    self.evaluate_apply(lhs, lhs_ast, rhs_ast, lambda_input, lambda_frame)?
+                }
+                _ => unreachable!(),
+            };
         }
 
         Ok(result)
     }
 
+    /// Apply a function to its arguments, evaluating any tail calls it makes.
     pub fn apply_function(
         &self,
         char_index: usize,
@@ -1259,20 +1535,54 @@ impl<'a> Evaluator<'a> {
         evaluated_proc: &'a Value<'a>,
         evaluated_args: &[&'a Value<'a>],
         frame: &Frame<'a>,
+    ) -> Result<&'a Value<'a>> {
+        let result =
+            self.call_function(char_index, input, evaluated_proc, evaluated_args, frame)?;
+        self.trampoline_evaluate_value(result)
+    }
+
+    /// Apply a function to its arguments. The result may be a thunk for a tail call made by the
+    /// function, which needs to be trampolined.
+    fn call_function(
+        &self,
+        char_index: usize,
+        input: &'a Value<'a>,
+        evaluated_proc: &'a Value<'a>,
+        evaluated_args: &[&'a Value<'a>],
+        frame: &Frame<'a>,
     ) -> Result<&'a Value<'a>> {
         match evaluated_proc {
             Value::Lambda {
                 ref ast,
-                ref frame,
-                input,
+                frame: ref lambda_frame,
+                input: lambda_input,
                 ..
             } => {
                 if let AstKind::Lambda {
-                    ref body, ref args, ..
+                    ref name,
+                    ref body,
+                    ref args,
+                    ref signature,
+                    ..
                 } = ast.kind
                 {
+                    let validated_args;
+                    let evaluated_args = match signature {
+                        Some(signature) => {
+                            validated_args = signature.validate(
+                                self.arena,
+                                char_index,
+                                name,
+                                evaluated_args,
+                                input,
+                            )?;
+                            &validated_args[..]
+                        }
+                        None => evaluated_args,
+                    };
+
                     // Create a new frame for use in the lambda, so it can have locals
-                    let frame = Frame::new_with_parent(frame);
+                    let frame = Frame::new_with_parent(lambda_frame);
 
                     // Bind the arguments to their respective names
                     for (index, arg) in args.iter().enumerate() {
@@ -1290,16 +1600,31 @@ impl<'a> Evaluator<'a> {
                     }
 
                     // Evaluate the lambda!
-                    self.evaluate(body, input, &frame)
+                    self.evaluate(body, lambda_input, &frame)
                 } else {
                     unreachable!()
                 }
             }
             Value::NativeFn {
-                ref name, ref func, ..
+                ref name,
+                ref signature,
+                ref func,
+                ..
             } => {
                 let context = self.fn_context(name, char_index, input, frame);
-                func(context, evaluated_args)
+                match signature {
+                    Some(signature) => {
+                        let validated_args = signature.validate(
+                            self.arena,
+                            char_index,
+                            name,
+                            evaluated_args,
+                            input,
+                        )?;
+                        func(context, &validated_args)
+                    }
+                    None => func(context, evaluated_args),
+                }
             }
             Value::Transformer {
                 ref pattern,
@@ -1329,59 +1654,190 @@ impl<'a> Evaluator<'a> {
             return Err(Error::T0410ArgumentNotValid(
                 pattern_ast.char_index,
                 1,
-                "undefined".to_string(),
+                "transform".to_string(),
             ));
         }
 
-        let result = input.clone(self.arena);
+        let input = self.unshare(input, &mut HashSet::new());
 
         let matches = self.evaluate(
             pattern_ast,
-            Value::wrap_in_array(self.arena, result, ArrayFlags::empty()),
+            Value::wrap_in_array(self.arena, input, ArrayFlags::empty()),
             frame,
         )?;
 
-        if !matches.is_undefined() {
-            let matches = Value::wrap_in_array_if_needed(self.arena, matches, ArrayFlags::empty());
-            for m in matches.members() {
-                let update = self.evaluate(update_ast, m, frame)?;
-                if !update.is_undefined() {
-                    if !update.is_object() {
-                        return Err(Error::T2011UpdateNotObject(
-                            update_ast.char_index,
-                            update.to_string(),
-                        ));
-                    } else {
-                        for (key, value) in update.entries() {
-                            m.__very_unsafe_make_mut().insert(key, value);
-                        }
+        if matches.is_undefined() {
+            return Ok(input);
+        }
+
+        // The input is never modified. Instead, each matched object is copied with its updates
+        // and deletions applied, and the copies are then put in place of the originals in a copy
+        // of the input which shares everything left unchanged.
+        let mut updated: HashMap<*const Value<'a>, &'a Value<'a>> = HashMap::new();
+
+        let matches = Value::wrap_in_array_if_needed(self.arena, matches, ArrayFlags::empty());
+        for m in matches.members() {
+            let original = m as *const Value<'a>;
+            let mut current = updated.get(&original).copied().unwrap_or(m);
+
+            let update = self.evaluate(update_ast, current, frame)?;
+            if !update.is_undefined() {
+                if !update.is_object() {
+                    return Err(Error::T2011UpdateNotObject(
+                        update_ast.char_index,
+                        update.to_string(),
+                    ));
+                }
+                if current.is_object() {
+                    let copy = current.clone(self.arena);
+                    for (key, value) in update.entries() {
+                        copy.insert(key, value);
                     }
+                    current = copy;
                 }
+            }
 
-                if let Some(delete_ast) = delete_ast {
-                    let deletions = self.evaluate(delete_ast, m, frame)?;
-                    if !deletions.is_undefined() {
-                        let deletions = Value::wrap_in_array_if_needed(
-                            self.arena,
-                            deletions,
-                            ArrayFlags::empty(),
-                        );
+            if let Some(delete_ast) = delete_ast {
+                let deletions = self.evaluate(delete_ast, current, frame)?;
+                if !deletions.is_undefined() {
+                    let deletions =
+                        Value::wrap_in_array_if_needed(self.arena, deletions, ArrayFlags::empty());
+                    if !deletions.is_array_of_strings() {
+                        return Err(Error::T2012DeleteNotStrings(
+                            delete_ast.char_index,
+                            deletions.to_string(),
+                        ));
+                    }
+                    if current.is_object() {
+                        let copy = current.clone(self.arena);
                         for deletion in deletions.members() {
-                            if !deletion.is_string() {
-                                return Err(Error::T2012DeleteNotStrings(
-                                    delete_ast.char_index,
-                                    deletions.to_string(),
-                                ));
-                            }
-                            if m.is_object() {
-                                m.__very_unsafe_make_mut().remove(&deletion.as_str());
-                            }
+                            copy.remove(&deletion.as_str());
                         }
+                        current = copy;
                     }
                 }
             }
+
+            if !std::ptr::eq(current, m) {
+                updated.insert(original, current);
+            }
         }
 
-        Ok(result)
+        if updated.is_empty() {
+            return Ok(input);
+        }
+
+        Ok(self.replace_updated(input, &updated))
+    }
+
+    /// Copy any object or array which appears more than once within `value`, so that each place
+    /// in it can be told apart by address, and a transform only updates the places it matches.
+    fn unshare(&self, value: &'a Value<'a>, seen: &mut HashSet<*const Value<'a>>) -> &'a Value<'a> {
+        match value {
+            Value::Array(..) => {
+                let mut changed = !seen.insert(value);
+                let members = value
+                    .members()
+                    .map(|member| {
+                        let unshared = self.unshare(member, seen);
+                        changed |= !std::ptr::eq(unshared, member);
+                        unshared
+                    })
+                    .collect::<Vec<_>>();
+
+                if !changed {
+                    return value;
+                }
+
+                let result =
+                    Value::array_with_capacity(self.arena, members.len(), value.get_flags());
+                members.into_iter().for_each(|member| result.push(member));
+                result
+            }
+            Value::Object(..) => {
+                let mut changed = !seen.insert(value);
+                let entries = value
+                    .entries()
+                    .map(|(key, entry)| {
+                        let unshared = self.unshare(entry, seen);
+                        changed |= !std::ptr::eq(unshared, *entry);
+                        (key, unshared)
+                    })
+                    .collect::<Vec<_>>();
+
+                if !changed {
+                    return value;
+                }
+
+                let result = Value::object_with_capacity(self.arena, entries.len());
+                entries
+                    .into_iter()
+                    .for_each(|(key, entry)| result.insert(key, entry));
+                result
+            }
+            _ => value,
+        }
+    }
+
+    /// Copy `value`, replacing any of the objects within it with their updated versions, and
+    /// sharing the parts which contain no updates.
+    fn replace_updated(
+        &self,
+        value: &'a Value<'a>,
+        updated: &HashMap<*const Value<'a>, &'a Value<'a>>,
+    ) -> &'a Value<'a> {
+        match value {
+            Value::Array(..) => {
+                let mut changed = false;
+                let members = value
+                    .members()
+                    .map(|member| {
+                        let replaced = self.replace_updated(member, updated);
+                        changed |= !std::ptr::eq(replaced, member);
+                        replaced
+                    })
+                    .collect::<Vec<_>>();
+
+                if !changed {
+                    return value;
+                }
+
+                let result =
+                    Value::array_with_capacity(self.arena, members.len(), value.get_flags());
+                members.into_iter().for_each(|member| result.push(member));
+                result
+            }
+            Value::Object(..) => {
+                let update = updated.get(&(value as *const Value<'a>)).copied();
+                let base = update.unwrap_or(value);
+
+                // Values set by the update are kept as they are, only the original ones can
+                // contain further updates
+                let mut changed = false;
+                let entries = base
+                    .entries()
+                    .map(|(key, entry)| {
+                        let replaced = if std::ptr::eq(*entry, value.get_entry(key)) {
+                            self.replace_updated(entry, updated)
+                        } else {
+                            entry
+                        };
+                        changed |= !std::ptr::eq(replaced, *entry);
+                        (key, replaced)
+                    })
+                    .collect::<Vec<_>>();
+
+                if !changed {
+                    return base;
+                }
+
+                let result = Value::object_with_capacity(self.arena, entries.len());
+                entries
+                    .into_iter()
+                    .for_each(|(key, entry)| result.insert(key, entry));
+                result
+            }
+            _ => value,
+        }
     }
 }
@@ -1,25 +1,26 @@
 use base64::Engine;
 use chrono::{TimeZone, Utc};
-use hashbrown::{DefaultHashBuilder, HashMap};
+use rand::seq::SliceRandom;
 use rand::Rng;
-use regress::{Range, Regex};
 use std::borrow::{Borrow, Cow};
 use std::collections::HashSet;
 use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
-use crate::datetime::{format_custom_date, parse_custom_format, parse_timezone_offset};
-use crate::evaluator::RegexLiteral;
+use crate::datetime::{format_custom_date, parse_custom_format, Zone};
+use crate::decimal_format::{format_number, DecimalFormat};
+use crate::integer_format::IntegerFormat;
 use crate::parser::expressions::check_balanced_brackets;
+use crate::parser::parse;
 
 use bumpalo::collections::CollectIn;
-use bumpalo::collections::String as BumpString;
 use bumpalo::collections::Vec as BumpVec;
 use bumpalo::Bump;
 
 use crate::{Error, Result};
 
 use super::frame::Frame;
+use super::matcher::{Match, Matcher};
 use super::value::serialize::{DumpFormatter, PrettyFormatter, Serializer};
 use super::value::{ArrayFlags, Value};
 use super::Evaluator;
@@ -101,8 +102,28 @@ impl<'a, 'e> FunctionContext<'a, 'e> {
     }
 
     pub fn trampoline_evaluate_value(&self, value: &'a Value<'a>) -> Result<&'a Value<'a>> {
-        self.evaluator
-            .trampoline_evaluate_value(value, self.input, &self.frame)
+        self.evaluator.trampoline_evaluate_value(value)
+    }
+
+    /// Call a function passed to a higher-order function, such as the callback of `$map`, with
+    /// as many of `args` as it declares parameters for, but always with the first.
+    ///
+    /// This means a callback declared as `function($v)`, or a native function like `$string`,
+    /// is only passed the value even though `$map` also offers the index and the array. The
+    /// arity of a native function with a signature excludes its `?` parameters.
+    pub fn call_callback(
+        &self,
+        func: &'a Value<'a>,
+        args: &[&'a Value<'a>],
+    ) -> Result<&'a Value<'a>> {
+        let arity = match func {
+            Value::NativeFn {
+                signature: Some(ref signature),
+                ..
+            } => signature.callback_arity(),
+            _ => func.arity(),
+        };
+        self.evaluate_function(func, &args[..arity.max(1).min(args.len())])
     }
 }
 
@@ -220,18 +241,8 @@ pub fn fn_map<'a>(
     let result = Value::array(context.arena, ArrayFlags::SEQUENCE);
 
     for (index, item) in arr.members().enumerate() {
-        let mut args = Vec::new();
-        let arity = func.arity();
-
-        args.push(item);
-        if arity >= 2 {
-            args.push(Value::number(context.arena, index as f64));
-        }
-        if arity >= 3 {
-            args.push(arr);
-        }
-
-        let mapped = context.trampoline_evaluate_value(context.evaluate_function(func, &args)?)?;
+        let index = Value::number(context.arena, index as f64);
+        let mapped = context.call_callback(func, &[item, index, arr])?;
 
         if !mapped.is_undefined() {
             result.push(mapped);
@@ -259,18 +270,8 @@ pub fn fn_filter<'a>(
     let result = Value::array(context.arena, ArrayFlags::SEQUENCE);
 
     for (index, item) in arr.members().enumerate() {
-        let mut args = Vec::new();
-        let arity = func.arity();
-
-        args.push(item);
-        if arity >= 2 {
-            args.push(Value::number(context.arena, index as f64));
-        }
-        if arity >= 3 {
-            args.push(arr);
-        }
-
-        let include = context.evaluate_function(func, &args)?;
+        let index = Value::number(context.arena, index as f64);
+        let include = context.call_callback(func, &[item, index, arr])?;
 
         if include.is_truthy() {
             result.push(item);
@@ -308,7 +309,7 @@ pub fn fn_each<'a>(
     for (key, value) in obj.entries() {
         let key = Value::string(context.arena, key);
 
-        let mapped = context.evaluate_function(func, &[value, key])?;
+        let mapped = context.call_callback(func, &[value, key, obj])?;
         if !mapped.is_undefined() {
             result.push(mapped);
         }
@@ -317,6 +318,93 @@ pub fn fn_each<'a>(
     Ok(result)
 }
 
+pub fn fn_sift<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 2);
+
+    let (obj, func) = if args.len() == 1 {
+        let obj_arg = if context.input.is_array() && context.input.has_flags(ArrayFlags::WRAPPED) {
+            &context.input[0]
+        } else {
+            context.input
+        };
+
+        (obj_arg, args[0])
+    } else {
+        (
+            args.first().copied().unwrap_or_else(Value::undefined),
+            args.get(1).copied().unwrap_or_else(Value::undefined),
+        )
+    };
+
+    if obj.is_undefined() {
+        return Ok(Value::undefined());
+    }
+
+    assert_arg!(obj.is_object(), context, 1);
+    assert_arg!(func.is_function(), context, 2);
+
+    let result = Value::object(context.arena);
+
+    for (key, value) in obj.entries() {
+        let key_value = Value::string(context.arena, key);
+        let include = context.call_callback(func, &[value, key_value, obj])?;
+
+        if include.is_truthy() {
+            result.insert(key, value);
+        }
+    }
+
+    if result.entries().len() == 0 {
+        Ok(Value::undefined())
+    } else {
+        Ok(result)
+    }
+}
+
+pub fn fn_spread<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 1);
+
+    let arg = if args.is_empty() {
+        if context.input.is_array() && context.input.has_flags(ArrayFlags::WRAPPED) {
+            &context.input[0]
+        } else {
+            context.input
+        }
+    } else {
+        args[0]
+    };
+
+    Ok(spread_internal(context.arena, arg))
+}
+
+fn spread_internal<'a>(arena: &'a Bump, arg: &'a Value<'a>) -> &'a Value<'a> {
+    match arg {
+        Value::Array(..) | Value::Range(..) => {
+            let mut result = BumpVec::new_in(arena);
+            for member in arg.members() {
+                fn_append_internal(&mut result, spread_internal(arena, member));
+            }
+            Value::array_from(arena, result, ArrayFlags::SEQUENCE)
+        }
+        Value::Object(..) => {
+            let result = Value::array(arena, ArrayFlags::SEQUENCE);
+            for (key, value) in arg.entries() {
+                let obj = Value::object(arena);
+                obj.insert(key, value);
+                result.push(obj);
+            }
+            result
+        }
+        _ => arg,
+    }
+}
+
 pub fn fn_keys<'a>(
     context: FunctionContext<'a, '_>,
     args: &[&'a Value<'a>],
@@ -400,6 +488,31 @@ pub fn fn_merge<'a>(
     Ok(result)
 }
 
+pub fn fn_type_of<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 1);
+
+    let arg = args.first().copied().unwrap_or_else(Value::undefined);
+
+    let type_name = match arg {
+        Value::Undefined => return Ok(Value::undefined()),
+        Value::Null => "null",
+        Value::Number(..) => "number",
+        Value::String(..) => "string",
+        Value::Bool(..) => "boolean",
+        Value::Array(..) | Value::Range(..) => "array",
+        Value::Object(..) => "object",
+        Value::Regex(..)
+        | Value::Lambda { .. }
+        | Value::NativeFn { .. }
+        | Value::Transformer { .. } => "function",
+    };
+
+    Ok(Value::string(context.arena, type_name))
+}
+
 pub fn fn_string<'a>(
     context: FunctionContext<'a, '_>,
     args: &[&'a Value<'a>],
@@ -440,22 +553,54 @@ pub fn fn_string<'a>(
     }
 }
 
+/// The string and characters arguments of `$substringBefore` and `$substringAfter`, where the
+/// string is taken from the context when only the characters are given.
+fn substring_args<'a>(
+    context: &FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<(&'a Value<'a>, &'a Value<'a>)> {
+    if args.len() == 1 {
+        let input = if context.input.is_array() && context.input.has_flags(ArrayFlags::WRAPPED) {
+            &context.input[0]
+        } else {
+            context.input
+        };
+        if !input.is_string() {
+            return Err(Error::T0411ContextValueNotCompatible(
+                context.char_index,
+                1,
+                context.name.to_string(),
+            ));
+        }
+        return Ok((input, args[0]));
+    }
+
+    Ok((
+        args.first().copied().unwrap_or_else(Value::undefined),
+        args.get(1).copied().unwrap_or_else(Value::undefined),
+    ))
+}
+
 pub fn fn_substring_before<'a>(
     context: FunctionContext<'a, '_>,
     args: &[&'a Value<'a>],
 ) -> Result<&'a Value<'a>> {
-    let string = args.first().copied().unwrap_or_else(Value::undefined);
+    max_args!(context, args, 2);
 
-    let chars = args.get(1).copied().unwrap_or_else(Value::undefined);
+    let (string, chars) = substring_args(&context, args)?;
 
-    if !string.is_string() {
+    if string.is_undefined() {
         return Ok(Value::undefined());
     }
 
-    if !chars.is_string() {
-        return Err(Error::D3010EmptyPattern(context.char_index));
+    assert_arg!(string.is_string(), context, 1);
+
+    if chars.is_undefined() {
+        return Ok(string);
     }
 
+    assert_arg!(chars.is_string(), context, 2);
+
     let string: &str = &string.as_str();
     let chars: &str = &chars.as_str();
 
@@ -470,18 +615,22 @@ pub fn fn_substring_after<'a>(
     context: FunctionContext<'a, '_>,
     args: &[&'a Value<'a>],
 ) -> Result<&'a Value<'a>> {
-    let string = args.first().copied().unwrap_or_else(Value::undefined);
+    max_args!(context, args, 2);
 
-    let chars = args.get(1).copied().unwrap_or_else(Value::undefined);
+    let (string, chars) = substring_args(&context, args)?;
 
-    if !string.is_string() {
+    if string.is_undefined() {
         return Ok(Value::undefined());
     }
 
-    if !chars.is_string() {
-        return Err(Error::D3010EmptyPattern(context.char_index));
+    assert_arg!(string.is_string(), context, 1);
+
+    if chars.is_undefined() {
+        return Ok(string);
     }
 
+    assert_arg!(chars.is_string(), context, 2);
+
     let string: &str = &string.as_str();
     let chars: &str = &chars.as_str();
 
@@ -511,6 +660,8 @@ pub fn fn_lowercase<'a>(
     context: FunctionContext<'a, '_>,
     args: &[&'a Value<'a>],
 ) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 1);
+
     let arg = args.first().copied().unwrap_or_else(Value::undefined);
 
     Ok(if !arg.is_string() {
@@ -524,6 +675,8 @@ pub fn fn_uppercase<'a>(
     context: FunctionContext<'a, '_>,
     args: &[&'a Value<'a>],
 ) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 1);
+
     let arg = args.first().copied().unwrap_or_else(Value::undefined);
 
     if !arg.is_string() {
@@ -566,6 +719,8 @@ pub fn fn_substring<'a>(
     context: FunctionContext<'a, '_>,
     args: &[&'a Value<'a>],
 ) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 3);
+
     let string = args.first().copied().unwrap_or_else(Value::undefined);
     let start = args.get(1).copied().unwrap_or_else(Value::undefined);
     let length = args.get(2).copied().unwrap_or_else(Value::undefined);
@@ -635,25 +790,13 @@ pub fn fn_contains<'a>(
 
     let str_value = str_value.as_str();
 
-    // Check if token_value is a regex or string
-    let contains_result = match token_value {
-        Value::Regex(ref regex_literal) => {
-            let regex = regex_literal.get_regex();
-            regex.find_iter(&str_value).next().is_some()
-        }
-        Value::String(_) => {
-            let token_value = token_value.as_str();
-            str_value.contains(&token_value.to_string())
-        }
-        _ => {
-            return Err(Error::T0410ArgumentNotValid(
-                context.char_index,
-                2,
-                context.name.to_string(),
-            ));
-        }
+    let matcher = match Matcher::from_value(token_value) {
+        Some(matcher) => matcher,
+        None => bad_arg!(context, 2),
     };
 
+    let contains_result = !matcher.find_all(&context, &str_value, Some(1))?.is_empty();
+
     Ok(Value::bool(contains_result))
 }
 
@@ -687,60 +830,35 @@ pub fn fn_replace<'a>(
         Some(limit_value.as_isize() as usize)
     };
 
-    // Check if pattern_value is a Regex or String and handle appropriately
-    let regex = match pattern_value {
-        Value::Regex(ref regex_literal) => regex_literal.get_regex(),
-        Value::String(ref pattern_str) => {
-            assert_arg!(replacement_value.is_string(), context, 3);
-            let replacement_str = replacement_value.as_str();
+    // A string pattern is replaced literally, without substituting groups
+    if let Value::String(ref pattern_str) = pattern_value {
+        assert_arg!(replacement_value.is_string(), context, 3);
+        let replacement_str = replacement_value.as_str();
 
-            let replaced_string = if let Some(limit) = limit_value {
-                str_value.replacen(&pattern_str.to_string(), &replacement_str, limit)
-            } else {
-                str_value.replace(&pattern_str.to_string(), &replacement_str)
-            };
+        let replaced_string = if let Some(limit) = limit_value {
+            str_value.replacen(&pattern_str.to_string(), &replacement_str, limit)
+        } else {
+            str_value.replace(&pattern_str.to_string(), &replacement_str)
+        };
 
-            return Ok(Value::string(context.arena, &replaced_string));
-        }
-        _ => bad_arg!(context, 2),
+        return Ok(Value::string(context.arena, &replaced_string));
+    }
+
+    let matcher = match Matcher::from_value(pattern_value) {
+        Some(matcher) => matcher,
+        None => bad_arg!(context, 2),
     };
 
     let mut result = String::new();
     let mut last_end = 0;
 
-    for (replacements, m) in regex.find_iter(&str_value).enumerate() {
-        if m.range().is_empty() {
-            return Err(Error::D1004ZeroLengthMatch(context.char_index));
-        }
-
-        if let Some(limit) = limit_value {
-            if replacements >= limit {
-                break;
-            }
-        }
-
-        result.push_str(&str_value[last_end..m.start()]);
-
-        let match_str = &str_value[m.start()..m.end()];
+    for m in find_non_empty(&context, &matcher, &str_value, limit_value)? {
+        result.push_str(&str_value[last_end..m.start]);
 
         // Process replacement based on the replacement_value type
         let replacement_text = match replacement_value {
-            Value::NativeFn { func, .. } => {
-                let match_list = evaluate_match(context.arena, regex, match_str, None);
-
-                let func_result = func(context.clone(), &[match_list])?;
-
-                if let Value::String(ref s) = func_result {
-                    s.as_str().to_string()
-                } else {
-                    return Err(Error::D3012InvalidReplacementType(context.char_index));
-                }
-            }
-
-            func @ Value::Lambda { .. } => {
-                let match_list = evaluate_match(context.arena, regex, match_str, None);
-
-                let args = &[match_list];
+            func @ (Value::NativeFn { .. } | Value::Lambda { .. }) => {
+                let args = &[match_object(context.arena, &m, false)];
 
                 let func_result =
                     context.trampoline_evaluate_value(context.evaluate_function(func, args)?)?;
@@ -752,14 +870,14 @@ pub fn fn_replace<'a>(
             }
 
             Value::String(replacement_str) => {
-                evaluate_replacement_string(replacement_str.as_str(), &str_value, &m)
+                evaluate_replacement_string(replacement_str.as_str(), &m)
             }
 
             _ => bad_arg!(context, 3),
         };
 
         result.push_str(&replacement_text);
-        last_end = m.end();
+        last_end = m.end;
     }
 
     result.push_str(&str_value[last_end..]);
@@ -777,13 +895,8 @@ pub fn fn_replace<'a>(
 ///
 /// # Parameters
 /// - `replacement_str`: The replacement string to parse and evaluate.
-/// - `str_value`: The complete original string, the first argument to `$replace`.
 /// - `m`: The `Match` object for the current match which is being replaced.
-fn evaluate_replacement_string(
-    replacement_str: &str,
-    str_value: &str,
-    m: &regress::Match,
-) -> String {
+fn evaluate_replacement_string(replacement_str: &str, m: &Match) -> String {
     #[derive(Debug)]
     enum S {
         Literal,
@@ -795,7 +908,10 @@ fn evaluate_replacement_string(
     let mut state = S::Literal;
     let mut acc = String::new();
 
-    let groups: Vec<Option<Range>> = m.groups().collect();
+    // Group 0 is the entire match
+    let groups: Vec<Option<&str>> = std::iter::once(Some(m.text.as_str()))
+        .chain(m.groups.iter().map(Option::as_deref))
+        .collect();
     let mut chars = replacement_str.chars();
 
     loop {
@@ -841,11 +957,8 @@ fn evaluate_replacement_string(
                 // valid group number, it will use $N and treat M as a literal. This is not documented behavior and
                 // feels like a bug, but our test cases cover it in several ways.
                 if next >= groups_len {
-                    if let Some(match_range) = groups.get(*so_far as usize).and_then(|x| x.as_ref())
-                    {
-                        str_value
-                            .get(match_range.start..match_range.end)
-                            .inspect(|s| acc.push_str(s));
+                    if let Some(group) = groups.get(*so_far as usize).and_then(|x| *x) {
+                        acc.push_str(group);
                     } else {
                         // The capture group did not match.
                     }
@@ -860,10 +973,8 @@ fn evaluate_replacement_string(
 
             // The group number is complete, so we can now process it
             (S::Group(index), c) => {
-                if let Some(match_range) = groups.get(*index as usize).and_then(|x| x.as_ref()) {
-                    str_value
-                        .get(match_range.start..match_range.end)
-                        .inspect(|s| acc.push_str(s));
+                if let Some(group) = groups.get(*index as usize).and_then(|x| *x) {
+                    acc.push_str(group);
                 } else {
                     // The capture group did not match.
                 }
@@ -906,16 +1017,9 @@ pub fn fn_split<'a>(
     assert_arg!(str_value.is_string(), context, 1);
 
     let str_value = str_value.as_str();
-    let separator_is_regex = match separator_value {
-        Value::Regex(_) => true,
-        Value::String(_) => false,
-        _ => {
-            return Err(Error::T0410ArgumentNotValid(
-                context.char_index,
-                2,
-                context.name.to_string(),
-            ));
-        }
+    let matcher = match Matcher::from_value(separator_value) {
+        Some(matcher) => matcher,
+        None => bad_arg!(context, 2),
     };
 
     // Handle optional limit
@@ -929,39 +1033,7 @@ pub fn fn_split<'a>(
         Some(limit_value.as_f64() as usize)
     };
 
-    let substrings: Vec<String> = if separator_is_regex {
-        // Regex-based split using find_iter to find matches
-        let regex = match separator_value {
-            Value::Regex(ref regex_literal) => regex_literal.get_regex(),
-            _ => unreachable!(),
-        };
-
-        let mut results = Vec::new();
-        let mut last_end = 0;
-        let effective_limit = limit.unwrap_or(usize::MAX);
-
-        for m in regex.find_iter(&str_value) {
-            if results.len() >= effective_limit {
-                break;
-            }
-
-            if m.start() > last_end {
-                let substring = str_value[last_end..m.start()].to_string();
-                results.push(substring);
-            }
-
-            last_end = m.end();
-        }
-
-        if results.len() < effective_limit {
-            let remaining = str_value[last_end..].to_string();
-            results.push(remaining);
-        }
-        results
-    } else {
-        // Convert separator_value to &str
-        let separator_str = separator_value.as_str().to_string();
-        let separator_str = separator_str.as_str();
+    let substrings: Vec<String> = if let Matcher::Literal(separator_str) = matcher {
         if separator_str.is_empty() {
             // Split into individual characters, collecting directly into a Vec<String>
             if let Some(limit) = limit {
@@ -985,6 +1057,19 @@ pub fn fn_split<'a>(
                 .map(|s| s.to_string())
                 .collect()
         }
+    } else {
+        let mut results = Vec::new();
+        let mut last_end = 0;
+
+        for m in find_non_empty(&context, &matcher, &str_value, limit)? {
+            results.push(str_value[last_end..m.start].to_string());
+            last_end = m.end;
+        }
+
+        if limit.is_none_or(|limit| results.len() < limit) {
+            results.push(str_value[last_end..].to_string());
+        }
+        results
     };
 
     let result = Value::array_with_capacity(context.arena, substrings.len(), ArrayFlags::empty());
@@ -1164,6 +1249,34 @@ pub fn fn_sum<'a>(
     Ok(Value::number(context.arena, sum))
 }
 
+pub fn fn_average<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 1);
+
+    let arg = args.first().copied().unwrap_or_else(Value::undefined);
+
+    // $average(undefined) returns undefined
+    if arg.is_undefined() {
+        return Ok(Value::undefined());
+    }
+
+    let arr = Value::wrap_in_array_if_needed(context.arena, arg, ArrayFlags::empty());
+
+    if arr.is_empty() {
+        return Ok(Value::undefined());
+    }
+
+    let mut sum = 0.0;
+
+    for member in arr.members() {
+        assert_array_of_type!(member.is_number(), context, 1, "number");
+        sum += member.as_f64();
+    }
+    Ok(Value::number(context.arena, sum / arr.len() as f64))
+}
+
 pub fn fn_number<'a>(
     context: FunctionContext<'a, '_>,
     args: &[&'a Value<'a>],
@@ -1192,6 +1305,177 @@ pub fn fn_number<'a>(
     }
 }
 
+pub fn fn_format_number<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 3);
+
+    // If the number is omitted, the context is formatted instead
+    let args: Cow<[&'a Value<'a>]> = match args {
+        [picture] | [picture, Value::Object(..)] if picture.is_string() => {
+            let input = if context.input.has_flags(ArrayFlags::WRAPPED) {
+                &context.input[0]
+            } else {
+                context.input
+            };
+            Cow::Owned(std::iter::once(input).chain(args.iter().copied()).collect())
+        }
+        _ => Cow::Borrowed(args),
+    };
+
+    let value = args.first().copied().unwrap_or_else(Value::undefined);
+    let picture = args.get(1).copied().unwrap_or_else(Value::undefined);
+    let options = args.get(2).copied().unwrap_or_else(Value::undefined);
+
+    if value.is_undefined() {
+        return Ok(Value::undefined());
+    }
+
+    assert_arg!(value.is_number(), context, 1);
+    assert_arg!(picture.is_string(), context, 2);
+    assert_arg!(options.is_undefined() || options.is_object(), context, 3);
+
+    let mut format = DecimalFormat::default();
+    if options.is_object() {
+        for (name, value) in options.entries() {
+            assert_arg!(value.is_string(), context, 3);
+            assert_arg!(format.set(name, &value.as_str()), context, 3);
+        }
+    }
+
+    let result = format_number(
+        value.as_f64(),
+        &picture.as_str(),
+        &format,
+        context.char_index,
+    )?;
+
+    Ok(Value::string(context.arena, &result))
+}
+
+pub fn fn_format_integer<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 2);
+
+    let value = args.first().copied().unwrap_or_else(Value::undefined);
+    let picture = args.get(1).copied().unwrap_or_else(Value::undefined);
+
+    if value.is_undefined() {
+        return Ok(Value::undefined());
+    }
+
+    assert_arg!(value.is_number(), context, 1);
+    assert_arg!(picture.is_string(), context, 2);
+
+    let format = IntegerFormat::parse(&picture.as_str(), context.char_index)?;
+    let result = format.format(
+        value.as_f64(),
+        context.evaluator.language("en"),
+        context.char_index,
+    )?;
+
+    Ok(Value::string(context.arena, &result))
+}
+
+pub fn fn_parse_integer<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 2);
+
+    let value = args.first().copied().unwrap_or_else(Value::undefined);
+    let picture = args.get(1).copied().unwrap_or_else(Value::undefined);
+
+    if value.is_undefined() {
+        return Ok(Value::undefined());
+    }
+
+    assert_arg!(value.is_string(), context, 1);
+    assert_arg!(picture.is_string(), context, 2);
+
+    let format = IntegerFormat::parse(&picture.as_str(), context.char_index)?;
+    match format.parse_integer(
+        &value.as_str(),
+        context.evaluator.language("en"),
+        context.char_index,
+    )? {
+        Some(result) => Ok(Value::number(context.arena, result)),
+        None => Ok(Value::undefined()),
+    }
+}
+
+pub fn fn_format_base<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 2);
+
+    let value = args.first().copied().unwrap_or_else(Value::undefined);
+    let radix = args.get(1).copied().unwrap_or_else(Value::undefined);
+
+    if value.is_undefined() {
+        return Ok(Value::undefined());
+    }
+
+    assert_arg!(value.is_number(), context, 1);
+    assert_arg!(radix.is_undefined() || radix.is_number(), context, 2);
+
+    let value = value.as_f64().round_ties_even();
+    let radix = if radix.is_undefined() {
+        10
+    } else {
+        radix.as_f64().round_ties_even() as isize
+    };
+
+    if !(2..=36).contains(&radix) {
+        return Err(Error::D3100RadixOutOfRange(context.char_index, radix));
+    }
+
+    // The magnitude can be far beyond a u64, so it's held as base 2^32 limbs, least
+    // significant first, and divided down one digit at a time
+    let bits = value.abs().to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i64 - 1075;
+    let mantissa = (bits & ((1 << 52) - 1)) | if bits >> 52 == 0 { 0 } else { 1 << 52 };
+    let mut limbs = if exponent < 0 {
+        let magnitude = mantissa >> exponent.unsigned_abs().min(63);
+        vec![magnitude as u32, (magnitude >> 32) as u32]
+    } else {
+        let shift = exponent as usize;
+        let mut limbs = vec![0u32; shift / 32 + 3];
+        let shifted = (mantissa as u128) << (shift % 32);
+        for (i, limb) in limbs[shift / 32..].iter_mut().enumerate() {
+            *limb = (shifted >> (32 * i)) as u32;
+        }
+        limbs
+    };
+
+    let mut digits = Vec::new();
+    loop {
+        let mut remainder = 0u64;
+        for limb in limbs.iter_mut().rev() {
+            let current = (remainder << 32) | *limb as u64;
+            *limb = (current / radix as u64) as u32;
+            remainder = current % radix as u64;
+        }
+        digits.push(char::from_digit(remainder as u32, radix as u32).unwrap_or('0'));
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        if limbs.is_empty() {
+            break;
+        }
+    }
+    if value < 0.0 {
+        digits.push('-');
+    }
+
+    let result: String = digits.into_iter().rev().collect();
+    Ok(Value::string(context.arena, &result))
+}
+
 pub fn fn_random<'a>(
     context: FunctionContext<'a, '_>,
     args: &[&'a Value<'a>],
@@ -1206,15 +1490,32 @@ pub fn fn_now<'a>(
     context: FunctionContext<'a, '_>,
     args: &[&'a Value<'a>],
 ) -> Result<&'a Value<'a>> {
-    max_args!(context, args, 2);
+    max_args!(context, args, 3);
 
     let now = Utc::now();
 
-    let (picture, timezone) = match args {
-        [picture, timezone] => (picture.as_str(), timezone.as_str()),
-        [picture] => (picture.as_str(), Cow::Borrowed("")),
-        [] => (Cow::Borrowed(""), Cow::Borrowed("")),
-        _ => return Ok(Value::string(context.arena, "")),
+    let picture = match args.first() {
+        Some(picture) if !picture.is_undefined() => {
+            assert_arg!(picture.is_string(), context, 1);
+            picture.as_str()
+        }
+        _ => Cow::Borrowed(""),
+    };
+
+    let timezone = match args.get(1) {
+        Some(timezone) if !timezone.is_undefined() => {
+            assert_arg!(timezone.is_string(), context, 2);
+            timezone.as_str()
+        }
+        _ => Cow::Borrowed(""),
+    };
+
+    let language = match args.get(2) {
+        Some(language) if !language.is_undefined() => {
+            assert_arg!(language.is_string(), context, 3);
+            language.as_str()
+        }
+        _ => Cow::Borrowed(""),
     };
 
     if picture.is_empty() && timezone.is_empty() {
@@ -1224,18 +1525,19 @@ pub fn fn_now<'a>(
         ));
     }
 
-    let adjusted_time = if !timezone.is_empty() {
-        parse_timezone_offset(&timezone)
-            .map(|offset| now.with_timezone(&offset))
-            .ok_or_else(|| Error::T0410ArgumentNotValid(2, 1, context.name.to_string()))?
+    let zone = if !timezone.is_empty() {
+        Zone::parse(&timezone).ok_or_else(|| {
+            Error::T0410ArgumentNotValid(context.char_index, 2, context.name.to_string())
+        })?
     } else {
-        now.into()
+        Zone::utc()
     };
 
     // If a valid picture is provided, format the time accordingly
     if !picture.is_empty() {
         // Handle the Result<String, Error> from format_custom_date
-        let formatted_date = format_custom_date(&adjusted_time, &picture)?;
+        let formatted_date =
+            format_custom_date(&now, &zone, context.evaluator.language(&language), &picture)?;
         return Ok(Value::string(context.arena, &formatted_date));
     }
 
@@ -1268,7 +1570,7 @@ pub fn from_millis<'a>(
         return Ok(Value::undefined());
     }
 
-    max_args!(context, args, 3);
+    max_args!(context, args, 4);
     assert_arg!(args[0].is_number(), context, 1);
 
     let millis = args[0].as_f64() as i64;
@@ -1277,7 +1579,22 @@ pub fn from_millis<'a>(
         bad_arg!(context, 1);
     };
 
-    let (picture, timezone) = match args {
+    let language = match args.get(3) {
+        Some(language) if !language.is_undefined() => {
+            assert_arg!(language.is_string(), context, 4);
+            language.as_str()
+        }
+        _ => Cow::Borrowed(""),
+    };
+
+    let (picture, timezone) = match args.get(..3).unwrap_or(args) {
+        [_, picture, timezone] if picture.is_undefined() && timezone.is_undefined() => {
+            (Cow::Borrowed(""), Cow::Borrowed(""))
+        }
+        [_, picture, timezone] if timezone.is_undefined() => {
+            assert_arg!(picture.is_string(), context, 2);
+            (picture.as_str(), Cow::Borrowed(""))
+        }
         [_, picture, timezone] if picture.is_undefined() => {
             assert_arg!(timezone.is_string(), context, 3);
             (Cow::Borrowed(""), timezone.as_str())
@@ -1307,18 +1624,23 @@ pub fn from_millis<'a>(
         return Err(Error::D3135PictureStringNoClosingBracketError(err));
     }
 
-    let adjusted_time = if !timezone.is_empty() {
-        parse_timezone_offset(&timezone)
-            .map(|offset| timestamp.with_timezone(&offset))
-            .ok_or_else(|| Error::T0410ArgumentNotValid(0, 1, context.name.to_string()))?
+    let zone = if !timezone.is_empty() {
+        Zone::parse(&timezone).ok_or_else(|| {
+            Error::T0410ArgumentNotValid(context.char_index, 3, context.name.to_string())
+        })?
     } else {
-        timestamp.into()
+        Zone::utc()
     };
 
     // If a picture is provided, format the timestamp accordingly
     if !picture.is_empty() {
         // Call format_custom_date and handle its result
-        let formatted_result = format_custom_date(&adjusted_time, &picture)?;
+        let formatted_result = format_custom_date(
+            &timestamp,
+            &zone,
+            context.evaluator.language(&language),
+            &picture,
+        )?;
 
         return Ok(Value::string(context.arena, &formatted_result));
     }
@@ -1326,7 +1648,9 @@ pub fn from_millis<'a>(
     // Return ISO 8601 if only timezone is provided
     Ok(Value::string(
         context.arena,
-        &adjusted_time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        &zone
+            .localize(&timestamp)
+            .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
     ))
 }
 
@@ -1368,7 +1692,7 @@ pub fn to_millis<'a>(
         return Ok(Value::undefined());
     }
 
-    max_args!(context, args, 2);
+    max_args!(context, args, 3);
     assert_arg!(args[0].is_string(), context, 1);
 
     // Extract the timestamp string
@@ -1378,17 +1702,27 @@ pub fn to_millis<'a>(
     }
 
     // Extract the optional picture string
-    let picture = match args {
-        [_, picture] if picture.is_undefined() => Cow::Borrowed(""),
-        [_, picture] => {
+    let picture = match args.get(1) {
+        Some(picture) if !picture.is_undefined() => {
             assert_arg!(picture.is_string(), context, 2);
             picture.as_str()
         }
         _ => Cow::Borrowed(""),
     };
 
-    // Handle different formats using a match handler function
-    match parse_custom_format(&timestamp_str, &picture) {
+    let language = match args.get(2) {
+        Some(language) if !language.is_undefined() => {
+            assert_arg!(language.is_string(), context, 3);
+            language.as_str()
+        }
+        _ => Cow::Borrowed(""),
+    };
+
+    match parse_custom_format(
+        &timestamp_str,
+        &picture,
+        context.evaluator.language(&language),
+    )? {
         Some(millis) => Ok(Value::number(context.arena, millis as f64)),
         None => Ok(Value::undefined()),
     }
@@ -1482,7 +1816,7 @@ pub fn single<'a>(
         });
 
     if !arr.is_array() {
-        let res = context.evaluate_function(func, &[arr])?;
+        let res = context.call_callback(func, &[arr])?;
         return if res.as_bool() {
             Ok(arr)
         } else {
@@ -1496,10 +1830,8 @@ pub fn single<'a>(
         let mut result: Option<&'a Value<'a>> = None;
 
         for (index, entry) in elements.iter().enumerate() {
-            let res = context.evaluate_function(
-                func,
-                &[entry, Value::number(context.arena, index as f64), arr],
-            )?;
+            let index_value = Value::number(context.arena, index as f64);
+            let res = context.call_callback(func, &[entry, index_value, arr])?;
 
             if res.as_bool() {
                 if result.is_some() {
@@ -1554,6 +1886,45 @@ pub fn fn_error<'a>(
     }))
 }
 
+pub fn fn_eval<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 2);
+
+    let expr = args.first().copied().unwrap_or_else(Value::undefined);
+    let focus = args.get(1).copied().unwrap_or_else(Value::undefined);
+
+    if expr.is_undefined() {
+        return Ok(Value::undefined());
+    }
+
+    assert_arg!(expr.is_string(), context, 1);
+
+    let input = if focus.is_undefined() {
+        context.input
+    } else if focus.is_array() && !focus.has_flags(ArrayFlags::SEQUENCE) {
+        // Wrap a JSON array so that it gets treated as a single input, as at the top level
+        Value::wrap_in_array(context.arena, focus, ArrayFlags::WRAPPED)
+    } else {
+        focus
+    };
+
+    let ast = parse(&expr.as_str())
+        .map_err(|e| Error::D3120SyntaxErrorInEval(context.char_index, e.to_string()))?;
+
+    context
+        .evaluator
+        .evaluate(&ast, input, &context.frame)
+        .map_err(|e| match e {
+            // Running out of time, stack or regex budget isn't the evaluated expression's fault
+            Error::U1001StackOverflow
+            | Error::U1001Timeout
+            | Error::U1002RegexBudgetExceeded(..) => e,
+            e => Error::D3121DynamicErrorInEval(context.char_index, e.to_string()),
+        })
+}
+
 pub fn fn_length<'a>(
     context: FunctionContext<'a, '_>,
     args: &[&'a Value<'a>],
@@ -1643,6 +2014,28 @@ pub fn fn_reverse<'a>(
     Ok(result)
 }
 
+pub fn fn_shuffle<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 1);
+
+    let arr = args.first().copied().unwrap_or_else(Value::undefined);
+
+    if arr.is_undefined() {
+        return Ok(Value::undefined());
+    }
+
+    let arr = Value::wrap_in_array_if_needed(context.arena, arr, ArrayFlags::empty());
+
+    let mut members: Vec<&'a Value<'a>> = arr.members().collect();
+    members.shuffle(&mut rand::rng());
+
+    let result = Value::array_with_capacity(context.arena, members.len(), ArrayFlags::empty());
+    members.into_iter().for_each(|member| result.push(member));
+    Ok(result)
+}
+
 #[allow(clippy::mutable_key_type)]
 pub fn fn_distinct<'a>(
     context: FunctionContext<'a, '_>,
@@ -1730,27 +2123,29 @@ pub fn fn_sort<'a, 'e>(
         ));
     }
 
-    // TODO: This is all a bit inefficient, copying Vecs of references around, but
-    // at least it's just references.
-
-    let unsorted = arr.members().collect::<Vec<&'a Value<'a>>>();
-    let sorted = if args.get(1).is_none() {
+    let mut sorted = arr.members().collect::<Vec<&'a Value<'a>>>();
+    if args.get(1).is_none() {
+        let all_numbers = sorted.iter().all(|member| member.is_number());
+        let all_strings = sorted.iter().all(|member| member.is_string());
+        if !all_numbers && !all_strings {
+            return Err(Error::D3070InvalidDefaultSort(context.char_index));
+        }
         merge_sort(
-            unsorted,
+            &mut sorted,
             &|a: &'a Value<'a>, b: &'a Value<'a>| match (a, b) {
                 (Value::Number(a), Value::Number(b)) => Ok(a > b),
                 (Value::String(a), Value::String(b)) => Ok(a > b),
                 _ => Err(Error::D3070InvalidDefaultSort(context.char_index)),
             },
-        )?
+        )?;
     } else {
         let comparator = args.get(1).copied().unwrap_or_else(Value::undefined);
         assert_arg!(comparator.is_function(), context, 2);
-        merge_sort(unsorted, &|a: &'a Value<'a>, b: &'a Value<'a>| {
-            let result = context.evaluate_function(comparator, &[a, b])?;
+        merge_sort(&mut sorted, &|a: &'a Value<'a>, b: &'a Value<'a>| {
+            let result = context.call_callback(comparator, &[a, b])?;
             Ok(result.is_truthy())
-        })?
-    };
+        })?;
+    }
 
     let result = Value::array_with_capacity(context.arena, sorted.len(), arr.get_flags());
     sorted.iter().for_each(|member| result.push(member));
@@ -1758,55 +2153,51 @@ pub fn fn_sort<'a, 'e>(
     Ok(result)
 }
 
-pub fn merge_sort<'a, F>(items: Vec<&'a Value<'a>>, comp: &F) -> Result<Vec<&'a Value<'a>>>
+/// Sort `items` in place with the same top-down merge sort as reference JSONata, so that a
+/// comparator is called with the same pairs in the same order. `comp(a, b)` returns `true` when
+/// `a` should come after `b`, and equal items keep their order.
+///
+/// Each merge goes through a single scratch buffer rather than allocating at every level.
+pub fn merge_sort<T, F>(items: &mut [T], comp: &F) -> Result<()>
 where
-    F: Fn(&'a Value<'a>, &'a Value<'a>) -> Result<bool>,
+    T: Copy,
+    F: Fn(T, T) -> Result<bool>,
 {
-    fn merge_iter<'a, F>(
-        result: &mut Vec<&'a Value<'a>>,
-        left: &[&'a Value<'a>],
-        right: &[&'a Value<'a>],
-        comp: &F,
-    ) -> Result<()>
+    fn sort<T, F>(items: &mut [T], scratch: &mut Vec<T>, comp: &F) -> Result<()>
     where
-        F: Fn(&'a Value<'a>, &'a Value<'a>) -> Result<bool>,
+        T: Copy,
+        F: Fn(T, T) -> Result<bool>,
     {
-        if left.is_empty() {
-            result.extend(right);
-            Ok(())
-        } else if right.is_empty() {
-            result.extend(left);
-            Ok(())
-        } else if comp(left[0], right[0])? {
-            result.push(right[0]);
-            merge_iter(result, left, &right[1..], comp)
-        } else {
-            result.push(left[0]);
-            merge_iter(result, &left[1..], right, comp)
+        if items.len() <= 1 {
+            return Ok(());
         }
-    }
 
-    fn merge<'a, F>(
-        left: &[&'a Value<'a>],
-        right: &[&'a Value<'a>],
-        comp: &F,
-    ) -> Result<Vec<&'a Value<'a>>>
-    where
-        F: Fn(&'a Value<'a>, &'a Value<'a>) -> Result<bool>,
-    {
-        let mut merged = Vec::with_capacity(left.len() + right.len());
-        merge_iter(&mut merged, left, right, comp)?;
-        Ok(merged)
-    }
+        let middle = items.len() / 2;
+        sort(&mut items[..middle], scratch, comp)?;
+        sort(&mut items[middle..], scratch, comp)?;
+
+        let (left, right) = items.split_at(middle);
+        let (mut i, mut j) = (0, 0);
+        scratch.clear();
+        while i < left.len() && j < right.len() {
+            if comp(left[i], right[j])? {
+                scratch.push(right[j]);
+                j += 1;
+            } else {
+                scratch.push(left[i]);
+                i += 1;
+            }
+        }
+        scratch.extend_from_slice(&left[i..]);
 
-    if items.len() <= 1 {
-        return Ok(items);
+        // Whatever is left of the right half is already in place
+        let merged = scratch.len();
+        items[..merged].copy_from_slice(scratch);
+        Ok(())
     }
-    let middle = (items.len() as f64 / 2.0).floor() as usize;
-    let (left, right) = items.split_at(middle);
-    let left = merge_sort(left.to_vec(), comp)?;
-    let right = merge_sort(right.to_vec(), comp)?;
-    merge(&left, &right, comp)
+
+    let mut scratch = Vec::with_capacity(items.len());
+    sort(items, &mut scratch, comp)
 }
 
 pub fn fn_base64_encode<'a>(
@@ -1847,6 +2238,157 @@ pub fn fn_base64_decode<'a>(
     Ok(Value::string(context.arena, &decoded))
 }
 
+/// The characters left as they are by JavaScript's `encodeURIComponent`, in addition to ASCII
+/// letters and digits.
+const URI_UNRESERVED: &str = "-_.!~*'()";
+
+/// The characters that `encodeURI` also leaves alone, and that `decodeURI` won't decode.
+const URI_RESERVED: &str = ";/?:@&=+$,#";
+
+fn encode_uri(value: &str, unescaped: &[&str]) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    let mut buffer = [0; 4];
+
+    for c in value.chars() {
+        if c.is_ascii_alphanumeric() || unescaped.iter().any(|set| set.contains(c)) {
+            encoded.push(c);
+        } else {
+            for byte in c.encode_utf8(&mut buffer).bytes() {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+
+    encoded
+}
+
+/// Decode percent-encoded UTF-8 in the same way as JavaScript's `decodeURI` and
+/// `decodeURIComponent`. Escapes of the `preserved` characters are left encoded. Returns
+/// `None` for a malformed escape or invalid UTF-8.
+fn decode_uri(value: &str, preserved: &str) -> Option<String> {
+    fn hex_byte(bytes: &[u8], index: usize) -> Option<u8> {
+        match bytes.get(index..index + 3) {
+            Some([b'%', high, low]) => {
+                let high = (*high as char).to_digit(16)?;
+                let low = (*low as char).to_digit(16)?;
+                Some((high * 16 + low) as u8)
+            }
+            _ => None,
+        }
+    }
+
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] != b'%' {
+            decoded.push(bytes[index]);
+            index += 1;
+            continue;
+        }
+
+        let lead = hex_byte(bytes, index)?;
+        let length = match lead {
+            0x00..=0x7F => 1,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return None,
+        };
+
+        if length == 1 && preserved.contains(lead as char) {
+            decoded.extend_from_slice(&bytes[index..index + 3]);
+        } else {
+            let mut sequence = vec![lead];
+            for n in 1..length {
+                sequence.push(hex_byte(bytes, index + n * 3)?);
+            }
+            decoded.extend_from_slice(std::str::from_utf8(&sequence).ok()?.as_bytes());
+        }
+
+        index += length * 3;
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+pub fn fn_encode_url_component<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 1);
+    let arg = args.first().copied().unwrap_or_else(Value::undefined);
+    if arg.is_undefined() {
+        return Ok(Value::undefined());
+    }
+    assert_arg!(arg.is_string(), context, 1);
+
+    let encoded = encode_uri(&arg.as_str(), &[URI_UNRESERVED]);
+
+    Ok(Value::string(context.arena, &encoded))
+}
+
+pub fn fn_encode_url<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 1);
+    let arg = args.first().copied().unwrap_or_else(Value::undefined);
+    if arg.is_undefined() {
+        return Ok(Value::undefined());
+    }
+    assert_arg!(arg.is_string(), context, 1);
+
+    let encoded = encode_uri(&arg.as_str(), &[URI_UNRESERVED, URI_RESERVED]);
+
+    Ok(Value::string(context.arena, &encoded))
+}
+
+pub fn fn_decode_url_component<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 1);
+    let arg = args.first().copied().unwrap_or_else(Value::undefined);
+    if arg.is_undefined() {
+        return Ok(Value::undefined());
+    }
+    assert_arg!(arg.is_string(), context, 1);
+
+    let decoded = decode_uri(&arg.as_str(), "").ok_or_else(|| {
+        Error::D3140MalformedUrl(
+            context.char_index,
+            context.name.to_string(),
+            arg.as_str().to_string(),
+        )
+    })?;
+
+    Ok(Value::string(context.arena, &decoded))
+}
+
+pub fn fn_decode_url<'a>(
+    context: FunctionContext<'a, '_>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    max_args!(context, args, 1);
+    let arg = args.first().copied().unwrap_or_else(Value::undefined);
+    if arg.is_undefined() {
+        return Ok(Value::undefined());
+    }
+    assert_arg!(arg.is_string(), context, 1);
+
+    let decoded = decode_uri(&arg.as_str(), URI_RESERVED).ok_or_else(|| {
+        Error::D3140MalformedUrl(
+            context.char_index,
+            context.name.to_string(),
+            arg.as_str().to_string(),
+        )
+    })?;
+
+    Ok(Value::string(context.arena, &decoded))
+}
+
 pub fn fn_round<'a>(
     context: FunctionContext<'a, '_>,
     args: &[&'a Value<'a>],
@@ -1892,7 +2434,7 @@ pub fn fn_reduce<'a>(
 
     let original_value = args[0];
     let func = args[1];
-    let init = args.get(2).copied();
+    let init = args.get(2).copied().filter(|init| !init.is_undefined());
 
     if func.is_function() && func.arity() < 2 {
         return Err(Error::D3050SecondArguement(context.name.to_string()));
@@ -1934,21 +2476,11 @@ pub fn fn_reduce<'a>(
         1
     };
 
-    for (index, value) in elements[start_index..].iter().enumerate() {
+    for (index, value) in elements.iter().enumerate().skip(start_index) {
         let index_value = Value::number(context.arena, index as f64);
 
-        let result =
-            context.evaluate_function(func, &[accumulator, value, index_value, original_value]);
-
-        match result {
-            Ok(new_accumulator) => {
-                // If the result is a thunk, let's evaluate it so it's ready for the next iteration
-                accumulator = context.trampoline_evaluate_value(new_accumulator)?;
-            }
-            Err(_) => {
-                return Err(Error::T0410ArgumentNotValid(1, 1, context.name.to_string()));
-            }
-        }
+        accumulator =
+            context.call_callback(func, &[accumulator, value, index_value, original_value])?;
     }
 
     Ok(accumulator)
@@ -2027,14 +2559,18 @@ pub fn fn_match<'a>(
         _ => return Err(Error::D3010EmptyPattern(context.char_index)),
     };
 
-    let regex_literal = match pattern_value {
-        Value::Regex(ref regex_literal) => regex_literal,
+    let matcher = match pattern_value {
         Value::String(ref s) => {
-            let regex = RegexLiteral::new(s.as_str(), false, false)
+            let regex = context
+                .evaluator
+                .compile_regex(s.as_str(), false, false)
                 .map_err(|_| Error::D3010EmptyPattern(context.char_index))?;
-            &*context.arena.alloc(regex)
+            Matcher::Regex(regex)
         }
-        _ => return Err(Error::D3010EmptyPattern(context.char_index)),
+        _ => match Matcher::from_value(pattern_value) {
+            Some(matcher) => matcher,
+            None => return Err(Error::D3010EmptyPattern(context.char_index)),
+        },
     };
 
     let limit = args.get(2).and_then(|val| {
@@ -2045,81 +2581,67 @@ pub fn fn_match<'a>(
         }
     });
 
-    Ok(evaluate_match(
-        context.arena,
-        regex_literal.get_regex(),
-        &value_to_validate.as_str(),
-        limit,
-    ))
+    // An options object can opt in to reporting named capture groups
+    let options = args.get(3).copied().unwrap_or_else(Value::undefined);
+    assert_arg!(options.is_undefined() || options.is_object(), context, 4);
+    let named_groups = options.is_object() && options.get_entry("namedGroups").is_truthy();
+
+    let matches = find_non_empty(&context, &matcher, &value_to_validate.as_str(), limit)?;
+
+    let result = Value::array_with_capacity(context.arena, matches.len(), ArrayFlags::empty());
+    for m in &matches {
+        result.push(match_object(context.arena, m, named_groups));
+    }
+    Ok(result)
 }
 
-/// An inner helper which evaluates the `$match` function.
-///
-/// The return value is a Value::Array which looks like:
-///
-/// [
-///   {
-///     "match": "ab",
-///     "index": 0,
-///     "groups": ["b"]
-///   },
-///   {
-///     "match": "abb",
-///     "index": 2,
-///     "groups": ["bb"]
-///   },
-///   {
-///     "match": "abb",
-///     "index": 5,
-///     "groups": ["bb" ]
-///   }
-/// ]
-fn evaluate_match<'a>(
-    arena: &'a Bump,
-    regex: &Regex,
-    input_str: &str,
+/// Find the matches for `$match`, `$replace` and `$split`, none of which allow a zero-length match.
+fn find_non_empty<'a>(
+    context: &FunctionContext<'a, '_>,
+    matcher: &Matcher<'a>,
+    input: &str,
     limit: Option<usize>,
-) -> &'a Value<'a> {
-    let limit = limit.unwrap_or(usize::MAX);
+) -> Result<Vec<Match>> {
+    let matches = matcher.find_all(context, input, limit)?;
+    if matches.iter().any(|m| m.start == m.end) {
+        return Err(Error::D1004ZeroLengthMatch(context.char_index));
+    }
+    Ok(matches)
+}
 
-    let key_match = BumpString::from_str_in("match", arena);
-    let key_index = BumpString::from_str_in("index", arena);
-    let key_groups = BumpString::from_str_in("groups", arena);
+/// Build the object describing a single match, as returned by `$match` and passed to
+/// replacement functions in `$replace`, which looks like:
+///
+/// {
+///   "match": "abb",
+///   "index": 2,
+///   "groups": ["bb", ""]
+/// }
+///
+/// Groups which did not participate in the match are empty strings. With `named_groups`, the
+/// object also has a `namedGroups` object mapping the names of named groups to their values.
+fn match_object<'a>(arena: &'a Bump, m: &Match, named_groups: bool) -> &'a Value<'a> {
+    let group_value =
+        |group: &Option<String>| &*Value::string(arena, group.as_deref().unwrap_or(""));
+
+    let groups = Value::array_from(
+        arena,
+        BumpVec::from_iter_in(m.groups.iter().map(group_value), arena),
+        ArrayFlags::empty(),
+    );
 
-    let mut matches: bumpalo::collections::Vec<&Value<'a>> =
-        bumpalo::collections::Vec::new_in(arena);
+    let match_obj = Value::object_with_capacity(arena, 4);
+    match_obj.insert("match", Value::string(arena, &m.text));
+    match_obj.insert("index", Value::number(arena, m.index as f64));
+    match_obj.insert("groups", groups);
 
-    for (i, m) in regex.find_iter(input_str).enumerate() {
-        if i >= limit {
-            break;
+    if named_groups {
+        let named = Value::object_with_capacity(arena, m.named_groups.len());
+        for (name, group) in &m.named_groups {
+            named.insert(name, group_value(group));
         }
-
-        let matched_text = &input_str[m.start()..m.end()];
-        let match_str = arena.alloc(Value::string(arena, matched_text));
-
-        let index_val = arena.alloc(Value::number(arena, i as f64));
-
-        // Extract capture groups as values
-        let capture_groups = m
-            .groups()
-            .filter_map(|group| group.map(|range| &input_str[range.start..range.end]))
-            .map(|s| BumpString::from_str_in(s, arena))
-            .map(|s| &*arena.alloc(Value::String(s)))
-            // Skip the first group which is the entire match
-            .skip(1);
-
-        let group_vec = BumpVec::from_iter_in(capture_groups, arena);
-
-        let groups_val = arena.alloc(Value::Array(group_vec, ArrayFlags::empty()));
-
-        let mut match_obj: HashMap<BumpString, &Value<'a>, DefaultHashBuilder, &Bump> =
-            HashMap::with_capacity_and_hasher_in(3, DefaultHashBuilder::default(), arena);
-        match_obj.insert(key_match.clone(), match_str);
-        match_obj.insert(key_index.clone(), index_val);
-        match_obj.insert(key_groups.clone(), groups_val);
-
-        matches.push(arena.alloc(Value::Object(match_obj)));
+        match_obj.insert("namedGroups", named);
     }
 
-    arena.alloc(Value::Array(matches, ArrayFlags::empty()))
+    match_obj
 }
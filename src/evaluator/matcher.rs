@@ -0,0 +1,232 @@
+use std::ops::Range;
+
+use super::functions::FunctionContext;
+use super::value::Value;
+use super::RegexLiteral;
+use crate::{Error, Result};
+
+/// The pattern argument of `$match`, `$replace`, `$split` and `$contains`.
+///
+/// Besides regular expressions and plain strings, a matcher can be any function implementing the
+/// reference matcher protocol: called with the input string, it returns undefined when there is no
+/// match, or an object with the `match`, `start`, `end` and `groups` of the first match along with
+/// a `next` function returning the following match in the same way.
+pub enum Matcher<'a> {
+    Regex(&'a RegexLiteral),
+    Literal(&'a str),
+    Function(&'a Value<'a>),
+}
+
+/// A single match found by a [`Matcher`].
+#[derive(Debug)]
+pub struct Match {
+    /// Byte offset of the start of the match in the input.
+    pub start: usize,
+    /// Byte offset of the end of the match in the input.
+    pub end: usize,
+    /// Offset of the start of the match in UTF-16 code units, as reported by reference JSONata.
+    pub index: usize,
+    /// The matched text.
+    pub text: String,
+    /// The capture groups, with `None` for groups which did not participate in the match.
+    pub groups: Vec<Option<String>>,
+    /// The named capture groups, in the order they appear in the pattern.
+    pub named_groups: Vec<(String, Option<String>)>,
+}
+
+impl<'a> Matcher<'a> {
+    /// Create a matcher from a function argument, or `None` if the value can't be used as one.
+    pub fn from_value(value: &'a Value<'a>) -> Option<Matcher<'a>> {
+        match value {
+            Value::Regex(ref regex_literal) => Some(Matcher::Regex(regex_literal)),
+            Value::String(ref s) => Some(Matcher::Literal(s.as_str())),
+            Value::Lambda { .. } | Value::NativeFn { .. } => Some(Matcher::Function(value)),
+            _ => None,
+        }
+    }
+
+    /// Find the matches in `input`, stopping after `limit` of them if given.
+    pub fn find_all(
+        &self,
+        context: &FunctionContext<'a, '_>,
+        input: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<Match>> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut matches = Vec::new();
+
+        match *self {
+            Matcher::Regex(regex) => {
+                let evaluator = context.evaluator;
+                evaluator.check_regex_input(context.char_index, input.len())?;
+                let found = if evaluator.bounds_regex() {
+                    regex
+                        .find_charged(input, limit, |steps| {
+                            evaluator.charge_regex_steps(context.char_index, steps)
+                        })
+                        .map_err(|message| {
+                            Error::S0303InvalidRegex(context.char_index, message)
+                        })??
+                } else {
+                    regex.find_all(input, limit)
+                };
+
+                let mut offsets = Utf16Offsets::new(input);
+                for m in found {
+                    let text = |range: Range<usize>| input[range].to_string();
+                    matches.push(Match {
+                        start: m.range.start,
+                        end: m.range.end,
+                        index: offsets.index_of(m.range.start),
+                        text: text(m.range),
+                        groups: m
+                            .captures
+                            .into_iter()
+                            .map(|group| group.map(text))
+                            .collect(),
+                        named_groups: m
+                            .named_groups
+                            .into_iter()
+                            .map(|(name, group)| (name, group.map(text)))
+                            .collect(),
+                    });
+                }
+            }
+            Matcher::Literal(literal) => {
+                let mut offsets = Utf16Offsets::new(input);
+                for (start, text) in input.match_indices(literal).take(limit) {
+                    matches.push(Match {
+                        start,
+                        end: start + text.len(),
+                        index: offsets.index_of(start),
+                        text: text.to_string(),
+                        groups: Vec::new(),
+                        named_groups: Vec::new(),
+                    });
+                }
+            }
+            Matcher::Function(func) => {
+                let input_value = Value::string(context.arena, input);
+                let mut result = call(context, func, &[input_value])?;
+                while !result.is_undefined() && matches.len() < limit {
+                    let previous_end = matches.last().map_or(0, |m: &Match| m.end);
+                    matches.push(function_match(context, input, result, previous_end)?);
+
+                    let next = result.get_entry("next");
+                    if !next.is_function() {
+                        break;
+                    }
+                    result = call(context, next, &[])?;
+                }
+            }
+        }
+
+        Ok(matches)
+    }
+}
+
+fn call<'a>(
+    context: &FunctionContext<'a, '_>,
+    func: &'a Value<'a>,
+    args: &[&'a Value<'a>],
+) -> Result<&'a Value<'a>> {
+    context.trampoline_evaluate_value(context.evaluate_function(func, args)?)
+}
+
+/// Convert the object returned by a matcher function into a [`Match`].
+///
+/// The `start` and `end` of the object are offsets into the input in UTF-16 code units. Matches
+/// must be in order and can't overlap, so the match can't start before `previous_end`, the byte
+/// offset of the end of the previous match.
+fn function_match<'a>(
+    context: &FunctionContext<'a, '_>,
+    input: &str,
+    result: &'a Value<'a>,
+    previous_end: usize,
+) -> Result<Match> {
+    let invalid = || Error::T1010MatcherInvalidResult(context.char_index, context.name.to_string());
+
+    if !result.is_object() {
+        return Err(invalid());
+    }
+
+    let start = result.get_entry("start");
+    let end = result.get_entry("end");
+    if !start.is_number()
+        || !end.is_number()
+        || start.as_f64() < 0.0
+        || start.as_f64() > end.as_f64()
+    {
+        return Err(invalid());
+    }
+
+    let index = start.as_usize();
+    let start = byte_offset(input, index);
+    let end = byte_offset(input, end.as_usize());
+    if start < previous_end {
+        return Err(invalid());
+    }
+
+    let text = match result.get_entry("match") {
+        Value::String(ref s) => s.to_string(),
+        _ => input[start..end].to_string(),
+    };
+
+    let groups = result.get_entry("groups");
+    let groups = if groups.is_array() {
+        groups
+            .members()
+            .map(|group| group.is_string().then(|| group.as_str().to_string()))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    Ok(Match {
+        start,
+        end,
+        index,
+        text,
+        groups,
+        named_groups: Vec::new(),
+    })
+}
+
+/// The byte offset of the character starting at the UTF-16 offset `index`, clamped to the end of
+/// `input`.
+fn byte_offset(input: &str, index: usize) -> usize {
+    let mut utf16_offset = 0;
+    for (offset, c) in input.char_indices() {
+        if utf16_offset >= index {
+            return offset;
+        }
+        utf16_offset += c.len_utf16();
+    }
+    input.len()
+}
+
+/// Converts increasing byte offsets into UTF-16 offsets without rescanning the input.
+struct Utf16Offsets<'s> {
+    input: &'s str,
+    byte: usize,
+    index: usize,
+}
+
+impl<'s> Utf16Offsets<'s> {
+    fn new(input: &'s str) -> Self {
+        Self {
+            input,
+            byte: 0,
+            index: 0,
+        }
+    }
+
+    fn index_of(&mut self, byte: usize) -> usize {
+        self.index += self.input[self.byte..byte]
+            .chars()
+            .map(char::len_utf16)
+            .sum::<usize>();
+        self.byte = byte;
+        self.index
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::{HashMap, VecDeque};
+
+use bumpalo::Bump;
+
+use super::RegexLiteral;
+
+/// The number of compiled regular expressions kept by a [`RegexCache`].
+const CAPACITY: usize = 256;
+
+/// A bounded cache of regular expressions compiled from strings during an evaluation, keyed by
+/// pattern and flags. When full, the oldest entry is evicted.
+///
+/// The regular expressions are allocated in the evaluation's arena so they can be borrowed for
+/// as long as the values which refer to them.
+pub struct RegexCache<'a> {
+    arena: &'a Bump,
+    entries: HashMap<(String, String), &'a RegexLiteral>,
+    order: VecDeque<(String, String)>,
+    hits: usize,
+    misses: usize,
+}
+
+impl<'a> RegexCache<'a> {
+    pub fn new(arena: &'a Bump) -> Self {
+        Self {
+            arena,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn get_or_compile(
+        &mut self,
+        pattern: &str,
+        case_insensitive: bool,
+        multi_line: bool,
+    ) -> Result<&'a RegexLiteral, regress::Error> {
+        let mut flags = String::new();
+        if case_insensitive {
+            flags.push('i');
+        }
+        if multi_line {
+            flags.push('m');
+        }
+        let key = (pattern.to_string(), flags);
+
+        if let Some(regex) = self.entries.get(&key) {
+            self.hits += 1;
+            return Ok(regex);
+        }
+
+        self.misses += 1;
+        let regex = &*self
+            .arena
+            .alloc(RegexLiteral::new(pattern, case_insensitive, multi_line)?);
+
+        if self.order.len() >= CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.order.push_back(key.clone());
+        self.entries.insert(key, regex);
+
+        Ok(regex)
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_oldest() {
+        let arena = Bump::new();
+        let mut cache = RegexCache::new(&arena);
+
+        for i in 0..=CAPACITY {
+            cache.get_or_compile(&i.to_string(), false, false).unwrap();
+        }
+        assert_eq!(cache.misses(), CAPACITY + 1);
+
+        // The most recent pattern is still cached, and the flags are part of the key
+        cache
+            .get_or_compile(&CAPACITY.to_string(), false, false)
+            .unwrap();
+        assert_eq!(cache.hits(), 1);
+        cache
+            .get_or_compile(&CAPACITY.to_string(), true, false)
+            .unwrap();
+        assert_eq!(cache.misses(), CAPACITY + 2);
+
+        // The first pattern was evicted
+        cache.get_or_compile("0", false, false).unwrap();
+        assert_eq!(cache.misses(), CAPACITY + 3);
+    }
+}
@@ -0,0 +1,370 @@
+//! Function signatures, e.g. `<s-n?:s>`, as described in the JSONata
+//! [documentation](http://docs.jsonata.org/programming#function-signatures).
+//!
+//! The reference implementation turns each signature into a regular expression which is run
+//! against a string of type symbols every time the function is called. Here a signature is
+//! compiled once into a list of parameters, and the arguments are matched against those
+//! directly.
+
+use bitflags::bitflags;
+use bumpalo::Bump;
+
+use super::value::{ArrayFlags, Value};
+use crate::{Error, Result};
+
+bitflags! {
+    /// A set of value types, as denoted by the symbols used in signatures.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Types: u8 {
+        const STRING    = 0b00000001;
+        const NUMBER    = 0b00000010;
+        const BOOL      = 0b00000100;
+        const NULL      = 0b00001000;
+        const ARRAY     = 0b00010000;
+        const OBJECT    = 0b00100000;
+        const FUNCTION  = 0b01000000;
+        const UNDEFINED = 0b10000000;
+    }
+}
+
+impl Types {
+    /// The type of a value, as a single flag.
+    pub fn of(value: &Value) -> Types {
+        match value {
+            Value::Undefined => Types::UNDEFINED,
+            Value::Null => Types::NULL,
+            Value::Number(..) => Types::NUMBER,
+            Value::Bool(..) => Types::BOOL,
+            Value::String(..) => Types::STRING,
+            Value::Array(..) | Value::Range(..) => Types::ARRAY,
+            Value::Object(..) => Types::OBJECT,
+            Value::Regex(..)
+            | Value::Lambda { .. }
+            | Value::NativeFn { .. }
+            | Value::Transformer { .. } => Types::FUNCTION,
+        }
+    }
+
+    fn from_symbol(symbol: char) -> Types {
+        match symbol {
+            's' => Types::STRING,
+            'n' => Types::NUMBER,
+            'b' => Types::BOOL,
+            'l' => Types::NULL,
+            'a' => Types::ARRAY,
+            'o' => Types::OBJECT,
+            'f' => Types::FUNCTION,
+            _ => Types::empty(),
+        }
+    }
+
+    /// The name used for the members of an array of this type in error messages.
+    fn name(symbol: char) -> &'static str {
+        match symbol {
+            's' => "string",
+            'n' => "number",
+            'b' => "boolean",
+            'l' => "null",
+            'a' => "array",
+            'o' => "object",
+            'f' => "function",
+            _ => "undefined",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Param {
+    /// The symbol that introduced the parameter, `(` for a choice group
+    symbol: char,
+
+    /// The types of argument that the parameter accepts
+    accepts: Types,
+
+    /// The type parameter of an array or function, e.g. the `n` in `a<n>`
+    subtype: Option<String>,
+
+    /// `?` (or `-`): the parameter can be omitted
+    optional: bool,
+
+    /// `+`: the parameter can consume one or more arguments
+    variadic: bool,
+
+    /// `-`: the context is used if the parameter is omitted
+    use_context: bool,
+}
+
+impl Param {
+    fn new(symbol: char, accepts: Types) -> Self {
+        Self {
+            symbol,
+            accepts,
+            subtype: None,
+            optional: false,
+            variadic: false,
+            use_context: false,
+        }
+    }
+}
+
+/// A compiled function signature.
+#[derive(Debug, Clone)]
+pub struct Signature {
+    params: Vec<Param>,
+}
+
+impl Signature {
+    /// Compile a signature, including its enclosing `<` and `>`. The `char_index` of the
+    /// opening `<` is used to report errors.
+    pub fn parse(signature: &str, char_index: usize) -> Result<Signature> {
+        let chars: Vec<char> = signature.chars().collect();
+        let mut params: Vec<Param> = Vec::new();
+        let mut position = 1;
+
+        while position < chars.len() {
+            let symbol = chars[position];
+
+            // Everything after the colon is the return type, which isn't checked
+            if symbol == ':' {
+                break;
+            }
+
+            match symbol {
+                's' | 'n' | 'b' | 'l' | 'o' => params.push(Param::new(
+                    symbol,
+                    Types::from_symbol(symbol) | Types::UNDEFINED,
+                )),
+                'a' | 'x' => params.push(Param::new(symbol, Types::all())),
+                'j' => params.push(Param::new(symbol, Types::all() - Types::FUNCTION)),
+                'f' => params.push(Param::new(symbol, Types::FUNCTION)),
+                '-' => {
+                    if let Some(param) = params.last_mut() {
+                        param.optional = true;
+                        param.use_context = true;
+                    }
+                }
+                '?' => {
+                    if let Some(param) = params.last_mut() {
+                        param.optional = true;
+                    }
+                }
+                '+' => {
+                    if let Some(param) = params.last_mut() {
+                        param.variadic = true;
+                    }
+                }
+                '(' => {
+                    let end = find_closing_bracket(&chars, position, '(', ')');
+                    let choice: String = chars[position + 1..end].iter().collect();
+                    if choice.contains('<') {
+                        return Err(Error::S0402ChoiceGroupParameterized(
+                            char_index + position,
+                            choice,
+                        ));
+                    }
+                    let accepts = choice
+                        .chars()
+                        .fold(Types::UNDEFINED, |types, c| types | Types::from_symbol(c));
+                    params.push(Param::new(symbol, accepts));
+                    position = end;
+                }
+                '<' => match params.last_mut() {
+                    Some(param) if param.symbol == 'a' || param.symbol == 'f' => {
+                        let end = find_closing_bracket(&chars, position, '<', '>');
+                        param.subtype = Some(chars[position + 1..end].iter().collect());
+                        position = end;
+                    }
+                    param => {
+                        return Err(Error::S0401TypeParamsNotSupported(
+                            char_index + position,
+                            param.map(|p| p.symbol.to_string()).unwrap_or_default(),
+                        ))
+                    }
+                },
+                _ => {}
+            }
+
+            position += 1;
+        }
+
+        Ok(Signature { params })
+    }
+
+    /// The number of parameters in the signature.
+    pub fn arity(&self) -> usize {
+        self.params.len()
+    }
+
+    /// The number of parameters which aren't marked optional with `?`, which is how many
+    /// arguments the function is given when it's passed as a callback, in the same way that
+    /// jsonata-js only counts JavaScript parameters without default values.
+    pub fn callback_arity(&self) -> usize {
+        self.params
+            .iter()
+            .filter(|param| !param.optional || param.use_context)
+            .count()
+    }
+
+    /// Validate the arguments to a function against the signature, returning the arguments
+    /// that should actually be passed to it.
+    ///
+    /// Omitted `-` parameters are replaced by the context, and anything passed to an array
+    /// parameter that isn't already an array is wrapped in one.
+    pub fn validate<'a>(
+        &self,
+        arena: &'a Bump,
+        char_index: usize,
+        name: &str,
+        args: &[&'a Value<'a>],
+        context: &'a Value<'a>,
+    ) -> Result<Vec<&'a Value<'a>>> {
+        let types: Vec<Types> = args.iter().map(|arg| Types::of(arg)).collect();
+
+        let Some(counts) = Self::match_params(&self.params, &types, true) else {
+            return Err(self.validation_error(char_index, name, &types));
+        };
+
+        let mut validated = Vec::with_capacity(self.params.len());
+        let mut index = 0;
+
+        for (param, count) in self.params.iter().zip(counts) {
+            if count == 0 {
+                if param.use_context {
+                    let context = if context.has_flags(ArrayFlags::WRAPPED) {
+                        context.get_member(0)
+                    } else {
+                        context
+                    };
+                    if !param.accepts.contains(Types::of(context)) {
+                        return Err(Error::T0411ContextValueNotCompatible(
+                            char_index,
+                            index + 1,
+                            name.to_string(),
+                        ));
+                    }
+                    validated.push(context);
+                } else {
+                    // An optional parameter with nothing to match doesn't consume an argument
+                    validated.push(Value::undefined());
+                }
+                continue;
+            }
+
+            for _ in 0..count {
+                let arg = args.get(index).copied().unwrap_or_else(Value::undefined);
+                if param.symbol == 'a' && !arg.is_undefined() {
+                    if let Some(ref subtype) = param.subtype {
+                        if !Self::is_array_of(arg, subtype) {
+                            return Err(Error::T0412ArgumentMustBeArrayOfType(
+                                char_index,
+                                index + 1,
+                                name.to_string(),
+                                Types::name(subtype.chars().next().unwrap_or_default()).to_string(),
+                            ));
+                        }
+                    }
+                    validated.push(Value::wrap_in_array_if_needed(
+                        arena,
+                        arg,
+                        ArrayFlags::empty(),
+                    ));
+                } else {
+                    validated.push(arg);
+                }
+                index += 1;
+            }
+        }
+
+        Ok(validated)
+    }
+
+    /// Whether an argument satisfies the type parameter of an array, either by being an
+    /// array where every member has that type, or by being a single value of that type.
+    fn is_array_of<'a>(arg: &'a Value<'a>, subtype: &str) -> bool {
+        if !arg.is_array() {
+            let mut symbol = subtype.chars();
+            return matches!((symbol.next(), symbol.next()), (Some(c), None) if Types::of(arg) == Types::from_symbol(c));
+        }
+
+        let mut members = arg.members();
+        match members.next() {
+            None => true,
+            Some(first) => {
+                let item_type = Types::of(first);
+                item_type == Types::from_symbol(subtype.chars().next().unwrap_or_default())
+                    && members.all(|member| Types::of(member) == item_type)
+            }
+        }
+    }
+
+    /// Match the argument types against the parameters, in the same way as the regular
+    /// expression built by the reference implementation: greedily, backtracking whenever the
+    /// remaining parameters can't be matched. Returns how many arguments each parameter took.
+    ///
+    /// If `to_end` is false then there can be arguments left over.
+    fn match_params(params: &[Param], types: &[Types], to_end: bool) -> Option<Vec<usize>> {
+        fn match_from(
+            params: &[Param],
+            types: &[Types],
+            to_end: bool,
+            counts: &mut Vec<usize>,
+        ) -> bool {
+            let Some((param, rest)) = params.split_first() else {
+                return !to_end || types.is_empty();
+            };
+
+            let limit = if param.variadic { types.len() } else { 1 };
+            let max = types
+                .iter()
+                .take(limit)
+                .take_while(|t| param.accepts.contains(**t))
+                .count();
+            let min = if param.optional { 0 } else { 1 };
+
+            for count in (min..=max).rev() {
+                counts.push(count);
+                if match_from(rest, &types[count..], to_end, counts) {
+                    return true;
+                }
+                counts.pop();
+            }
+
+            false
+        }
+
+        let mut counts = Vec::with_capacity(params.len());
+        match_from(params, types, to_end, &mut counts).then_some(counts)
+    }
+
+    /// Find the first argument that doesn't match the signature, by matching increasingly
+    /// long runs of the parameters against the arguments.
+    fn validation_error(&self, char_index: usize, name: &str, types: &[Types]) -> Error {
+        let mut good_to = 0;
+
+        for end in 1..=self.params.len() {
+            match Self::match_params(&self.params[..end], types, false) {
+                Some(counts) => good_to = counts.iter().sum(),
+                None => break,
+            }
+        }
+
+        Error::T0410ArgumentNotValid(char_index, good_to + 1, name.to_string())
+    }
+}
+
+fn find_closing_bracket(chars: &[char], start: usize, open: char, close: char) -> usize {
+    let mut depth = 1;
+
+    for (position, c) in chars.iter().enumerate().skip(start + 1) {
+        if *c == close {
+            depth -= 1;
+            if depth == 0 {
+                return position;
+            }
+        } else if *c == open {
+            depth += 1;
+        }
+    }
+
+    chars.len()
+}
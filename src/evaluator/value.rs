@@ -5,22 +5,25 @@ use bumpalo::boxed::Box;
 use bumpalo::collections::String as BumpString;
 use bumpalo::collections::Vec as BumpVec;
 use bumpalo::Bump;
-use hashbrown::DefaultHashBuilder;
-use hashbrown::HashMap;
 
 use super::frame::Frame;
 use super::functions::FunctionContext;
+use super::signature::Signature;
 use crate::parser::ast::{Ast, AstKind, RegexLiteral};
 use crate::{Error, Result};
 
+mod from_serde;
 pub mod impls;
 pub mod iterator;
+pub mod object;
 mod range;
 pub mod serialize;
 
+use self::from_serde::ValueSerializer;
 use self::range::Range;
 use self::serialize::{DumpFormatter, PrettyFormatter, Serializer};
 pub use iterator::MemberIterator;
+pub use object::ObjectMap;
 
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -53,7 +56,7 @@ pub enum Value<'a> {
     String(BumpString<'a>),
     Regex(std::boxed::Box<RegexLiteral>),
     Array(BumpVec<'a, &'a Value<'a>>, ArrayFlags),
-    Object(HashMap<BumpString<'a>, &'a Value<'a>, DefaultHashBuilder, &'a Bump>),
+    Object(ObjectMap<'a>),
     Range(Range<'a>),
     Lambda {
         ast: Box<'a, Ast>,
@@ -63,6 +66,7 @@ pub enum Value<'a> {
     NativeFn {
         name: String,
         arity: usize,
+        signature: Option<Signature>,
         func: fn(FunctionContext<'a, '_>, &[&'a Value<'a>]) -> Result<&'a Value<'a>>,
     },
     Transformer {
@@ -80,7 +84,7 @@ impl<'a> Value<'a> {
         unsafe { std::mem::transmute::<&Value<'static>, &'a Value<'a>>(&UNDEFINED) }
     }
 
-    pub fn null(arena: &Bump) -> &mut Value {
+    pub fn null(arena: &Bump) -> &mut Value<'_> {
         arena.alloc(Value::Null)
     }
 
@@ -92,11 +96,11 @@ impl<'a> Value<'a> {
         }
     }
 
-    pub fn number(arena: &Bump, value: impl Into<f64>) -> &mut Value {
+    pub fn number(arena: &Bump, value: impl Into<f64>) -> &mut Value<'_> {
         arena.alloc(Value::Number(value.into()))
     }
 
-    pub fn number_from_u128(arena: &Bump, value: u128) -> Result<&mut Value> {
+    pub fn number_from_u128(arena: &Bump, value: u128) -> Result<&mut Value<'_>> {
         let value_f64 = value as f64;
         if value_f64 as u128 != value {
             // number is too large to retain precision
@@ -109,7 +113,7 @@ impl<'a> Value<'a> {
         arena.alloc(Value::String(BumpString::from_str_in(value, arena)))
     }
 
-    pub fn array(arena: &Bump, flags: ArrayFlags) -> &mut Value {
+    pub fn array(arena: &Bump, flags: ArrayFlags) -> &mut Value<'_> {
         let v = BumpVec::new_in(arena);
         arena.alloc(Value::Array(v, flags))
     }
@@ -122,30 +126,66 @@ impl<'a> Value<'a> {
         arena.alloc(Value::Array(arr, flags))
     }
 
-    pub fn array_with_capacity(arena: &Bump, capacity: usize, flags: ArrayFlags) -> &mut Value {
+    pub fn array_with_capacity(arena: &Bump, capacity: usize, flags: ArrayFlags) -> &mut Value<'_> {
         arena.alloc(Value::Array(
             BumpVec::with_capacity_in(capacity, arena),
             flags,
         ))
     }
 
-    pub fn object(arena: &Bump) -> &mut Value {
-        arena.alloc(Value::Object(HashMap::new_in(arena)))
+    pub fn object(arena: &Bump) -> &mut Value<'_> {
+        arena.alloc(Value::Object(ObjectMap::new_in(arena)))
     }
 
-    pub fn object_from<H>(
-        hash: &HashMap<BumpString<'a>, &'a Value<'a>, H, &'a Bump>,
-        arena: &'a Bump,
-    ) -> &'a mut Value<'a> {
-        let result = Value::object_with_capacity(arena, hash.len());
-        if let Value::Object(o) = result {
-            o.extend(hash.iter().map(|(k, v)| (k.clone(), *v)));
+    pub fn object_from(object: &ObjectMap<'a>, arena: &'a Bump) -> &'a mut Value<'a> {
+        let result = Value::object_with_capacity(arena, object.len());
+        for (key, value) in object.iter() {
+            result.insert(key, value);
         }
         result
     }
 
-    pub fn object_with_capacity(arena: &Bump, capacity: usize) -> &mut Value {
-        arena.alloc(Value::Object(HashMap::with_capacity_in(capacity, arena)))
+    pub fn object_with_capacity(arena: &Bump, capacity: usize) -> &mut Value<'_> {
+        arena.alloc(Value::Object(ObjectMap::with_capacity_in(capacity, arena)))
+    }
+
+    /// Create a value from a `serde_json::Value`.
+    pub fn from_json(arena: &'a Bump, value: &serde_json::Value) -> &'a mut Value<'a> {
+        match value {
+            serde_json::Value::Null => Value::null(arena),
+            serde_json::Value::Bool(b) => arena.alloc(Value::Bool(*b)),
+            serde_json::Value::Number(n) => Value::number(arena, n.as_f64().unwrap()),
+            serde_json::Value::String(s) => Value::string(arena, s),
+            serde_json::Value::Array(a) => {
+                let array = Value::array_with_capacity(arena, a.len(), ArrayFlags::empty());
+                for v in a.iter() {
+                    array.push(Value::from_json(arena, v))
+                }
+                array
+            }
+            serde_json::Value::Object(o) => {
+                let object = Value::object_with_capacity(arena, o.len());
+                for (k, v) in o.iter() {
+                    object.insert(k, Value::from_json(arena, v));
+                }
+                object
+            }
+        }
+    }
+
+    /// Parse a value from JSON text. Only strict JSON is accepted, and errors report the line
+    /// and column in the input where parsing failed.
+    pub fn from_json_str(arena: &'a Bump, input: &str) -> Result<&'a mut Value<'a>> {
+        crate::json::parse(arena, input)
+    }
+
+    /// Create a value from anything which implements `serde::Serialize`, mapping it to JSON the
+    /// same way `serde_json` does. Fails with an `I0101` error if serialization fails.
+    pub fn from_serialize<T: serde::Serialize + ?Sized>(
+        arena: &'a Bump,
+        value: &T,
+    ) -> Result<&'a mut Value<'a>> {
+        value.serialize(ValueSerializer::new(arena))
     }
 
     pub fn lambda(
@@ -170,6 +210,21 @@ impl<'a> Value<'a> {
         arena.alloc(Value::NativeFn {
             name: name.to_string(),
             arity,
+            signature: None,
+            func,
+        })
+    }
+
+    pub fn nativefn_with_signature(
+        arena: &'a Bump,
+        name: &str,
+        signature: &Signature,
+        func: fn(FunctionContext<'a, '_>, &[&'a Value<'a>]) -> Result<&'a Value<'a>>,
+    ) -> &'a mut Value<'a> {
+        arena.alloc(Value::NativeFn {
+            name: name.to_string(),
+            arity: signature.arity(),
+            signature: Some(signature.clone()),
             func,
         })
     }
@@ -342,7 +397,7 @@ impl<'a> Value<'a> {
         }
     }
 
-    pub fn entries(&self) -> hashbrown::hash_map::Iter<'_, BumpString<'a>, &'a Value> {
+    pub fn entries(&self) -> object::Iter<'_, 'a> {
         match self {
             Value::Object(map) => map.iter(),
             _ => panic!("Not an object"),
@@ -443,9 +498,7 @@ impl<'a> Value<'a> {
 
     pub fn insert(&mut self, key: &str, value: &'a Value<'a>) {
         match *self {
-            Value::Object(ref mut map) => {
-                map.insert(BumpString::from_str_in(key, map.allocator()), value);
-            }
+            Value::Object(ref mut map) => map.insert(key, value),
             _ => panic!("Not an object"),
         }
     }
@@ -520,7 +573,17 @@ impl<'a> Value<'a> {
             Self::Array(a, f) => Value::array_from(arena, a.clone(), *f),
             Self::Object(o) => Value::object_from(o, arena),
             Self::Lambda { ast, input, frame } => Value::lambda(arena, ast, input, frame.clone()),
-            Self::NativeFn { name, arity, func } => Value::nativefn(arena, name, *arity, *func),
+            Self::NativeFn {
+                name,
+                arity,
+                signature,
+                func,
+            } => arena.alloc(Value::NativeFn {
+                name: name.clone(),
+                arity: *arity,
+                signature: signature.clone(),
+                func: *func,
+            }),
             Self::Transformer {
                 pattern,
                 update,
@@ -547,21 +610,4 @@ impl<'a> Value<'a> {
             serializer.serialize(self).expect("Shouldn't fail")
         }
     }
-
-    // TODO: I don't have a good way to make modifications to values right now, so here's this absolutely
-    // no good, very bad, shouldn't exist reference transmuter :(
-    //
-    // This only exists for object transfomers, which specifically reach into existing values to make
-    // changes by updating and removing keys.
-    //
-    // Need to think up another way, but the whole evaluation pipeline is based on the immutability of Value,
-    // so something needs to give.
-    #[allow(invalid_reference_casting)]
-    pub fn __very_unsafe_make_mut(&'a self) -> &'a mut Value<'a> {
-        unsafe {
-            let const_ptr = self as *const Value<'a>;
-            let mut_ptr = const_ptr as *mut Value<'a>;
-            &mut *mut_ptr
-        }
-    }
 }
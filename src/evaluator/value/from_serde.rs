@@ -0,0 +1,387 @@
+use std::fmt::Display;
+
+use bumpalo::Bump;
+use serde::ser::{self, Serialize};
+
+use super::{ArrayFlags, Value};
+use crate::{Error, Result};
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::I0101UnserializableInput(msg.to_string())
+    }
+}
+
+/// A serde `Serializer` which builds a [`Value`] in an arena, without going through JSON text or
+/// a `serde_json::Value` first.
+///
+/// Values are mapped to JSON the same way `serde_json` maps them: `None`, units and non-finite
+/// numbers become `null`, enum variants with data become single-key objects, and map keys must
+/// be strings, numbers or booleans.
+pub struct ValueSerializer<'a> {
+    arena: &'a Bump,
+}
+
+impl<'a> ValueSerializer<'a> {
+    pub fn new(arena: &'a Bump) -> Self {
+        Self { arena }
+    }
+
+    fn variant(&self, variant: &'static str, value: &'a Value<'a>) -> Result<&'a mut Value<'a>> {
+        let object = Value::object_with_capacity(self.arena, 1);
+        object.insert(variant, value);
+        Ok(object)
+    }
+}
+
+impl<'a> ser::Serializer for ValueSerializer<'a> {
+    type Ok = &'a mut Value<'a>;
+    type Error = Error;
+
+    type SerializeSeq = SerializeArray<'a>;
+    type SerializeTuple = SerializeArray<'a>;
+    type SerializeTupleStruct = SerializeArray<'a>;
+    type SerializeTupleVariant = SerializeArray<'a>;
+    type SerializeMap = SerializeObject<'a>;
+    type SerializeStruct = SerializeObject<'a>;
+    type SerializeStructVariant = SerializeObject<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        Ok(self.arena.alloc(Value::Bool(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        if v.is_finite() {
+            Ok(Value::number(self.arena, v))
+        } else {
+            Ok(Value::null(self.arena))
+        }
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        Ok(Value::string(self.arena, v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        let array = Value::array_with_capacity(self.arena, v.len(), ArrayFlags::empty());
+        for byte in v {
+            array.push(Value::number(self.arena, *byte));
+        }
+        Ok(array)
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok(Value::null(self.arena))
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Ok(Value::null(self.arena))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        let value = value.serialize(ValueSerializer::new(self.arena))?;
+        self.variant(variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SerializeArray::new(self.arena, len.unwrap_or(0), None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        Ok(SerializeArray::new(self.arena, len, None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Ok(SerializeArray::new(self.arena, len, None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(SerializeArray::new(self.arena, len, Some(variant)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(SerializeObject::new(self.arena, len.unwrap_or(0), None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        Ok(SerializeObject::new(self.arena, len, None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(SerializeObject::new(self.arena, len, Some(variant)))
+    }
+}
+
+/// Builds an array from a sequence or tuple, which is wrapped in an object when it's the data of
+/// an enum variant.
+pub struct SerializeArray<'a> {
+    arena: &'a Bump,
+    array: &'a mut Value<'a>,
+    variant: Option<&'static str>,
+}
+
+impl<'a> SerializeArray<'a> {
+    fn new(arena: &'a Bump, capacity: usize, variant: Option<&'static str>) -> Self {
+        Self {
+            arena,
+            array: Value::array_with_capacity(arena, capacity, ArrayFlags::empty()),
+            variant,
+        }
+    }
+
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        let value = value.serialize(ValueSerializer::new(self.arena))?;
+        self.array.push(value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<&'a mut Value<'a>> {
+        match self.variant {
+            Some(variant) => ValueSerializer::new(self.arena).variant(variant, self.array),
+            None => Ok(self.array),
+        }
+    }
+}
+
+impl<'a> ser::SerializeSeq for SerializeArray<'a> {
+    type Ok = &'a mut Value<'a>;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTuple for SerializeArray<'a> {
+    type Ok = &'a mut Value<'a>;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for SerializeArray<'a> {
+    type Ok = &'a mut Value<'a>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for SerializeArray<'a> {
+    type Ok = &'a mut Value<'a>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+/// Builds an object from a map or struct, which is wrapped in another object when it's the data
+/// of an enum variant.
+pub struct SerializeObject<'a> {
+    arena: &'a Bump,
+    object: &'a mut Value<'a>,
+    variant: Option<&'static str>,
+    key: Option<String>,
+}
+
+impl<'a> SerializeObject<'a> {
+    fn new(arena: &'a Bump, capacity: usize, variant: Option<&'static str>) -> Self {
+        Self {
+            arena,
+            object: Value::object_with_capacity(arena, capacity),
+            variant,
+            key: None,
+        }
+    }
+
+    fn insert<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<()> {
+        let value = value.serialize(ValueSerializer::new(self.arena))?;
+        self.object.insert(key, value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<&'a mut Value<'a>> {
+        match self.variant {
+            Some(variant) => ValueSerializer::new(self.arena).variant(variant, self.object),
+            None => Ok(self.object),
+        }
+    }
+}
+
+impl<'a> ser::SerializeMap for SerializeObject<'a> {
+    type Ok = &'a mut Value<'a>;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+        // Keys are serialized like any other value, and then turned into strings the way
+        // serde_json does it
+        let key = key.serialize(ValueSerializer::new(self.arena))?;
+        self.key = Some(match key {
+            Value::String(ref key) => key.to_string(),
+            Value::Number(..) | Value::Bool(..) => key.serialize(false),
+            _ => return Err(ser::Error::custom("key must be a string")),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("serialize_value called before serialize_key"))?;
+        self.insert(&key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStruct for SerializeObject<'a> {
+    type Ok = &'a mut Value<'a>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for SerializeObject<'a> {
+    type Ok = &'a mut Value<'a>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
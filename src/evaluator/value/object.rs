@@ -0,0 +1,145 @@
+use std::hash::BuildHasher;
+
+use bumpalo::collections::String as BumpString;
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+use hashbrown::{DefaultHashBuilder, HashTable};
+
+use super::Value;
+
+/// The entries of an object, which are kept in the order they were first inserted in, as they
+/// are in reference JSONata.
+///
+/// The entries are stored in a vector, along with a hash table of their positions in it for
+/// looking them up by key.
+pub struct ObjectMap<'a> {
+    entries: BumpVec<'a, (BumpString<'a>, &'a Value<'a>)>,
+    indices: HashTable<usize, &'a Bump>,
+    hash_builder: DefaultHashBuilder,
+}
+
+impl<'a> ObjectMap<'a> {
+    pub fn new_in(arena: &'a Bump) -> Self {
+        Self::with_capacity_in(0, arena)
+    }
+
+    pub fn with_capacity_in(capacity: usize, arena: &'a Bump) -> Self {
+        Self {
+            entries: BumpVec::with_capacity_in(capacity, arena),
+            indices: HashTable::with_capacity_in(capacity, arena),
+            hash_builder: DefaultHashBuilder::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&'a Value<'a>> {
+        self.position(key).map(|index| self.entries[index].1)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// Insert an entry at the end, or replace the value of an existing entry in its place.
+    pub fn insert(&mut self, key: &str, value: &'a Value<'a>) {
+        if let Some(index) = self.position(key) {
+            self.entries[index].1 = value;
+            return;
+        }
+
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.entries.len();
+        let arena = self.entries.bump();
+        self.entries
+            .push((BumpString::from_str_in(key, arena), value));
+
+        let entries = &self.entries;
+        let hash_builder = &self.hash_builder;
+        self.indices.insert_unique(hash, index, |&index| {
+            hash_builder.hash_one(entries[index].0.as_str())
+        });
+    }
+
+    /// Remove an entry, keeping the order of the others.
+    pub fn remove(&mut self, key: &str) -> Option<&'a Value<'a>> {
+        let hash = self.hash_builder.hash_one(key);
+        let entries = &self.entries;
+        let index = match self
+            .indices
+            .find_entry(hash, |&index| entries[index].0 == key)
+        {
+            Ok(entry) => entry.remove().0,
+            Err(_) => return None,
+        };
+
+        let (_, value) = self.entries.remove(index);
+        for position in self.indices.iter_mut() {
+            if *position > index {
+                *position -= 1;
+            }
+        }
+
+        Some(value)
+    }
+
+    pub fn iter(&self) -> Iter<'_, 'a> {
+        Iter(self.entries.iter())
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &BumpString<'a>> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        let hash = self.hash_builder.hash_one(key);
+        self.indices
+            .find(hash, |&index| self.entries[index].0 == key)
+            .copied()
+    }
+}
+
+/// Objects are equal when they have the same entries, in any order.
+impl PartialEq for ObjectMap<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key).is_some_and(|other| **value == *other))
+    }
+}
+
+impl std::fmt::Debug for ObjectMap<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// An iterator over the entries of an [`ObjectMap`] in order.
+pub struct Iter<'o, 'a>(std::slice::Iter<'o, (BumpString<'a>, &'a Value<'a>)>);
+
+impl<'o, 'a> Iterator for Iter<'o, 'a> {
+    type Item = (&'o BumpString<'a>, &'o &'a Value<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(key, value)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_, '_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(key, value)| (key, value))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_, '_> {}
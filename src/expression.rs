@@ -0,0 +1,244 @@
+use bumpalo::Bump;
+
+use crate::evaluator::{frame::Frame, functions::*, EvaluationOptions, EvaluationStats, Evaluator};
+use crate::json;
+use crate::language::{Language, Languages};
+use crate::parser::{self, ast::Ast};
+use crate::{ArrayFlags, Bindings, Result, Value};
+
+/// The expression used to compose functions with `~>`.
+const CHAIN: &str = "function($f, $g) { function($x){ $g($f($x)) } }";
+
+/// A compiled JSONata expression.
+///
+/// Unlike [`JsonAta`](crate::JsonAta), an `Expression` isn't tied to an arena. It's compiled once,
+/// can be cached and shared between threads, and each evaluation creates all of its state in the
+/// arena it's given.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    ast: Ast,
+    chain_ast: Ast,
+    languages: Languages,
+}
+
+impl Expression {
+    pub fn compile(expr: &str) -> Result<Expression> {
+        Ok(Self {
+            ast: parser::parse(expr)?,
+            chain_ast: parser::parse(CHAIN)?,
+            languages: Languages::default(),
+        })
+    }
+
+    /// Register a language for the date/time functions under a tag such as `nl`, replacing
+    /// any existing language with that tag. English, Spanish, German and French are built in.
+    pub fn register_language(&mut self, tag: &str, language: impl Language + 'static) {
+        self.languages.register(tag, language);
+    }
+
+    pub fn ast(&self) -> &Ast {
+        &self.ast
+    }
+
+    pub fn evaluate<'a>(
+        &self,
+        arena: &'a Bump,
+        input: Option<&str>,
+        bindings: Option<&Bindings<'a>>,
+    ) -> Result<&'a Value<'a>> {
+        let input = parse_input(arena, input)?;
+        self.evaluate_input(arena, input, bindings, &EvaluationOptions::default())
+            .0
+    }
+
+    /// Evaluate against an input which has already been created in the arena, so that it can be
+    /// shared between evaluations without being parsed again for each of them.
+    pub fn evaluate_value<'a>(
+        &self,
+        arena: &'a Bump,
+        input: &'a Value<'a>,
+        bindings: Option<&Bindings<'a>>,
+    ) -> Result<&'a Value<'a>> {
+        self.evaluate_input(arena, input, bindings, &EvaluationOptions::default())
+            .0
+    }
+
+    pub fn evaluate_timeboxed<'a>(
+        &self,
+        arena: &'a Bump,
+        input: Option<&str>,
+        max_depth: Option<usize>,
+        time_limit: Option<usize>,
+    ) -> Result<&'a Value<'a>> {
+        let input = parse_input(arena, input)?;
+        self.evaluate_value_timeboxed(arena, input, max_depth, time_limit)
+    }
+
+    pub fn evaluate_value_timeboxed<'a>(
+        &self,
+        arena: &'a Bump,
+        input: &'a Value<'a>,
+        max_depth: Option<usize>,
+        time_limit: Option<usize>,
+    ) -> Result<&'a Value<'a>> {
+        let options = EvaluationOptions {
+            max_depth,
+            time_limit,
+            ..Default::default()
+        };
+        self.evaluate_input(arena, input, None, &options).0
+    }
+
+    /// Evaluate with both bindings and limits, returning the statistics of the evaluation along
+    /// with its result. The input can be created with [`Value::from_json_str`], or be
+    /// [`Value::undefined`] for no input.
+    pub fn evaluate_with_options<'a>(
+        &self,
+        arena: &'a Bump,
+        input: &'a Value<'a>,
+        bindings: Option<&Bindings<'a>>,
+        options: &EvaluationOptions,
+    ) -> (Result<&'a Value<'a>>, EvaluationStats) {
+        self.evaluate_input(arena, input, bindings, options)
+    }
+
+    fn evaluate_input<'a>(
+        &self,
+        arena: &'a Bump,
+        input: &'a Value<'a>,
+        bindings: Option<&Bindings<'a>>,
+        options: &EvaluationOptions,
+    ) -> (Result<&'a Value<'a>>, EvaluationStats) {
+        let input = wrap_input(arena, input);
+        let frame = evaluation_frame(&natives(arena, &Frame::new()), bindings, input);
+        let evaluator = self.evaluator(arena, options);
+        let result = evaluator.evaluate(&self.ast, input, &frame);
+        (result, evaluator.stats())
+    }
+
+    pub(crate) fn evaluator<'a>(
+        &self,
+        arena: &'a Bump,
+        options: &EvaluationOptions,
+    ) -> Evaluator<'a> {
+        Evaluator::new(
+            Some(self.chain_ast.clone()),
+            arena,
+            options.max_depth,
+            options.time_limit,
+        )
+        .with_languages(self.languages.clone())
+        .with_regex_budget(options.regex_budget)
+    }
+}
+
+/// Parse the JSON input to an evaluation.
+pub(crate) fn parse_input<'a>(arena: &'a Bump, input: Option<&str>) -> Result<&'a Value<'a>> {
+    match input {
+        Some(input) => Ok(json::parse(arena, input)?),
+        None => Ok(Value::undefined()),
+    }
+}
+
+/// If the input is an array, wrap it in an array so that it gets treated as a single input.
+pub(crate) fn wrap_input<'a>(arena: &'a Bump, input: &'a Value<'a>) -> &'a Value<'a> {
+    if input.is_array() {
+        Value::wrap_in_array(arena, input, ArrayFlags::WRAPPED)
+    } else {
+        input
+    }
+}
+
+/// Create the frame for a single evaluation, with the bindings and the input bound to `$`.
+pub(crate) fn evaluation_frame<'a>(
+    parent: &Frame<'a>,
+    bindings: Option<&Bindings<'a>>,
+    input: &'a Value<'a>,
+) -> Frame<'a> {
+    let frame = match bindings {
+        Some(bindings) => bindings.frame(parent),
+        None => Frame::new_with_parent(parent),
+    };
+    frame.bind("$", input);
+    frame
+}
+
+/// Create a frame with the built-in functions bound, which take precedence over the variables
+/// in `parent`.
+pub(crate) fn natives<'a>(arena: &'a Bump, parent: &Frame<'a>) -> Frame<'a> {
+    let frame = Frame::new_with_parent(parent);
+
+    macro_rules! bind_native {
+        ($name:literal, $arity:literal, $fn:ident) => {
+            frame.bind($name, Value::nativefn(arena, $name, $arity, $fn));
+        };
+    }
+
+    bind_native!("abs", 1, fn_abs);
+    bind_native!("append", 2, fn_append);
+    bind_native!("average", 1, fn_average);
+    bind_native!("assert", 2, fn_assert);
+    bind_native!("base64decode", 1, fn_base64_decode);
+    bind_native!("base64encode", 1, fn_base64_encode);
+    bind_native!("boolean", 1, fn_boolean);
+    bind_native!("ceil", 1, fn_ceil);
+    bind_native!("contains", 2, fn_contains);
+    bind_native!("count", 1, fn_count);
+    bind_native!("decodeUrl", 1, fn_decode_url);
+    bind_native!("decodeUrlComponent", 1, fn_decode_url_component);
+    bind_native!("distinct", 1, fn_distinct);
+    bind_native!("each", 2, fn_each);
+    bind_native!("encodeUrl", 1, fn_encode_url);
+    bind_native!("encodeUrlComponent", 1, fn_encode_url_component);
+    bind_native!("error", 1, fn_error);
+    bind_native!("eval", 2, fn_eval);
+    bind_native!("exists", 1, fn_exists);
+    bind_native!("fromMillis", 4, from_millis);
+    bind_native!("toMillis", 3, to_millis);
+    bind_native!("single", 2, single);
+    bind_native!("filter", 2, fn_filter);
+    bind_native!("floor", 1, fn_floor);
+    bind_native!("formatNumber", 3, fn_format_number);
+    bind_native!("formatBase", 2, fn_format_base);
+    bind_native!("formatInteger", 2, fn_format_integer);
+    bind_native!("join", 2, fn_join);
+    bind_native!("keys", 1, fn_keys);
+    bind_native!("length", 1, fn_length);
+    bind_native!("lookup", 2, fn_lookup);
+    bind_native!("lowercase", 1, fn_lowercase);
+    bind_native!("map", 2, fn_map);
+    bind_native!("match", 2, fn_match);
+    bind_native!("max", 1, fn_max);
+    bind_native!("merge", 1, fn_merge);
+    bind_native!("min", 1, fn_min);
+    bind_native!("not", 1, fn_not);
+    bind_native!("now", 3, fn_now);
+    bind_native!("number", 1, fn_number);
+    bind_native!("pad", 2, fn_pad);
+    bind_native!("parseInteger", 2, fn_parse_integer);
+    bind_native!("power", 2, fn_power);
+    bind_native!("random", 0, fn_random);
+    bind_native!("reduce", 3, fn_reduce);
+    bind_native!("replace", 4, fn_replace);
+    bind_native!("reverse", 1, fn_reverse);
+    bind_native!("shuffle", 1, fn_shuffle);
+    bind_native!("sift", 2, fn_sift);
+    bind_native!("round", 2, fn_round);
+    bind_native!("sort", 2, fn_sort);
+    bind_native!("split", 3, fn_split);
+    bind_native!("spread", 1, fn_spread);
+    bind_native!("sqrt", 1, fn_sqrt);
+    bind_native!("string", 1, fn_string);
+    bind_native!("substring", 3, fn_substring);
+    bind_native!("substringBefore", 2, fn_substring_before);
+    bind_native!("substringAfter", 2, fn_substring_after);
+    bind_native!("sum", 1, fn_sum);
+    bind_native!("trim", 1, fn_trim);
+    bind_native!("type", 1, fn_type_of);
+    bind_native!("uppercase", 1, fn_uppercase);
+    bind_native!("zip", 1, fn_zip);
+    bind_native!("millis", 0, fn_millis);
+    bind_native!("uuid", 0, fn_uuid);
+
+    frame
+}
@@ -0,0 +1,451 @@
+//! Integer formatting and parsing using the picture strings of XPath F&O 3.1
+//! [section 4.6.1](https://www.w3.org/TR/xpath-functions-31/#formatting-integers), as used by
+//! `$formatInteger` and `$parseInteger`.
+//!
+//! The same pictures appear as the presentation modifiers of the numeric components of a
+//! date/time picture, e.g. the `w` in `[Dw]`, so this is shared with the date/time code.
+
+use crate::language::{from_words, title_case, words_regex, Language};
+use crate::Error;
+
+const ROMAN_NUMERALS: [(u64, &str); 13] = [
+    (1000, "M"),
+    (900, "CM"),
+    (500, "D"),
+    (400, "CD"),
+    (100, "C"),
+    (90, "XC"),
+    (50, "L"),
+    (40, "XL"),
+    (10, "X"),
+    (9, "IX"),
+    (5, "V"),
+    (4, "IV"),
+    (1, "I"),
+];
+
+/// The code points of the zero digit of each Unicode decimal digit family.
+const DECIMAL_GROUPS: [u32; 37] = [
+    0x30, 0x0660, 0x06F0, 0x07C0, 0x0966, 0x09E6, 0x0A66, 0x0AE6, 0x0B66, 0x0BE6, 0x0C66, 0x0CE6,
+    0x0D66, 0x0DE6, 0x0E50, 0x0ED0, 0x0F20, 0x1040, 0x1090, 0x17E0, 0x1810, 0x1946, 0x19D0, 0x1A80,
+    0x1A90, 0x1B50, 0x1BB0, 0x1C40, 0x1C50, 0xA620, 0xA8D0, 0xA900, 0xA9D0, 0xA9F0, 0xAA50, 0xABF0,
+    0xFF10,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Upper,
+    Lower,
+    Title,
+}
+
+/// Where the grouping separators go in a decimal digit pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Grouping {
+    /// The same separator, every `n` digits
+    Regular(usize, char),
+
+    /// Separators at the given number of digits from the right, in increasing order
+    Irregular(Vec<(usize, char)>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Primary {
+    /// A decimal digit pattern, e.g. `#,##0`
+    Decimal {
+        zero: char,
+        mandatory_digits: usize,
+        optional_digits: usize,
+        grouping: Grouping,
+    },
+
+    /// `A` or `a`
+    Letters,
+
+    /// `I` or `i`
+    Roman,
+
+    /// `W`, `w` or `Ww`
+    Words,
+
+    /// Any other numbering sequence, none of which are supported
+    Sequence(String),
+}
+
+/// An analysed integer picture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegerFormat {
+    pub primary: Primary,
+    pub case: Case,
+    pub ordinal: bool,
+}
+
+impl IntegerFormat {
+    /// Analyse a picture such as `#,##0;o`. The `char_index` is used to report errors.
+    pub fn parse(picture: &str, char_index: usize) -> Result<IntegerFormat, Error> {
+        let (primary, modifier) = match picture.rfind(';') {
+            Some(semicolon) => (&picture[..semicolon], Some(&picture[semicolon + 1..])),
+            None => (picture, None),
+        };
+
+        let ordinal = modifier.is_some_and(|m| m.starts_with('o'));
+
+        let (primary, case) = match primary {
+            "A" => (Primary::Letters, Case::Upper),
+            "a" => (Primary::Letters, Case::Lower),
+            "I" => (Primary::Roman, Case::Upper),
+            "i" => (Primary::Roman, Case::Lower),
+            "W" => (Primary::Words, Case::Upper),
+            "Ww" => (Primary::Words, Case::Title),
+            "w" => (Primary::Words, Case::Lower),
+            _ => (analyse_decimal_pattern(primary, char_index)?, Case::Lower),
+        };
+
+        Ok(IntegerFormat {
+            primary,
+            case,
+            ordinal,
+        })
+    }
+
+    /// Format an integer, after rounding it down, using the words of a language.
+    pub fn format(
+        &self,
+        value: f64,
+        language: &dyn Language,
+        char_index: usize,
+    ) -> Result<String, Error> {
+        let value = value.floor();
+        let negative = value < 0.0;
+        let value = value.abs();
+
+        let formatted = match self.primary {
+            Primary::Letters => to_letters(
+                value as u64,
+                if self.case == Case::Upper { 'A' } else { 'a' },
+            ),
+            Primary::Roman => {
+                let roman = to_roman(value as u64);
+                if self.case == Case::Upper {
+                    roman
+                } else {
+                    roman.to_lowercase()
+                }
+            }
+            Primary::Words => {
+                let words = language.to_words(value, self.ordinal);
+                match self.case {
+                    Case::Upper => words.to_uppercase(),
+                    Case::Lower => words,
+                    Case::Title => title_case(&words, language),
+                }
+            }
+            Primary::Decimal {
+                zero,
+                mandatory_digits,
+                ref grouping,
+                ..
+            } => {
+                let digits = format!("{}", value);
+                let offset = zero as u32 - '0' as u32;
+                let mut formatted: Vec<char> =
+                    std::iter::repeat_n('0', mandatory_digits.saturating_sub(digits.len()))
+                        .chain(digits.chars())
+                        .map(|c| char::from_u32(c as u32 + offset).unwrap_or(c))
+                        .collect();
+
+                match grouping {
+                    Grouping::Regular(every, separator) => {
+                        let groups = (formatted.len() - 1) / every;
+                        for group in (1..=groups).rev() {
+                            let position = formatted.len() - group * every;
+                            formatted.insert(position, *separator);
+                        }
+                    }
+                    Grouping::Irregular(separators) => {
+                        for (position, separator) in separators.iter().rev() {
+                            let position = formatted.len().saturating_sub(*position);
+                            formatted.insert(position, *separator);
+                        }
+                    }
+                }
+
+                let mut formatted: String = formatted.into_iter().collect();
+
+                if self.ordinal {
+                    formatted.push_str(language.ordinal_suffix(value as u64));
+                }
+
+                formatted
+            }
+            Primary::Sequence(ref token) => {
+                return Err(Error::D3130SequenceNotSupported(char_index, token.clone()))
+            }
+        };
+
+        Ok(if negative {
+            format!("-{}", formatted)
+        } else {
+            formatted
+        })
+    }
+
+    /// Parse an integer that was formatted with this picture, returning `None` if it isn't
+    /// recognised.
+    pub fn parse_integer(
+        &self,
+        value: &str,
+        language: &dyn Language,
+        char_index: usize,
+    ) -> Result<Option<f64>, Error> {
+        let result = match self.primary {
+            Primary::Letters => {
+                from_letters(value, if self.case == Case::Upper { 'A' } else { 'a' })
+            }
+            Primary::Roman => from_roman(&value.to_uppercase()),
+            Primary::Words => from_words(value, language),
+            Primary::Decimal {
+                zero, ref grouping, ..
+            } => {
+                let digits = if self.ordinal {
+                    value.trim_end_matches(|c: char| !c.is_numeric())
+                } else {
+                    value
+                };
+
+                let offset = zero as u32 - '0' as u32;
+                digits
+                    .chars()
+                    .filter(|c| match grouping {
+                        Grouping::Regular(_, separator) => c != separator,
+                        Grouping::Irregular(separators) => separators.iter().all(|(_, s)| c != s),
+                    })
+                    .map(|c| char::from_u32((c as u32).wrapping_sub(offset)).unwrap_or(c))
+                    .collect::<String>()
+                    .parse::<f64>()
+                    .ok()
+            }
+            Primary::Sequence(ref token) => {
+                return Err(Error::D3130SequenceNotSupported(char_index, token.clone()))
+            }
+        };
+
+        Ok(result)
+    }
+
+    /// A regular expression that matches an integer formatted with this picture, for finding
+    /// the components of a date/time. A decimal digit pattern with more than one digit matches
+    /// at most that many digits, so that components can be parsed without separators.
+    pub fn regex(&self, language: &dyn Language, char_index: usize) -> Result<String, Error> {
+        let regex = match self.primary {
+            Primary::Letters if self.case == Case::Upper => "[A-Z]+".to_string(),
+            Primary::Letters => "[a-z]+".to_string(),
+            Primary::Roman if self.case == Case::Upper => "[MDCLXVI]+".to_string(),
+            Primary::Roman => "[mdclxvi]+".to_string(),
+            Primary::Words => words_regex(language),
+            Primary::Decimal {
+                zero,
+                mandatory_digits,
+                optional_digits,
+                ..
+            } => {
+                let nine = char::from_u32(zero as u32 + 9).unwrap_or(zero);
+                let width = mandatory_digits + optional_digits;
+                let mut regex = if width >= 2 {
+                    format!("[{}-{}]{{{},{}}}", zero, nine, mandatory_digits, width)
+                } else {
+                    format!("[{}-{}]+", zero, nine)
+                };
+                if self.ordinal {
+                    let mut suffixes: Vec<&str> =
+                        (0..100).map(|n| language.ordinal_suffix(n)).collect();
+                    suffixes.dedup();
+                    let suffixes: Vec<String> =
+                        suffixes.iter().map(|suffix| escape_regex(suffix)).collect();
+                    regex.push_str(&format!("(?:{})", suffixes.join("|")));
+                }
+                regex
+            }
+            Primary::Sequence(ref token) => {
+                return Err(Error::D3130SequenceNotSupported(char_index, token.clone()))
+            }
+        };
+
+        Ok(regex)
+    }
+}
+
+/// Analyse a decimal digit pattern, which is anything made of digits from a single family,
+/// `#` optional digits and grouping separators. Anything without a mandatory digit is a
+/// numbering sequence.
+fn analyse_decimal_pattern(pattern: &str, char_index: usize) -> Result<Primary, Error> {
+    let mut zero: Option<u32> = None;
+    let mut mandatory_digits = 0;
+    let mut optional_digits = 0;
+    let mut position = 0;
+    let mut separators = Vec::new();
+
+    // Separator positions are counted in digits from the right
+    for c in pattern.chars().rev() {
+        let code = c as u32;
+        if let Some(group) = DECIMAL_GROUPS
+            .iter()
+            .find(|group| (**group..=**group + 9).contains(&code))
+        {
+            match zero {
+                None => zero = Some(*group),
+                Some(zero) if zero != *group => {
+                    return Err(Error::D3131MixedDecimalGroups(char_index))
+                }
+                _ => {}
+            }
+            mandatory_digits += 1;
+            position += 1;
+        } else if c == '#' {
+            optional_digits += 1;
+            position += 1;
+        } else {
+            separators.push((position, c));
+        }
+    }
+
+    let Some(zero) = zero.and_then(char::from_u32) else {
+        return Ok(Primary::Sequence(pattern.to_string()));
+    };
+
+    let grouping = match regular_grouping(&separators) {
+        Some(every) => Grouping::Regular(every, separators[0].1),
+        None => Grouping::Irregular(separators),
+    };
+
+    Ok(Primary::Decimal {
+        zero,
+        mandatory_digits,
+        optional_digits,
+        grouping,
+    })
+}
+
+/// The interval between the grouping separators, if they are all the same character and
+/// evenly spaced.
+fn regular_grouping(separators: &[(usize, char)]) -> Option<usize> {
+    fn gcd(a: usize, b: usize) -> usize {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+
+    let (_, first) = separators.first()?;
+    if separators.iter().any(|(_, c)| c != first) {
+        return None;
+    }
+
+    let factor = separators.iter().map(|(p, _)| *p).reduce(gcd)?;
+    if factor == 0 {
+        return None;
+    }
+
+    (1..=separators.len())
+        .all(|n| separators.iter().any(|(p, _)| *p == n * factor))
+        .then_some(factor)
+}
+
+/// Escape the characters of a literal that are special in a regular expression.
+pub fn escape_regex(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if ".*+?^${}()|[]\\/".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Write a number in upper case roman numerals. Zero is the empty string.
+pub fn to_roman(mut value: u64) -> String {
+    let mut roman = String::new();
+    for (numeral, symbol) in ROMAN_NUMERALS {
+        while value >= numeral {
+            roman.push_str(symbol);
+            value -= numeral;
+        }
+    }
+    roman
+}
+
+/// Parse upper case roman numerals.
+pub fn from_roman(roman: &str) -> Option<f64> {
+    let mut decimal = 0.0;
+    let mut max = 1.0;
+
+    for c in roman.chars().rev() {
+        let value = match c {
+            'M' => 1000.0,
+            'D' => 500.0,
+            'C' => 100.0,
+            'L' => 50.0,
+            'X' => 10.0,
+            'V' => 5.0,
+            'I' => 1.0,
+            _ => return None,
+        };
+        if value < max {
+            decimal -= value;
+        } else {
+            max = value;
+            decimal += value;
+        }
+    }
+
+    Some(decimal)
+}
+
+/// Write a number as a sequence of letters, e.g. `a`, `b`, ..., `z`, `aa`, `ab`, ...
+fn to_letters(mut value: u64, a: char) -> String {
+    let mut letters = Vec::new();
+    while value > 0 {
+        letters.push(char::from_u32(((value - 1) % 26) as u32 + a as u32).unwrap_or(a));
+        value = (value - 1) / 26;
+    }
+    letters.iter().rev().collect()
+}
+
+fn from_letters(letters: &str, a: char) -> Option<f64> {
+    letters
+        .chars()
+        .rev()
+        .enumerate()
+        .try_fold(0.0, |decimal, (i, c)| {
+            let digit = (c as u32).checked_sub(a as u32)?;
+            (digit < 26).then(|| decimal + (digit + 1) as f64 * 26f64.powi(i as i32))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::English;
+
+    #[test]
+    fn irregular_grouping() {
+        let format = IntegerFormat::parse("#:###,##0", 0).unwrap();
+        assert_eq!(
+            format.format(1234567890.0, &English, 0).unwrap(),
+            "1234:567,890"
+        );
+        assert_eq!(
+            format.parse_integer("1234:567,890", &English, 0).unwrap(),
+            Some(1234567890.0)
+        );
+    }
+
+    #[test]
+    fn letters() {
+        assert_eq!(to_letters(702, 'a'), "zz");
+        assert_eq!(from_letters("zz", 'a'), Some(702.0));
+        assert_eq!(from_letters("zZ", 'a'), None);
+    }
+}
@@ -0,0 +1,498 @@
+//! A strict JSON parser for evaluation input.
+//!
+//! The input is read in a single pass, and values are written straight into the arena, without
+//! going through tokens or an AST. Nesting is handled with an explicit stack rather than
+//! recursion, so deeply nested input can't overflow the stack. The elements of arrays and the
+//! entries of objects are collected in scratch buffers which are shared by all the containers,
+//! so that each one is allocated in the arena once, with the right capacity.
+
+use std::ops::Range;
+
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+
+use crate::errors::InputPosition;
+use crate::{ArrayFlags, Error, ObjectMap, Result, Value};
+
+/// Parse JSON text into a value in the arena.
+pub fn parse<'a>(arena: &'a Bump, input: &str) -> Result<&'a mut Value<'a>> {
+    Parser::new(arena, input).parse()
+}
+
+/// A container which is being parsed, holding where its contents start in the scratch buffers.
+enum Container {
+    Array {
+        start: usize,
+    },
+    Object {
+        start: usize,
+        keys_start: usize,
+        key: Range<usize>,
+    },
+}
+
+struct Parser<'a, 'i> {
+    arena: &'a Bump,
+    input: &'i str,
+    bytes: &'i [u8],
+    position: usize,
+    elements: Vec<&'a Value<'a>>,
+    entries: Vec<(Range<usize>, &'a Value<'a>)>,
+    keys: String,
+    scratch: String,
+}
+
+impl<'a, 'i> Parser<'a, 'i> {
+    fn new(arena: &'a Bump, input: &'i str) -> Self {
+        Self {
+            arena,
+            input,
+            bytes: input.as_bytes(),
+            position: 0,
+            elements: Vec::new(),
+            entries: Vec::new(),
+            keys: String::new(),
+            scratch: String::new(),
+        }
+    }
+
+    fn parse(mut self) -> Result<&'a mut Value<'a>> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(value),
+            Some(_) => Err(self.unexpected("the end of the input")),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<&'a mut Value<'a>> {
+        let mut stack: Vec<Container> = Vec::new();
+
+        'value: loop {
+            self.skip_whitespace();
+            let mut value = match self.peek() {
+                Some(b'{') => {
+                    self.position += 1;
+                    self.skip_whitespace();
+                    if self.peek() == Some(b'}') {
+                        self.position += 1;
+                        Value::object(self.arena)
+                    } else {
+                        let keys_start = self.keys.len();
+                        let key = self.parse_key()?;
+                        stack.push(Container::Object {
+                            start: self.entries.len(),
+                            keys_start,
+                            key,
+                        });
+                        continue 'value;
+                    }
+                }
+                Some(b'[') => {
+                    self.position += 1;
+                    self.skip_whitespace();
+                    if self.peek() == Some(b']') {
+                        self.position += 1;
+                        Value::array(self.arena, ArrayFlags::empty())
+                    } else {
+                        stack.push(Container::Array {
+                            start: self.elements.len(),
+                        });
+                        continue 'value;
+                    }
+                }
+                Some(b'"') => self.parse_string()?,
+                Some(b'-' | b'0'..=b'9') => self.parse_number()?,
+                Some(b't') => {
+                    self.parse_literal("true")?;
+                    self.arena.alloc(Value::Bool(true))
+                }
+                Some(b'f') => {
+                    self.parse_literal("false")?;
+                    self.arena.alloc(Value::Bool(false))
+                }
+                Some(b'n') => {
+                    self.parse_literal("null")?;
+                    Value::null(self.arena)
+                }
+                _ => return Err(self.unexpected("a value")),
+            };
+
+            // Add the value to the containers it completes, closing any that end with it
+            loop {
+                match stack.last_mut() {
+                    None => return Ok(value),
+                    Some(Container::Array { start }) => {
+                        self.elements.push(value);
+                        self.skip_whitespace();
+                        match self.peek() {
+                            Some(b',') => {
+                                self.position += 1;
+                                continue 'value;
+                            }
+                            Some(b']') => {
+                                self.position += 1;
+                                let elements = self.elements.drain(*start..);
+                                let mut array =
+                                    BumpVec::with_capacity_in(elements.len(), self.arena);
+                                array.extend(elements);
+                                value = self.arena.alloc(Value::Array(array, ArrayFlags::empty()));
+                            }
+                            _ => return Err(self.unexpected("',' or ']'")),
+                        }
+                    }
+                    Some(Container::Object {
+                        start,
+                        keys_start,
+                        key,
+                    }) => {
+                        self.entries.push((key.clone(), value));
+                        self.skip_whitespace();
+                        match self.peek() {
+                            Some(b',') => {
+                                self.position += 1;
+                                self.skip_whitespace();
+                                *key = self.parse_key()?;
+                                continue 'value;
+                            }
+                            Some(b'}') => {
+                                self.position += 1;
+                                let entries = self.entries.drain(*start..);
+                                let mut object =
+                                    ObjectMap::with_capacity_in(entries.len(), self.arena);
+                                for (key, value) in entries {
+                                    object.insert(&self.keys[key], value);
+                                }
+                                self.keys.truncate(*keys_start);
+                                value = self.arena.alloc(Value::Object(object));
+                            }
+                            _ => return Err(self.unexpected("',' or '}'")),
+                        }
+                    }
+                }
+                stack.pop();
+            }
+        }
+    }
+
+    /// Parse an object key and the colon after it, returning where the key is in `keys`.
+    fn parse_key(&mut self) -> Result<Range<usize>> {
+        if self.peek() != Some(b'"') {
+            return Err(self.unexpected("a string"));
+        }
+        let start = self.keys.len();
+        let mut keys = std::mem::take(&mut self.keys);
+        let result = self.read_string(&mut keys);
+        self.keys = keys;
+        result?;
+        let key = start..self.keys.len();
+
+        self.skip_whitespace();
+        if self.peek() != Some(b':') {
+            return Err(self.unexpected("':'"));
+        }
+        self.position += 1;
+        Ok(key)
+    }
+
+    fn parse_string(&mut self) -> Result<&'a mut Value<'a>> {
+        // Strings without escapes are copied straight from the input
+        let start = self.position + 1;
+        let end = self.scan_string(start);
+        if self.bytes.get(end) == Some(&b'"') {
+            self.position = end + 1;
+            return Ok(Value::string(self.arena, &self.input[start..end]));
+        }
+
+        let mut scratch = std::mem::take(&mut self.scratch);
+        scratch.clear();
+        let result = self.read_string(&mut scratch);
+        let value = result.map(|_| Value::string(self.arena, &scratch));
+        self.scratch = scratch;
+        value
+    }
+
+    /// Find the end of the run of characters from `start` which don't need any special handling
+    /// in a string.
+    fn scan_string(&self, start: usize) -> usize {
+        self.bytes[start..]
+            .iter()
+            .position(|&b| b == b'"' || b == b'\\' || b < 0x20)
+            .map_or(self.bytes.len(), |i| start + i)
+    }
+
+    /// Read a string starting at its opening quote, decoding escapes into `output`.
+    fn read_string(&mut self, output: &mut String) -> Result<()> {
+        self.position += 1;
+        loop {
+            let end = self.scan_string(self.position);
+            output.push_str(&self.input[self.position..end]);
+            self.position = end;
+
+            match self.peek() {
+                Some(b'"') => {
+                    self.position += 1;
+                    return Ok(());
+                }
+                Some(b'\\') => {
+                    self.position += 1;
+                    self.read_escape(output)?;
+                }
+                Some(_) => return Err(Error::I0205ControlCharacterInString(self.input_position())),
+                None => return Err(self.unexpected("'\"'")),
+            }
+        }
+    }
+
+    /// Read an escape sequence after its backslash.
+    fn read_escape(&mut self, output: &mut String) -> Result<()> {
+        let escape = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{0008}',
+            Some(b'f') => '\u{000c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                let start = self.position - 1;
+                self.position += 1;
+                let c = self.read_unicode_escape(start)?;
+                output.push(c);
+                return Ok(());
+            }
+            Some(_) => return Err(Error::I0203InvalidEscape(self.input_position())),
+            None => return Err(self.unexpected("an escape sequence")),
+        };
+        self.position += 1;
+        output.push(escape);
+        Ok(())
+    }
+
+    /// Read the hex digits of a `\u` escape, and of a second one for a surrogate pair.
+    fn read_unicode_escape(&mut self, start: usize) -> Result<char> {
+        let input = self.input;
+        let invalid = || Error::I0204InvalidUnicodeEscape(InputPosition::new(input, start));
+
+        let first = self.read_hex().ok_or_else(invalid)?;
+        let code = match first {
+            0xd800..=0xdbff => {
+                if !self.bytes[self.position..].starts_with(b"\\u") {
+                    return Err(invalid());
+                }
+                self.position += 2;
+                let second = self.read_hex().ok_or_else(invalid)?;
+                if !(0xdc00..=0xdfff).contains(&second) {
+                    return Err(invalid());
+                }
+                0x10000 + ((first - 0xd800) << 10) + (second - 0xdc00)
+            }
+            0xdc00..=0xdfff => return Err(invalid()),
+            _ => first,
+        };
+        char::from_u32(code).ok_or_else(invalid)
+    }
+
+    fn read_hex(&mut self) -> Option<u32> {
+        let digits = self.input.get(self.position..self.position + 4)?;
+        if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        self.position += 4;
+        u32::from_str_radix(digits, 16).ok()
+    }
+
+    fn parse_number(&mut self) -> Result<&'a mut Value<'a>> {
+        let start = self.position;
+
+        if self.peek() == Some(b'-') {
+            self.position += 1;
+        }
+        match self.peek() {
+            Some(b'0') => {
+                self.position += 1;
+                if matches!(self.peek(), Some(b'0'..=b'9')) {
+                    return Err(Error::I0206InvalidNumber(self.input_position()));
+                }
+            }
+            Some(b'1'..=b'9') => self.skip_digits(),
+            _ => return Err(Error::I0206InvalidNumber(self.input_position())),
+        }
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            self.expect_digits()?;
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.position += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.position += 1;
+            }
+            self.expect_digits()?;
+        }
+
+        let number = &self.input[start..self.position];
+        match number.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(Value::number(self.arena, n)),
+            _ => Err(Error::I0207NumberOutOfRange(
+                InputPosition::new(self.input, start),
+                number.to_string(),
+            )),
+        }
+    }
+
+    fn expect_digits(&mut self) -> Result<()> {
+        if !matches!(self.peek(), Some(b'0'..=b'9')) {
+            return Err(Error::I0206InvalidNumber(self.input_position()));
+        }
+        self.skip_digits();
+        Ok(())
+    }
+
+    fn skip_digits(&mut self) {
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.position += 1;
+        }
+    }
+
+    fn parse_literal(&mut self, literal: &str) -> Result<()> {
+        for expected in literal.bytes() {
+            if self.peek() != Some(expected) {
+                return Err(self.unexpected(&format!("'{}'", literal)));
+            }
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\n' | b'\r' | b'\t')) {
+            self.position += 1;
+        }
+    }
+
+    #[inline(always)]
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn input_position(&self) -> InputPosition {
+        InputPosition::new(self.input, self.position)
+    }
+
+    /// An error for finding something other than what was expected at the current position.
+    fn unexpected(&self, expected: &str) -> Error {
+        match self.input[self.position..].chars().next() {
+            Some(c) => {
+                Error::I0201UnexpectedCharacter(self.input_position(), expected.to_string(), c)
+            }
+            None => Error::I0202UnexpectedEndOfInput(self.input_position(), expected.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("null")]
+    #[test_case("true")]
+    #[test_case("false")]
+    #[test_case("0")]
+    #[test_case("-1.5e3")]
+    #[test_case("0.25")]
+    #[test_case(r#""hello""#)]
+    #[test_case(r#""a\"b\\c\/d\b\f\n\r\t""#)]
+    #[test_case(r#""\u00e9\ud83d\ude00""#)]
+    #[test_case("[]")]
+    #[test_case("{}")]
+    #[test_case(r#"[1, "two", [true, null], {"a": {}}]"#)]
+    #[test_case(r#"{"z": 1, "a": [2, {"m": "n"}], "é": "ü"}"#)]
+    #[test_case(" \t\r\n[ 1 ,\n 2 ] \n")]
+    fn matches_serde_json(input: &str) {
+        let arena = Bump::new();
+        let expected = Value::from_json(&arena, &serde_json::from_str(input).unwrap());
+
+        let value = parse(&arena, input).unwrap();
+
+        assert_eq!(*value, *expected);
+    }
+
+    #[test]
+    fn keeps_key_order() {
+        let arena = Bump::new();
+
+        let value = parse(&arena, r#"{"b": 1, "a": {"d": 2, "c": 3}, "b": 4}"#).unwrap();
+
+        assert_eq!(value.serialize(false), r#"{"b":4,"a":{"d":2,"c":3}}"#);
+    }
+
+    #[test]
+    fn deeply_nested() {
+        let arena = Bump::new();
+        let input = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+
+        let mut value = &*parse(&arena, &input).unwrap();
+        let mut depth = 1;
+        while !value.is_empty() {
+            value = value.get_member(0);
+            depth += 1;
+        }
+
+        assert_eq!(depth, 100_000);
+    }
+
+    #[test_case("", "I0202", 1, 1; "empty")]
+    #[test_case("{\"a\": 1+1}", "I0201", 1, 8; "expression")]
+    #[test_case("[1, 2,]", "I0201", 1, 7; "trailing comma")]
+    #[test_case("{'a': 1}", "I0201", 1, 2; "single quotes")]
+    #[test_case("{\n  \"a\": 1\n  \"b\": 2\n}", "I0201", 3, 3; "missing comma")]
+    #[test_case("[1] // comment", "I0201", 1, 5; "comment")]
+    #[test_case("[1, 2", "I0202", 1, 6; "unterminated array")]
+    #[test_case("{\"a\" 1}", "I0201", 1, 6; "missing colon")]
+    #[test_case("{\"a\": }", "I0201", 1, 7; "missing value")]
+    #[test_case("\"abc", "I0202", 1, 5; "unterminated string")]
+    #[test_case("\"a\\xb\"", "I0203", 1, 4; "invalid escape")]
+    #[test_case("\"\\u12g4\"", "I0204", 1, 2; "invalid unicode escape")]
+    #[test_case("\"\\ud800\"", "I0204", 1, 2; "lone surrogate")]
+    #[test_case("\"a\tb\"", "I0205", 1, 3; "control character")]
+    #[test_case("01", "I0206", 1, 2; "leading zero")]
+    #[test_case("1.", "I0206", 1, 3; "missing fraction")]
+    #[test_case("-", "I0206", 1, 2; "missing digits")]
+    #[test_case("1e", "I0206", 1, 3; "missing exponent")]
+    #[test_case("1e400", "I0207", 1, 1; "out of range")]
+    #[test_case("tru", "I0202", 1, 4; "truncated literal")]
+    #[test_case("NaN", "I0201", 1, 1; "not a number")]
+    #[test_case("[\"é\", x]", "I0201", 1, 7; "column in characters")]
+    fn errors(input: &str, code: &str, line: usize, column: usize) {
+        let arena = Bump::new();
+
+        let error = parse(&arena, input).unwrap_err();
+
+        assert_eq!(error.code(), code);
+        let position = match error {
+            Error::I0201UnexpectedCharacter(p, ..)
+            | Error::I0202UnexpectedEndOfInput(p, ..)
+            | Error::I0203InvalidEscape(p)
+            | Error::I0204InvalidUnicodeEscape(p)
+            | Error::I0205ControlCharacterInString(p)
+            | Error::I0206InvalidNumber(p)
+            | Error::I0207NumberOutOfRange(p, ..) => p,
+            _ => unreachable!(),
+        };
+        assert_eq!((position.line, position.column), (line, column));
+    }
+
+    #[test_case("[1] x", "I0201 @ line 1, column 5 (byte 4): Unexpected 'x' in the input, expected the end of the input"; "trailing input")]
+    #[test_case("[1 2]", "I0201 @ line 1, column 4 (byte 3): Unexpected '2' in the input, expected ',' or ']'"; "missing comma")]
+    #[test_case("[1, 2", "I0202 @ line 1, column 6 (byte 5): Expected ',' or ']' before the end of the input"; "unterminated array")]
+    fn error_messages(input: &str, message: &str) {
+        let arena = Bump::new();
+
+        let error = parse(&arena, input).unwrap_err();
+
+        assert_eq!(error.to_string(), message);
+    }
+}
@@ -0,0 +1,1366 @@
+//! The words used when formatting and parsing dates and integers, which depend on the language
+//! given to the date/time functions, e.g. the `es` in `$fromMillis($millis, '[MNn]', (), 'es')`.
+//!
+//! English, Spanish, German and French are built in. Embedders can add others by implementing
+//! [`Language`] and registering it with [`crate::JsonAta::register_language`] or
+//! [`crate::Expression::register_language`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::integer_format::escape_regex;
+
+/// How a word contributes to the value of a number written in words.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberWord {
+    /// Added to the value, e.g. "twenty" or the German "zwanzig"
+    Add(f64),
+
+    /// Multiplies what precedes it, e.g. "hundred" or the French "mille"
+    Multiply(f64),
+
+    /// Joins other words and doesn't affect the value, e.g. "and" or the Spanish "y"
+    Connector,
+}
+
+/// Provides the names of months and days, and the words for numbers, in a language.
+pub trait Language: Send + Sync {
+    /// The name of a month in title case, where January is 1.
+    fn month_name(&self, month: usize) -> &str;
+
+    /// The name of a day of the week in title case, where Monday is 1.
+    fn day_name(&self, day: usize) -> &str;
+
+    /// The name of the morning or afternoon half of the day.
+    fn period_name(&self, pm: bool) -> &str {
+        if pm {
+            "pm"
+        } else {
+            "am"
+        }
+    }
+
+    /// Spell out a non-negative integer in lower case, e.g. "twenty-first".
+    fn to_words(&self, value: f64, ordinal: bool) -> String;
+
+    /// The lower case words that numbers written by [`Language::to_words`] are made of, used
+    /// to parse them. Spaces, hyphens and commas between words are ignored, so compounds such
+    /// as the German "einundzwanzig" only need their parts listed.
+    fn number_words(&self) -> &[(&str, NumberWord)];
+
+    /// The suffix of an ordinal written in digits, e.g. "st" for 1.
+    fn ordinal_suffix(&self, value: u64) -> &str;
+}
+
+/// The languages available to the date/time functions, keyed by language tag.
+#[derive(Clone)]
+pub struct Languages(HashMap<String, Arc<dyn Language>>);
+
+impl fmt::Debug for Languages {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+impl Default for Languages {
+    fn default() -> Self {
+        let mut languages = Languages(HashMap::new());
+        languages.register("en", English);
+        languages.register("es", Spanish);
+        languages.register("de", German);
+        languages.register("fr", French);
+        languages
+    }
+}
+
+impl Languages {
+    pub fn register(&mut self, tag: &str, language: impl Language + 'static) {
+        self.0.insert(tag.to_lowercase(), Arc::new(language));
+    }
+
+    /// Find a language by tag, falling back to its primary subtag, so `es-MX` is Spanish, and
+    /// then to English. An unknown tag isn't an error, as the language argument is optional in
+    /// reference JSONata, which only has English.
+    pub fn get(&self, tag: &str) -> &dyn Language {
+        let tag = tag.to_lowercase();
+        let primary = tag.split(['-', '_']).next().unwrap_or_default();
+        match self.0.get(&tag).or_else(|| self.0.get(primary)) {
+            Some(language) => language.as_ref(),
+            None => &English,
+        }
+    }
+}
+
+/// Capitalise each word of a number written by [`Language::to_words`], apart from connectors
+/// such as "and".
+pub fn title_case(words: &str, language: &dyn Language) -> String {
+    let is_connector = |word: &str| {
+        language
+            .number_words()
+            .iter()
+            .any(|(w, kind)| *kind == NumberWord::Connector && *w == word)
+    };
+
+    let mut titled = String::with_capacity(words.len());
+    let mut word = String::new();
+    for c in words.chars().chain(std::iter::once(' ')) {
+        if c.is_alphabetic() {
+            word.push(c);
+            continue;
+        }
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) if !is_connector(&word) => {
+                titled.extend(first.to_uppercase());
+                titled.push_str(chars.as_str());
+            }
+            _ => titled.push_str(&word),
+        }
+        word.clear();
+        titled.push(c);
+    }
+    titled.pop();
+
+    titled
+}
+
+/// Parse a number written in words in a language, e.g. "two thousand and twenty-first".
+pub fn from_words(text: &str, language: &dyn Language) -> Option<f64> {
+    // Split the text into number words, backtracking where a longer word leads nowhere, e.g.
+    // the "cents" at the start of the French "centsix"
+    fn split(text: &str, lexicon: &[(String, NumberWord)], words: &mut Vec<NumberWord>) -> bool {
+        if text.is_empty() {
+            return true;
+        }
+        for (word, kind) in lexicon {
+            if let Some(rest) = text.strip_prefix(word.as_str()) {
+                words.push(*kind);
+                if split(rest, lexicon, words) {
+                    return true;
+                }
+                words.pop();
+            }
+        }
+        false
+    }
+
+    let is_separator = |c: char| c.is_whitespace() || c == '-' || c == ',';
+    let text: String = text
+        .to_lowercase()
+        .chars()
+        .filter(|c| !is_separator(*c))
+        .collect();
+
+    let mut lexicon: Vec<(String, NumberWord)> = language
+        .number_words()
+        .iter()
+        .map(|(word, kind)| (word.chars().filter(|c| !is_separator(*c)).collect(), *kind))
+        .collect();
+    lexicon.sort_by_key(|(word, _)| std::cmp::Reverse(word.chars().count()));
+
+    let mut words = Vec::new();
+    if text.is_empty() || !split(&text, &lexicon, &mut words) {
+        return None;
+    }
+
+    // Each segment is a multiple of a power of a thousand, which are summed at the end. The
+    // last multiplier of a segment tells a smaller one that starts a new segment, as in the
+    // Spanish "un millón mil", from one that applies to it, as in "mil millones"
+    let mut segments = vec![(0.0, 0.0)];
+    for word in words {
+        let (top, scale) = segments.pop().unwrap_or_default();
+        match word {
+            NumberWord::Add(value) if top >= 1000.0 => {
+                segments.push((top, scale));
+                segments.push((value, 0.0));
+            }
+            NumberWord::Add(value) => segments.push((top + value, scale)),
+            NumberWord::Multiply(value) if value < scale => {
+                segments.push((top, scale));
+                segments.push((value, value));
+            }
+            NumberWord::Multiply(value) => segments.push((top.max(1.0) * value, value)),
+            NumberWord::Connector => segments.push((top, scale)),
+        }
+    }
+
+    Some(segments.iter().map(|(value, _)| value).sum())
+}
+
+/// A regular expression that matches numbers written in words in a language.
+pub fn words_regex(language: &dyn Language) -> String {
+    let mut words: Vec<String> = language
+        .number_words()
+        .iter()
+        .map(|(word, _)| escape_regex(word))
+        .collect();
+    words.sort_by_key(|w| std::cmp::Reverse(w.chars().count()));
+    format!("(?:{}|[\\-, ])+", words.join("|"))
+}
+
+pub struct English;
+
+const EN_MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const EN_DAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+const EN_FEW: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const EN_ORDINALS: [&str; 20] = [
+    "zeroth",
+    "first",
+    "second",
+    "third",
+    "fourth",
+    "fifth",
+    "sixth",
+    "seventh",
+    "eighth",
+    "ninth",
+    "tenth",
+    "eleventh",
+    "twelfth",
+    "thirteenth",
+    "fourteenth",
+    "fifteenth",
+    "sixteenth",
+    "seventeenth",
+    "eighteenth",
+    "nineteenth",
+];
+
+const EN_DECADES: [&str; 8] = [
+    "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+const EN_MAGNITUDES: [&str; 4] = ["thousand", "million", "billion", "trillion"];
+
+const EN_NUMBER_WORDS: [(&str, NumberWord); 67] = [
+    ("zero", NumberWord::Add(0.0)),
+    ("one", NumberWord::Add(1.0)),
+    ("two", NumberWord::Add(2.0)),
+    ("three", NumberWord::Add(3.0)),
+    ("four", NumberWord::Add(4.0)),
+    ("five", NumberWord::Add(5.0)),
+    ("six", NumberWord::Add(6.0)),
+    ("seven", NumberWord::Add(7.0)),
+    ("eight", NumberWord::Add(8.0)),
+    ("nine", NumberWord::Add(9.0)),
+    ("ten", NumberWord::Add(10.0)),
+    ("eleven", NumberWord::Add(11.0)),
+    ("twelve", NumberWord::Add(12.0)),
+    ("thirteen", NumberWord::Add(13.0)),
+    ("fourteen", NumberWord::Add(14.0)),
+    ("fifteen", NumberWord::Add(15.0)),
+    ("sixteen", NumberWord::Add(16.0)),
+    ("seventeen", NumberWord::Add(17.0)),
+    ("eighteen", NumberWord::Add(18.0)),
+    ("nineteen", NumberWord::Add(19.0)),
+    ("zeroth", NumberWord::Add(0.0)),
+    ("first", NumberWord::Add(1.0)),
+    ("second", NumberWord::Add(2.0)),
+    ("third", NumberWord::Add(3.0)),
+    ("fourth", NumberWord::Add(4.0)),
+    ("fifth", NumberWord::Add(5.0)),
+    ("sixth", NumberWord::Add(6.0)),
+    ("seventh", NumberWord::Add(7.0)),
+    ("eighth", NumberWord::Add(8.0)),
+    ("ninth", NumberWord::Add(9.0)),
+    ("tenth", NumberWord::Add(10.0)),
+    ("eleventh", NumberWord::Add(11.0)),
+    ("twelfth", NumberWord::Add(12.0)),
+    ("thirteenth", NumberWord::Add(13.0)),
+    ("fourteenth", NumberWord::Add(14.0)),
+    ("fifteenth", NumberWord::Add(15.0)),
+    ("sixteenth", NumberWord::Add(16.0)),
+    ("seventeenth", NumberWord::Add(17.0)),
+    ("eighteenth", NumberWord::Add(18.0)),
+    ("nineteenth", NumberWord::Add(19.0)),
+    ("twenty", NumberWord::Add(20.0)),
+    ("thirty", NumberWord::Add(30.0)),
+    ("forty", NumberWord::Add(40.0)),
+    ("fifty", NumberWord::Add(50.0)),
+    ("sixty", NumberWord::Add(60.0)),
+    ("seventy", NumberWord::Add(70.0)),
+    ("eighty", NumberWord::Add(80.0)),
+    ("ninety", NumberWord::Add(90.0)),
+    ("twentieth", NumberWord::Add(20.0)),
+    ("thirtieth", NumberWord::Add(30.0)),
+    ("fortieth", NumberWord::Add(40.0)),
+    ("fiftieth", NumberWord::Add(50.0)),
+    ("sixtieth", NumberWord::Add(60.0)),
+    ("seventieth", NumberWord::Add(70.0)),
+    ("eightieth", NumberWord::Add(80.0)),
+    ("ninetieth", NumberWord::Add(90.0)),
+    ("hundred", NumberWord::Multiply(1e2)),
+    ("hundredth", NumberWord::Multiply(1e2)),
+    ("thousand", NumberWord::Multiply(1e3)),
+    ("thousandth", NumberWord::Multiply(1e3)),
+    ("million", NumberWord::Multiply(1e6)),
+    ("millionth", NumberWord::Multiply(1e6)),
+    ("billion", NumberWord::Multiply(1e9)),
+    ("billionth", NumberWord::Multiply(1e9)),
+    ("trillion", NumberWord::Multiply(1e12)),
+    ("trillionth", NumberWord::Multiply(1e12)),
+    ("and", NumberWord::Connector),
+];
+
+impl Language for English {
+    fn month_name(&self, month: usize) -> &str {
+        EN_MONTHS[month - 1]
+    }
+
+    fn day_name(&self, day: usize) -> &str {
+        EN_DAYS[day - 1]
+    }
+
+    /// Numbers beyond the trillions are written as multiples of a trillion, e.g. "one
+    /// thousand trillion".
+    fn to_words(&self, value: f64, ordinal: bool) -> String {
+        fn lookup(num: f64, prev: bool, ordinal: bool) -> String {
+            let mut words = String::new();
+
+            if num <= 19.0 {
+                if prev {
+                    words.push_str(" and ");
+                }
+                let index = num as usize;
+                words.push_str(if ordinal {
+                    EN_ORDINALS[index]
+                } else {
+                    EN_FEW[index]
+                });
+            } else if num < 100.0 {
+                let tens = (num / 10.0).floor();
+                let remainder = num - tens * 10.0;
+                if prev {
+                    words.push_str(" and ");
+                }
+                words.push_str(EN_DECADES[tens as usize - 2]);
+                if remainder > 0.0 {
+                    words.push('-');
+                    words.push_str(&lookup(remainder, false, ordinal));
+                } else if ordinal {
+                    words.pop();
+                    words.push_str("ieth");
+                }
+            } else if num < 1000.0 {
+                let hundreds = (num / 100.0).floor();
+                let remainder = num - hundreds * 100.0;
+                if prev {
+                    words.push_str(", ");
+                }
+                words.push_str(EN_FEW[hundreds as usize]);
+                words.push_str(" hundred");
+                if remainder > 0.0 {
+                    words.push_str(&lookup(remainder, true, ordinal));
+                } else if ordinal {
+                    words.push_str("th");
+                }
+            } else {
+                let magnitude = ((num.log10() / 3.0).floor() as usize).min(EN_MAGNITUDES.len());
+                let factor = 10f64.powi(magnitude as i32 * 3);
+                let mantissa = (num / factor).floor();
+                let remainder = num - mantissa * factor;
+                if prev {
+                    words.push_str(", ");
+                }
+                words.push_str(&lookup(mantissa, false, false));
+                words.push(' ');
+                words.push_str(EN_MAGNITUDES[magnitude - 1]);
+                if remainder > 0.0 {
+                    words.push_str(&lookup(remainder, true, ordinal));
+                } else if ordinal {
+                    words.push_str("th");
+                }
+            }
+
+            words
+        }
+
+        lookup(value, false, ordinal)
+    }
+
+    fn number_words(&self) -> &[(&str, NumberWord)] {
+        &EN_NUMBER_WORDS
+    }
+
+    fn ordinal_suffix(&self, value: u64) -> &str {
+        match (value % 10, value % 100) {
+            (_, 11..=13) => "th",
+            (1, _) => "st",
+            (2, _) => "nd",
+            (3, _) => "rd",
+            _ => "th",
+        }
+    }
+}
+
+pub struct Spanish;
+
+const ES_MONTHS: [&str; 12] = [
+    "Enero",
+    "Febrero",
+    "Marzo",
+    "Abril",
+    "Mayo",
+    "Junio",
+    "Julio",
+    "Agosto",
+    "Septiembre",
+    "Octubre",
+    "Noviembre",
+    "Diciembre",
+];
+
+const ES_DAYS: [&str; 7] = [
+    "Lunes",
+    "Martes",
+    "Miércoles",
+    "Jueves",
+    "Viernes",
+    "Sábado",
+    "Domingo",
+];
+
+const ES_FEW: [&str; 30] = [
+    "cero",
+    "uno",
+    "dos",
+    "tres",
+    "cuatro",
+    "cinco",
+    "seis",
+    "siete",
+    "ocho",
+    "nueve",
+    "diez",
+    "once",
+    "doce",
+    "trece",
+    "catorce",
+    "quince",
+    "dieciséis",
+    "diecisiete",
+    "dieciocho",
+    "diecinueve",
+    "veinte",
+    "veintiuno",
+    "veintidós",
+    "veintitrés",
+    "veinticuatro",
+    "veinticinco",
+    "veintiséis",
+    "veintisiete",
+    "veintiocho",
+    "veintinueve",
+];
+
+const ES_TENS: [&str; 7] = [
+    "treinta",
+    "cuarenta",
+    "cincuenta",
+    "sesenta",
+    "setenta",
+    "ochenta",
+    "noventa",
+];
+
+const ES_HUNDREDS: [&str; 9] = [
+    "ciento",
+    "doscientos",
+    "trescientos",
+    "cuatrocientos",
+    "quinientos",
+    "seiscientos",
+    "setecientos",
+    "ochocientos",
+    "novecientos",
+];
+
+const ES_ORDINALS: [&str; 20] = [
+    "cero",
+    "primero",
+    "segundo",
+    "tercero",
+    "cuarto",
+    "quinto",
+    "sexto",
+    "séptimo",
+    "octavo",
+    "noveno",
+    "décimo",
+    "undécimo",
+    "duodécimo",
+    "decimotercero",
+    "decimocuarto",
+    "decimoquinto",
+    "decimosexto",
+    "decimoséptimo",
+    "decimoctavo",
+    "decimonoveno",
+];
+
+const ES_ORDINAL_TENS: [&str; 8] = [
+    "vigésimo",
+    "trigésimo",
+    "cuadragésimo",
+    "quincuagésimo",
+    "sexagésimo",
+    "septuagésimo",
+    "octogésimo",
+    "nonagésimo",
+];
+
+const ES_ORDINAL_HUNDREDS: [&str; 9] = [
+    "centésimo",
+    "ducentésimo",
+    "tricentésimo",
+    "cuadringentésimo",
+    "quingentésimo",
+    "sexcentésimo",
+    "septingentésimo",
+    "octingentésimo",
+    "noningentésimo",
+];
+
+const ES_NUMBER_WORDS: [(&str, NumberWord); 97] = [
+    ("cero", NumberWord::Add(0.0)),
+    ("un", NumberWord::Add(1.0)),
+    ("uno", NumberWord::Add(1.0)),
+    ("dos", NumberWord::Add(2.0)),
+    ("tres", NumberWord::Add(3.0)),
+    ("cuatro", NumberWord::Add(4.0)),
+    ("cinco", NumberWord::Add(5.0)),
+    ("seis", NumberWord::Add(6.0)),
+    ("siete", NumberWord::Add(7.0)),
+    ("ocho", NumberWord::Add(8.0)),
+    ("nueve", NumberWord::Add(9.0)),
+    ("diez", NumberWord::Add(10.0)),
+    ("once", NumberWord::Add(11.0)),
+    ("doce", NumberWord::Add(12.0)),
+    ("trece", NumberWord::Add(13.0)),
+    ("catorce", NumberWord::Add(14.0)),
+    ("quince", NumberWord::Add(15.0)),
+    ("dieciséis", NumberWord::Add(16.0)),
+    ("diecisiete", NumberWord::Add(17.0)),
+    ("dieciocho", NumberWord::Add(18.0)),
+    ("diecinueve", NumberWord::Add(19.0)),
+    ("veinte", NumberWord::Add(20.0)),
+    ("veintiún", NumberWord::Add(21.0)),
+    ("veintiuno", NumberWord::Add(21.0)),
+    ("veintidós", NumberWord::Add(22.0)),
+    ("veintitrés", NumberWord::Add(23.0)),
+    ("veinticuatro", NumberWord::Add(24.0)),
+    ("veinticinco", NumberWord::Add(25.0)),
+    ("veintiséis", NumberWord::Add(26.0)),
+    ("veintisiete", NumberWord::Add(27.0)),
+    ("veintiocho", NumberWord::Add(28.0)),
+    ("veintinueve", NumberWord::Add(29.0)),
+    ("treinta", NumberWord::Add(30.0)),
+    ("cuarenta", NumberWord::Add(40.0)),
+    ("cincuenta", NumberWord::Add(50.0)),
+    ("sesenta", NumberWord::Add(60.0)),
+    ("setenta", NumberWord::Add(70.0)),
+    ("ochenta", NumberWord::Add(80.0)),
+    ("noventa", NumberWord::Add(90.0)),
+    ("cien", NumberWord::Add(100.0)),
+    ("ciento", NumberWord::Add(100.0)),
+    ("doscientos", NumberWord::Add(200.0)),
+    ("trescientos", NumberWord::Add(300.0)),
+    ("cuatrocientos", NumberWord::Add(400.0)),
+    ("quinientos", NumberWord::Add(500.0)),
+    ("seiscientos", NumberWord::Add(600.0)),
+    ("setecientos", NumberWord::Add(700.0)),
+    ("ochocientos", NumberWord::Add(800.0)),
+    ("novecientos", NumberWord::Add(900.0)),
+    ("mil", NumberWord::Multiply(1e3)),
+    ("millón", NumberWord::Multiply(1e6)),
+    ("millones", NumberWord::Multiply(1e6)),
+    ("billón", NumberWord::Multiply(1e12)),
+    ("billones", NumberWord::Multiply(1e12)),
+    ("primer", NumberWord::Add(1.0)),
+    ("primero", NumberWord::Add(1.0)),
+    ("segundo", NumberWord::Add(2.0)),
+    ("tercer", NumberWord::Add(3.0)),
+    ("tercero", NumberWord::Add(3.0)),
+    ("cuarto", NumberWord::Add(4.0)),
+    ("quinto", NumberWord::Add(5.0)),
+    ("sexto", NumberWord::Add(6.0)),
+    ("séptimo", NumberWord::Add(7.0)),
+    ("octavo", NumberWord::Add(8.0)),
+    ("noveno", NumberWord::Add(9.0)),
+    ("décimo", NumberWord::Add(10.0)),
+    ("undécimo", NumberWord::Add(11.0)),
+    ("duodécimo", NumberWord::Add(12.0)),
+    ("decimotercero", NumberWord::Add(13.0)),
+    ("decimocuarto", NumberWord::Add(14.0)),
+    ("decimoquinto", NumberWord::Add(15.0)),
+    ("decimosexto", NumberWord::Add(16.0)),
+    ("decimoséptimo", NumberWord::Add(17.0)),
+    ("decimoctavo", NumberWord::Add(18.0)),
+    ("decimonoveno", NumberWord::Add(19.0)),
+    ("vigésimo", NumberWord::Add(20.0)),
+    ("trigésimo", NumberWord::Add(30.0)),
+    ("cuadragésimo", NumberWord::Add(40.0)),
+    ("quincuagésimo", NumberWord::Add(50.0)),
+    ("sexagésimo", NumberWord::Add(60.0)),
+    ("septuagésimo", NumberWord::Add(70.0)),
+    ("octogésimo", NumberWord::Add(80.0)),
+    ("nonagésimo", NumberWord::Add(90.0)),
+    ("centésimo", NumberWord::Add(100.0)),
+    ("ducentésimo", NumberWord::Add(200.0)),
+    ("tricentésimo", NumberWord::Add(300.0)),
+    ("cuadringentésimo", NumberWord::Add(400.0)),
+    ("quingentésimo", NumberWord::Add(500.0)),
+    ("sexcentésimo", NumberWord::Add(600.0)),
+    ("septingentésimo", NumberWord::Add(700.0)),
+    ("octingentésimo", NumberWord::Add(800.0)),
+    ("noningentésimo", NumberWord::Add(900.0)),
+    ("milésimo", NumberWord::Multiply(1e3)),
+    ("millonésimo", NumberWord::Multiply(1e6)),
+    ("billonésimo", NumberWord::Multiply(1e12)),
+    ("y", NumberWord::Connector),
+    ("una", NumberWord::Add(1.0)),
+];
+
+impl Spanish {
+    fn below_thousand(n: u64) -> String {
+        if n == 100 {
+            return "cien".to_string();
+        }
+
+        let (hundreds, remainder) = (n / 100, n % 100);
+        let mut words = Vec::new();
+        if hundreds > 0 {
+            words.push(ES_HUNDREDS[hundreds as usize - 1].to_string());
+        }
+        if remainder >= 30 {
+            let (tens, units) = (remainder / 10, remainder % 10);
+            words.push(ES_TENS[tens as usize - 3].to_string());
+            if units > 0 {
+                words.push(format!("y {}", ES_FEW[units as usize]));
+            }
+        } else if remainder > 0 || hundreds == 0 {
+            words.push(ES_FEW[remainder as usize].to_string());
+        }
+        words.join(" ")
+    }
+
+    /// The form of a number before "mil" or "millones", where "uno" becomes "un".
+    fn apocope(words: String) -> String {
+        if let Some(stem) = words.strip_suffix("veintiuno") {
+            format!("{}veintiún", stem)
+        } else if let Some(stem) = words.strip_suffix("uno") {
+            format!("{}un", stem)
+        } else {
+            words
+        }
+    }
+
+    fn cardinal(mut n: u64) -> String {
+        let mut words = Vec::new();
+
+        for (scale, singular, plural) in [
+            (1_000_000_000_000, "billón", "billones"),
+            (1_000_000, "millón", "millones"),
+        ] {
+            let count = n / scale;
+            if count == 1 {
+                words.push(format!("un {}", singular));
+            } else if count > 1 {
+                words.push(format!(
+                    "{} {}",
+                    Self::apocope(Self::cardinal(count)),
+                    plural
+                ));
+            }
+            n %= scale;
+        }
+
+        let thousands = n / 1000;
+        if thousands == 1 {
+            words.push("mil".to_string());
+        } else if thousands > 1 {
+            words.push(format!(
+                "{} mil",
+                Self::apocope(Self::below_thousand(thousands))
+            ));
+        }
+        n %= 1000;
+
+        if n > 0 || words.is_empty() {
+            words.push(Self::below_thousand(n));
+        }
+        words.join(" ")
+    }
+
+    /// Ordinals of a million and above are written as cardinals.
+    fn ordinal(n: u64) -> String {
+        if n == 0 || n >= 1_000_000 {
+            return Self::cardinal(n);
+        }
+
+        let mut words = Vec::new();
+        let thousands = n / 1000;
+        if thousands == 1 {
+            words.push("milésimo".to_string());
+        } else if thousands > 1 {
+            let prefix = Self::apocope(Self::cardinal(thousands)).replace(' ', "");
+            words.push(format!("{}milésimo", prefix));
+        }
+
+        let (hundreds, remainder) = ((n % 1000) / 100, n % 100);
+        if hundreds > 0 {
+            words.push(ES_ORDINAL_HUNDREDS[hundreds as usize - 1].to_string());
+        }
+        if remainder >= 20 {
+            let (tens, units) = (remainder / 10, remainder % 10);
+            words.push(ES_ORDINAL_TENS[tens as usize - 2].to_string());
+            if units > 0 {
+                words.push(ES_ORDINALS[units as usize].to_string());
+            }
+        } else if remainder > 0 {
+            words.push(ES_ORDINALS[remainder as usize].to_string());
+        }
+        words.join(" ")
+    }
+}
+
+impl Language for Spanish {
+    fn month_name(&self, month: usize) -> &str {
+        ES_MONTHS[month - 1]
+    }
+
+    fn day_name(&self, day: usize) -> &str {
+        ES_DAYS[day - 1]
+    }
+
+    fn to_words(&self, value: f64, ordinal: bool) -> String {
+        if ordinal {
+            Self::ordinal(value as u64)
+        } else {
+            Self::cardinal(value as u64)
+        }
+    }
+
+    fn number_words(&self) -> &[(&str, NumberWord)] {
+        &ES_NUMBER_WORDS
+    }
+
+    fn ordinal_suffix(&self, _value: u64) -> &str {
+        "º"
+    }
+}
+
+pub struct German;
+
+const DE_MONTHS: [&str; 12] = [
+    "Januar",
+    "Februar",
+    "März",
+    "April",
+    "Mai",
+    "Juni",
+    "Juli",
+    "August",
+    "September",
+    "Oktober",
+    "November",
+    "Dezember",
+];
+
+const DE_DAYS: [&str; 7] = [
+    "Montag",
+    "Dienstag",
+    "Mittwoch",
+    "Donnerstag",
+    "Freitag",
+    "Samstag",
+    "Sonntag",
+];
+
+const DE_FEW: [&str; 20] = [
+    "null",
+    "eins",
+    "zwei",
+    "drei",
+    "vier",
+    "fünf",
+    "sechs",
+    "sieben",
+    "acht",
+    "neun",
+    "zehn",
+    "elf",
+    "zwölf",
+    "dreizehn",
+    "vierzehn",
+    "fünfzehn",
+    "sechzehn",
+    "siebzehn",
+    "achtzehn",
+    "neunzehn",
+];
+
+const DE_TENS: [&str; 8] = [
+    "zwanzig", "dreißig", "vierzig", "fünfzig", "sechzig", "siebzig", "achtzig", "neunzig",
+];
+
+const DE_ORDINALS: [&str; 20] = [
+    "nullte",
+    "erste",
+    "zweite",
+    "dritte",
+    "vierte",
+    "fünfte",
+    "sechste",
+    "siebte",
+    "achte",
+    "neunte",
+    "zehnte",
+    "elfte",
+    "zwölfte",
+    "dreizehnte",
+    "vierzehnte",
+    "fünfzehnte",
+    "sechzehnte",
+    "siebzehnte",
+    "achtzehnte",
+    "neunzehnte",
+];
+
+const DE_NUMBER_WORDS: [(&str, NumberWord); 73] = [
+    ("null", NumberWord::Add(0.0)),
+    ("ein", NumberWord::Add(1.0)),
+    ("eine", NumberWord::Add(1.0)),
+    ("eins", NumberWord::Add(1.0)),
+    ("zwei", NumberWord::Add(2.0)),
+    ("drei", NumberWord::Add(3.0)),
+    ("vier", NumberWord::Add(4.0)),
+    ("fünf", NumberWord::Add(5.0)),
+    ("sechs", NumberWord::Add(6.0)),
+    ("sieben", NumberWord::Add(7.0)),
+    ("acht", NumberWord::Add(8.0)),
+    ("neun", NumberWord::Add(9.0)),
+    ("zehn", NumberWord::Add(10.0)),
+    ("elf", NumberWord::Add(11.0)),
+    ("zwölf", NumberWord::Add(12.0)),
+    ("dreizehn", NumberWord::Add(13.0)),
+    ("vierzehn", NumberWord::Add(14.0)),
+    ("fünfzehn", NumberWord::Add(15.0)),
+    ("sechzehn", NumberWord::Add(16.0)),
+    ("siebzehn", NumberWord::Add(17.0)),
+    ("achtzehn", NumberWord::Add(18.0)),
+    ("neunzehn", NumberWord::Add(19.0)),
+    ("zwanzig", NumberWord::Add(20.0)),
+    ("dreißig", NumberWord::Add(30.0)),
+    ("vierzig", NumberWord::Add(40.0)),
+    ("fünfzig", NumberWord::Add(50.0)),
+    ("sechzig", NumberWord::Add(60.0)),
+    ("siebzig", NumberWord::Add(70.0)),
+    ("achtzig", NumberWord::Add(80.0)),
+    ("neunzig", NumberWord::Add(90.0)),
+    ("hundert", NumberWord::Multiply(1e2)),
+    ("tausend", NumberWord::Multiply(1e3)),
+    ("million", NumberWord::Multiply(1e6)),
+    ("millionen", NumberWord::Multiply(1e6)),
+    ("milliarde", NumberWord::Multiply(1e9)),
+    ("milliarden", NumberWord::Multiply(1e9)),
+    ("billion", NumberWord::Multiply(1e12)),
+    ("billionen", NumberWord::Multiply(1e12)),
+    ("nullte", NumberWord::Add(0.0)),
+    ("erste", NumberWord::Add(1.0)),
+    ("zweite", NumberWord::Add(2.0)),
+    ("dritte", NumberWord::Add(3.0)),
+    ("vierte", NumberWord::Add(4.0)),
+    ("fünfte", NumberWord::Add(5.0)),
+    ("sechste", NumberWord::Add(6.0)),
+    ("siebte", NumberWord::Add(7.0)),
+    ("achte", NumberWord::Add(8.0)),
+    ("neunte", NumberWord::Add(9.0)),
+    ("zehnte", NumberWord::Add(10.0)),
+    ("elfte", NumberWord::Add(11.0)),
+    ("zwölfte", NumberWord::Add(12.0)),
+    ("dreizehnte", NumberWord::Add(13.0)),
+    ("vierzehnte", NumberWord::Add(14.0)),
+    ("fünfzehnte", NumberWord::Add(15.0)),
+    ("sechzehnte", NumberWord::Add(16.0)),
+    ("siebzehnte", NumberWord::Add(17.0)),
+    ("achtzehnte", NumberWord::Add(18.0)),
+    ("neunzehnte", NumberWord::Add(19.0)),
+    ("zwanzigste", NumberWord::Add(20.0)),
+    ("dreißigste", NumberWord::Add(30.0)),
+    ("vierzigste", NumberWord::Add(40.0)),
+    ("fünfzigste", NumberWord::Add(50.0)),
+    ("sechzigste", NumberWord::Add(60.0)),
+    ("siebzigste", NumberWord::Add(70.0)),
+    ("achtzigste", NumberWord::Add(80.0)),
+    ("neunzigste", NumberWord::Add(90.0)),
+    ("hundertste", NumberWord::Multiply(1e2)),
+    ("tausendste", NumberWord::Multiply(1e3)),
+    ("millionste", NumberWord::Multiply(1e6)),
+    ("milliardste", NumberWord::Multiply(1e9)),
+    ("billionste", NumberWord::Multiply(1e12)),
+    ("und", NumberWord::Connector),
+    ("erster", NumberWord::Add(1.0)),
+];
+
+impl German {
+    /// A number below a thousand as a single word, e.g. "einhundertzweiundzwanzig".
+    fn below_thousand(n: u64) -> String {
+        let (hundreds, remainder) = (n / 100, n % 100);
+        let mut words = String::new();
+        if hundreds > 0 {
+            words.push_str(if hundreds == 1 {
+                "ein"
+            } else {
+                DE_FEW[hundreds as usize]
+            });
+            words.push_str("hundert");
+        }
+        if remainder >= 20 {
+            let (tens, units) = (remainder / 10, remainder % 10);
+            if units > 0 {
+                words.push_str(if units == 1 {
+                    "ein"
+                } else {
+                    DE_FEW[units as usize]
+                });
+                words.push_str("und");
+            }
+            words.push_str(DE_TENS[tens as usize - 2]);
+        } else if remainder > 0 || hundreds == 0 {
+            words.push_str(DE_FEW[remainder as usize]);
+        }
+        words
+    }
+
+    /// The form of a number that precedes "tausend" or "millionen", where "eins" becomes "ein".
+    fn prefix(words: String) -> String {
+        match words.strip_suffix("eins") {
+            Some(stem) => format!("{}ein", stem),
+            None => words,
+        }
+    }
+
+    fn cardinal(mut n: u64) -> String {
+        let mut words = Vec::new();
+
+        for (scale, singular, plural) in [
+            (1_000_000_000_000, "billion", "billionen"),
+            (1_000_000_000, "milliarde", "milliarden"),
+            (1_000_000, "million", "millionen"),
+        ] {
+            let count = n / scale;
+            if count == 1 {
+                words.push(format!("eine {}", singular));
+            } else if count > 1 {
+                words.push(format!(
+                    "{} {}",
+                    Self::prefix(Self::cardinal(count)),
+                    plural
+                ));
+            }
+            n %= scale;
+        }
+
+        // Everything below a million is written as one word
+        let mut compound = String::new();
+        let thousands = n / 1000;
+        if thousands > 0 {
+            compound.push_str(&Self::prefix(Self::below_thousand(thousands)));
+            compound.push_str("tausend");
+        }
+        n %= 1000;
+        if n > 0 || (thousands == 0 && words.is_empty()) {
+            compound.push_str(&Self::below_thousand(n));
+        }
+        if !compound.is_empty() {
+            words.push(compound);
+        }
+
+        words.join(" ")
+    }
+
+    fn ordinal(n: u64) -> String {
+        let remainder = n % 100;
+        if n == 0 {
+            return DE_ORDINALS[0].to_string();
+        } else if remainder == 0 || remainder >= 20 {
+            return format!("{}ste", Self::cardinal(n));
+        }
+
+        let ordinal = DE_ORDINALS[remainder as usize];
+        match n - remainder {
+            0 => ordinal.to_string(),
+            rest if rest % 1_000_000 == 0 => format!("{} {}", Self::cardinal(rest), ordinal),
+            rest => format!("{}{}", Self::cardinal(rest), ordinal),
+        }
+    }
+}
+
+impl Language for German {
+    fn month_name(&self, month: usize) -> &str {
+        DE_MONTHS[month - 1]
+    }
+
+    fn day_name(&self, day: usize) -> &str {
+        DE_DAYS[day - 1]
+    }
+
+    fn to_words(&self, value: f64, ordinal: bool) -> String {
+        if ordinal {
+            Self::ordinal(value as u64)
+        } else {
+            Self::cardinal(value as u64)
+        }
+    }
+
+    fn number_words(&self) -> &[(&str, NumberWord)] {
+        &DE_NUMBER_WORDS
+    }
+
+    fn ordinal_suffix(&self, _value: u64) -> &str {
+        "."
+    }
+}
+
+pub struct French;
+
+const FR_MONTHS: [&str; 12] = [
+    "Janvier",
+    "Février",
+    "Mars",
+    "Avril",
+    "Mai",
+    "Juin",
+    "Juillet",
+    "Août",
+    "Septembre",
+    "Octobre",
+    "Novembre",
+    "Décembre",
+];
+
+const FR_DAYS: [&str; 7] = [
+    "Lundi", "Mardi", "Mercredi", "Jeudi", "Vendredi", "Samedi", "Dimanche",
+];
+
+const FR_FEW: [&str; 17] = [
+    "zéro", "un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf", "dix", "onze",
+    "douze", "treize", "quatorze", "quinze", "seize",
+];
+
+const FR_TENS: [&str; 5] = ["vingt", "trente", "quarante", "cinquante", "soixante"];
+
+const FR_NUMBER_WORDS: [(&str, NumberWord); 64] = [
+    ("zéro", NumberWord::Add(0.0)),
+    ("un", NumberWord::Add(1.0)),
+    ("une", NumberWord::Add(1.0)),
+    ("deux", NumberWord::Add(2.0)),
+    ("trois", NumberWord::Add(3.0)),
+    ("quatre", NumberWord::Add(4.0)),
+    ("cinq", NumberWord::Add(5.0)),
+    ("six", NumberWord::Add(6.0)),
+    ("sept", NumberWord::Add(7.0)),
+    ("huit", NumberWord::Add(8.0)),
+    ("neuf", NumberWord::Add(9.0)),
+    ("dix", NumberWord::Add(10.0)),
+    ("onze", NumberWord::Add(11.0)),
+    ("douze", NumberWord::Add(12.0)),
+    ("treize", NumberWord::Add(13.0)),
+    ("quatorze", NumberWord::Add(14.0)),
+    ("quinze", NumberWord::Add(15.0)),
+    ("seize", NumberWord::Add(16.0)),
+    ("vingt", NumberWord::Add(20.0)),
+    ("trente", NumberWord::Add(30.0)),
+    ("quarante", NumberWord::Add(40.0)),
+    ("cinquante", NumberWord::Add(50.0)),
+    ("soixante", NumberWord::Add(60.0)),
+    ("quatre-vingt", NumberWord::Add(80.0)),
+    ("quatre-vingts", NumberWord::Add(80.0)),
+    ("cent", NumberWord::Multiply(1e2)),
+    ("cents", NumberWord::Multiply(1e2)),
+    ("mille", NumberWord::Multiply(1e3)),
+    ("million", NumberWord::Multiply(1e6)),
+    ("millions", NumberWord::Multiply(1e6)),
+    ("milliard", NumberWord::Multiply(1e9)),
+    ("milliards", NumberWord::Multiply(1e9)),
+    ("billion", NumberWord::Multiply(1e12)),
+    ("billions", NumberWord::Multiply(1e12)),
+    ("premier", NumberWord::Add(1.0)),
+    ("première", NumberWord::Add(1.0)),
+    ("unième", NumberWord::Add(1.0)),
+    ("deuxième", NumberWord::Add(2.0)),
+    ("troisième", NumberWord::Add(3.0)),
+    ("quatrième", NumberWord::Add(4.0)),
+    ("cinquième", NumberWord::Add(5.0)),
+    ("sixième", NumberWord::Add(6.0)),
+    ("septième", NumberWord::Add(7.0)),
+    ("huitième", NumberWord::Add(8.0)),
+    ("neuvième", NumberWord::Add(9.0)),
+    ("dixième", NumberWord::Add(10.0)),
+    ("onzième", NumberWord::Add(11.0)),
+    ("douzième", NumberWord::Add(12.0)),
+    ("treizième", NumberWord::Add(13.0)),
+    ("quatorzième", NumberWord::Add(14.0)),
+    ("quinzième", NumberWord::Add(15.0)),
+    ("seizième", NumberWord::Add(16.0)),
+    ("vingtième", NumberWord::Add(20.0)),
+    ("trentième", NumberWord::Add(30.0)),
+    ("quarantième", NumberWord::Add(40.0)),
+    ("cinquantième", NumberWord::Add(50.0)),
+    ("soixantième", NumberWord::Add(60.0)),
+    ("quatre-vingtième", NumberWord::Add(80.0)),
+    ("centième", NumberWord::Multiply(1e2)),
+    ("millième", NumberWord::Multiply(1e3)),
+    ("millionième", NumberWord::Multiply(1e6)),
+    ("milliardième", NumberWord::Multiply(1e9)),
+    ("billionième", NumberWord::Multiply(1e12)),
+    ("et", NumberWord::Connector),
+];
+
+impl French {
+    fn below_hundred(n: u64) -> String {
+        match n {
+            0..=16 => FR_FEW[n as usize].to_string(),
+            17..=19 => format!("dix-{}", FR_FEW[n as usize - 10]),
+            20..=69 => {
+                let (tens, units) = (FR_TENS[n as usize / 10 - 2], n % 10);
+                match units {
+                    0 => tens.to_string(),
+                    1 => format!("{} et un", tens),
+                    _ => format!("{}-{}", tens, FR_FEW[units as usize]),
+                }
+            }
+            71 => "soixante et onze".to_string(),
+            70..=79 => format!("soixante-{}", Self::below_hundred(n - 60)),
+            80 => "quatre-vingts".to_string(),
+            _ => format!("quatre-vingt-{}", Self::below_hundred(n - 80)),
+        }
+    }
+
+    fn below_thousand(n: u64) -> String {
+        let (hundreds, remainder) = (n / 100, n % 100);
+        let mut words = match hundreds {
+            0 => return Self::below_hundred(remainder),
+            1 => "cent".to_string(),
+            _ if remainder == 0 => format!("{} cents", FR_FEW[hundreds as usize]),
+            _ => format!("{} cent", FR_FEW[hundreds as usize]),
+        };
+        if remainder > 0 {
+            words.push(' ');
+            words.push_str(&Self::below_hundred(remainder));
+        }
+        words
+    }
+
+    fn cardinal(mut n: u64) -> String {
+        let mut words = Vec::new();
+
+        for (scale, name) in [
+            (1_000_000_000_000, "billion"),
+            (1_000_000_000, "milliard"),
+            (1_000_000, "million"),
+        ] {
+            let count = n / scale;
+            if count == 1 {
+                words.push(format!("un {}", name));
+            } else if count > 1 {
+                words.push(format!("{} {}s", Self::cardinal(count), name));
+            }
+            n %= scale;
+        }
+
+        // "Mille" is invariable, and "vingts" and "cents" lose their plural before it
+        let thousands = n / 1000;
+        if thousands == 1 {
+            words.push("mille".to_string());
+        } else if thousands > 1 {
+            let prefix = Self::below_thousand(thousands);
+            let prefix = if prefix.ends_with("cents") || prefix.ends_with("vingts") {
+                &prefix[..prefix.len() - 1]
+            } else {
+                &prefix
+            };
+            words.push(format!("{} mille", prefix));
+        }
+        n %= 1000;
+
+        if n > 0 || words.is_empty() {
+            words.push(Self::below_thousand(n));
+        }
+        words.join(" ")
+    }
+
+    fn ordinal(n: u64) -> String {
+        match n {
+            0 => return Self::cardinal(0),
+            1 => return "premier".to_string(),
+            _ => {}
+        }
+
+        let cardinal = Self::cardinal(n);
+        let split = cardinal.rfind([' ', '-']).map_or(0, |i| i + 1);
+        let (rest, last) = cardinal.split_at(split);
+        let stem = match last {
+            "cinq" => "cinqu",
+            "neuf" => "neuv",
+            "trois" => "trois",
+            _ if last.ends_with('s') => &last[..last.len() - 1],
+            _ => last.strip_suffix('e').unwrap_or(last),
+        };
+        format!("{}{}ième", rest, stem)
+    }
+}
+
+impl Language for French {
+    fn month_name(&self, month: usize) -> &str {
+        FR_MONTHS[month - 1]
+    }
+
+    fn day_name(&self, day: usize) -> &str {
+        FR_DAYS[day - 1]
+    }
+
+    fn to_words(&self, value: f64, ordinal: bool) -> String {
+        if ordinal {
+            Self::ordinal(value as u64)
+        } else {
+            Self::cardinal(value as u64)
+        }
+    }
+
+    fn number_words(&self) -> &[(&str, NumberWord)] {
+        &FR_NUMBER_WORDS
+    }
+
+    fn ordinal_suffix(&self, value: u64) -> &str {
+        if value == 1 {
+            "er"
+        } else {
+            "e"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_round_trip() {
+        let languages: [&dyn Language; 4] = [&English, &Spanish, &German, &French];
+        for language in languages {
+            for n in [
+                0.0,
+                1.0,
+                7.0,
+                16.0,
+                21.0,
+                40.0,
+                71.0,
+                80.0,
+                91.0,
+                101.0,
+                200.0,
+                366.0,
+                1999.0,
+                2018.0,
+                21_000.0,
+                80_000.0,
+                1_001_000.0,
+                20_000_001.0,
+            ] {
+                for ordinal in [false, true] {
+                    let words = language.to_words(n, ordinal);
+                    assert_eq!(from_words(&words, language), Some(n), "{}", words);
+                }
+            }
+        }
+        assert_eq!(
+            from_words(&English.to_words(1e15, false), &English),
+            Some(1e15)
+        );
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(
+            English.to_words(1999.0, true),
+            "one thousand, nine hundred and ninety-ninth"
+        );
+        assert_eq!(Spanish.to_words(2018.0, false), "dos mil dieciocho");
+        assert_eq!(Spanish.to_words(21_000.0, false), "veintiún mil");
+        assert_eq!(Spanish.to_words(23.0, true), "vigésimo tercero");
+        assert_eq!(German.to_words(2018.0, false), "zweitausendachtzehn");
+        assert_eq!(German.to_words(21.0, true), "einundzwanzigste");
+        assert_eq!(German.to_words(103.0, true), "einhundertdritte");
+        assert_eq!(French.to_words(80_000.0, false), "quatre-vingt mille");
+        assert_eq!(French.to_words(71.0, false), "soixante et onze");
+        assert_eq!(French.to_words(21.0, true), "vingt et unième");
+        assert_eq!(French.to_words(3.0, true), "troisième");
+    }
+
+    #[test]
+    fn title_case_skips_connectors() {
+        let words = English.to_words(123.0, false);
+        assert_eq!(title_case(&words, &English), "One Hundred and Twenty-Three");
+        assert_eq!(
+            title_case(&Spanish.to_words(31.0, false), &Spanish),
+            "Treinta y Uno"
+        );
+    }
+
+    #[test]
+    fn language_fallback() {
+        let languages = Languages::default();
+        assert_eq!(languages.get("es-MX").month_name(3), "Marzo");
+        assert_eq!(languages.get("DE").month_name(3), "März");
+        assert_eq!(languages.get("xx").month_name(3), "March");
+    }
+}
@@ -1,39 +1,62 @@
 #![cfg_attr(not(doctest), doc = include_str!("../README.md"))]
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 
 use bumpalo::Bump;
 
+mod bindings;
 mod datetime;
+mod decimal_format;
 mod errors;
 mod evaluator;
+mod expression;
+mod integer_format;
+mod json;
+mod language;
 mod parser;
 
-pub use errors::Error;
+pub use bindings::{Bindings, IntoBinding};
+pub use errors::{Error, InputPosition};
 pub use evaluator::functions::FunctionContext;
-pub use evaluator::value::{ArrayFlags, Value};
+pub use evaluator::value::{ArrayFlags, ObjectMap, Value};
+pub use evaluator::{EvaluationOptions, EvaluationStats, RegexBudget};
+pub use expression::Expression;
+pub use language::{Language, NumberWord};
 
-use evaluator::{frame::Frame, functions::*, Evaluator};
+use evaluator::{frame::Frame, signature::Signature};
+use language::Languages;
 use parser::ast::Ast;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 pub struct JsonAta<'a> {
-    ast: Ast,
+    expression: Expression,
+    /// The functions registered and variables assigned from Rust
     frame: Frame<'a>,
+    /// The built-in functions, which take precedence over the registered ones
+    natives: Frame<'a>,
     arena: &'a Bump,
+    languages: RefCell<Languages>,
+    regex_budget: Cell<RegexBudget>,
+    stats: Cell<EvaluationStats>,
 }
 
 impl<'a> JsonAta<'a> {
     pub fn new(expr: &str, arena: &'a Bump) -> Result<JsonAta<'a>> {
+        let frame = Frame::new();
         Ok(Self {
-            ast: parser::parse(expr)?,
-            frame: Frame::new(),
+            expression: Expression::compile(expr)?,
+            natives: expression::natives(arena, &frame),
+            frame,
             arena,
+            languages: RefCell::new(Languages::default()),
+            regex_budget: Cell::new(RegexBudget::default()),
+            stats: Cell::new(EvaluationStats::default()),
         })
     }
 
     pub fn ast(&self) -> &Ast {
-        &self.ast
+        self.expression.ast()
     }
 
     pub fn assign_var(&self, name: &str, value: &'a Value<'a>) {
@@ -52,29 +75,39 @@ impl<'a> JsonAta<'a> {
         );
     }
 
-    fn json_value_to_value(&self, json_value: &serde_json::Value) -> &'a mut Value<'a> {
-        match json_value {
-            serde_json::Value::Null => Value::null(self.arena),
-            serde_json::Value::Bool(b) => self.arena.alloc(Value::Bool(*b)),
-            serde_json::Value::Number(n) => Value::number(self.arena, n.as_f64().unwrap()),
-            serde_json::Value::String(s) => Value::string(self.arena, s),
+    /// Register a function which has its arguments validated against a signature, such as
+    /// `<s-n?:s>`, before it's called. This takes care of the context being passed as an
+    /// argument where the signature allows it with `-`, and of wrapping single values passed
+    /// as arrays. The arity of the function is the number of parameters in the signature.
+    pub fn register_function_with_signature(
+        &self,
+        name: &str,
+        signature: &str,
+        implementation: fn(FunctionContext<'a, '_>, &[&'a Value<'a>]) -> Result<&'a Value<'a>>,
+    ) -> Result<()> {
+        let signature = Signature::parse(signature, 0)?;
+        self.frame.bind(
+            name,
+            Value::nativefn_with_signature(self.arena, name, &signature, implementation),
+        );
+        Ok(())
+    }
 
-            serde_json::Value::Array(a) => {
-                let array = Value::array_with_capacity(self.arena, a.len(), ArrayFlags::empty());
-                for v in a.iter() {
-                    array.push(self.json_value_to_value(v))
-                }
+    /// Register a language for the date/time functions under a tag such as `nl`, replacing
+    /// any existing language with that tag. English, Spanish, German and French are built in.
+    pub fn register_language(&self, tag: &str, language: impl Language + 'static) {
+        self.languages.borrow_mut().register(tag, language);
+    }
 
-                array
-            }
-            serde_json::Value::Object(o) => {
-                let object = Value::object_with_capacity(self.arena, o.len());
-                for (k, v) in o.iter() {
-                    object.insert(k, self.json_value_to_value(v));
-                }
-                object
-            }
-        }
+    /// Limit the work done by regular expressions in each evaluation. A time limit is only
+    /// checked between searches unless there's also a step limit.
+    pub fn set_regex_budget(&self, regex_budget: RegexBudget) {
+        self.regex_budget.set(regex_budget);
+    }
+
+    /// Statistics about the work done in the most recent evaluation.
+    pub fn stats(&self) -> EvaluationStats {
+        self.stats.get()
     }
 
     pub fn evaluate(
@@ -82,14 +115,35 @@ impl<'a> JsonAta<'a> {
         input: Option<&str>,
         bindings: Option<&HashMap<&str, &serde_json::Value>>,
     ) -> Result<&'a Value<'a>> {
-        if let Some(bindings) = bindings {
-            for (key, json_value) in bindings.iter() {
-                let value = self.json_value_to_value(json_value);
-                self.assign_var(key, value);
+        let input = expression::parse_input(self.arena, input)?;
+        match bindings {
+            Some(bindings) => {
+                let bindings = Bindings::from_json(self.arena, bindings);
+                self.evaluate_in_frame(input, Some(&bindings), None, None)
             }
-        };
+            None => self.evaluate_in_frame(input, None, None, None),
+        }
+    }
 
-        self.evaluate_timeboxed(input, None, None)
+    /// Evaluate with variables which are only bound for this evaluation.
+    pub fn evaluate_with_bindings(
+        &self,
+        input: Option<&str>,
+        bindings: &Bindings<'a>,
+    ) -> Result<&'a Value<'a>> {
+        let input = expression::parse_input(self.arena, input)?;
+        self.evaluate_in_frame(input, Some(bindings), None, None)
+    }
+
+    /// Evaluate against an input which has already been created in the arena, e.g. with
+    /// [`Value::from_json`] or [`Value::from_serialize`], so that one document can be shared by
+    /// many evaluations without being parsed again for each of them.
+    pub fn evaluate_value(
+        &self,
+        input: &'a Value<'a>,
+        bindings: Option<&Bindings<'a>>,
+    ) -> Result<&'a Value<'a>> {
+        self.evaluate_in_frame(input, bindings, None, None)
     }
 
     pub fn evaluate_timeboxed(
@@ -98,87 +152,61 @@ impl<'a> JsonAta<'a> {
         max_depth: Option<usize>,
         time_limit: Option<usize>,
     ) -> Result<&'a Value<'a>> {
-        let input = match input {
-            Some(input) => {
-                let input_ast = parser::parse(input)?;
-                let evaluator = Evaluator::new(None, self.arena, None, None);
-                evaluator.evaluate(&input_ast, Value::undefined(), &Frame::new())?
-            }
-            None => Value::undefined(),
-        };
+        let input = expression::parse_input(self.arena, input)?;
+        self.evaluate_in_frame(input, None, max_depth, time_limit)
+    }
 
-        // If the input is an array, wrap it in an array so that it gets treated as a single input
-        let input = if input.is_array() {
-            Value::wrap_in_array(self.arena, input, ArrayFlags::WRAPPED)
-        } else {
-            input
-        };
+    pub fn evaluate_value_timeboxed(
+        &self,
+        input: &'a Value<'a>,
+        max_depth: Option<usize>,
+        time_limit: Option<usize>,
+    ) -> Result<&'a Value<'a>> {
+        self.evaluate_in_frame(input, None, max_depth, time_limit)
+    }
 
-        macro_rules! bind_native {
-            ($name:literal, $arity:literal, $fn:ident) => {
-                self.frame
-                    .bind($name, Value::nativefn(&self.arena, $name, $arity, $fn));
-            };
-        }
+    /// Evaluate with both bindings and limits. The regex budget in `options` is used instead of
+    /// the one set with [`JsonAta::set_regex_budget`]. The input can be created with
+    /// [`Value::from_json_str`], or be [`Value::undefined`] for no input.
+    pub fn evaluate_with_options(
+        &self,
+        input: &'a Value<'a>,
+        bindings: Option<&Bindings<'a>>,
+        options: &EvaluationOptions,
+    ) -> Result<&'a Value<'a>> {
+        self.evaluate_with(input, bindings, options)
+    }
+
+    fn evaluate_in_frame(
+        &self,
+        input: &'a Value<'a>,
+        bindings: Option<&Bindings<'a>>,
+        max_depth: Option<usize>,
+        time_limit: Option<usize>,
+    ) -> Result<&'a Value<'a>> {
+        let options = EvaluationOptions {
+            max_depth,
+            time_limit,
+            regex_budget: self.regex_budget.get(),
+        };
+        self.evaluate_with(input, bindings, &options)
+    }
 
-        self.frame.bind("$", input);
-        bind_native!("abs", 1, fn_abs);
-        bind_native!("append", 2, fn_append);
-        bind_native!("assert", 2, fn_assert);
-        bind_native!("base64decode", 1, fn_base64_decode);
-        bind_native!("base64encode", 1, fn_base64_encode);
-        bind_native!("boolean", 1, fn_boolean);
-        bind_native!("ceil", 1, fn_ceil);
-        bind_native!("contains", 2, fn_contains);
-        bind_native!("count", 1, fn_count);
-        bind_native!("distinct", 1, fn_distinct);
-        bind_native!("each", 2, fn_each);
-        bind_native!("error", 1, fn_error);
-        bind_native!("exists", 1, fn_exists);
-        bind_native!("fromMillis", 3, from_millis);
-        bind_native!("toMillis", 2, to_millis);
-        bind_native!("single", 2, single);
-        bind_native!("filter", 2, fn_filter);
-        bind_native!("floor", 1, fn_floor);
-        bind_native!("join", 2, fn_join);
-        bind_native!("keys", 1, fn_keys);
-        bind_native!("length", 1, fn_length);
-        bind_native!("lookup", 2, fn_lookup);
-        bind_native!("lowercase", 1, fn_lowercase);
-        bind_native!("map", 2, fn_map);
-        bind_native!("match", 2, fn_match);
-        bind_native!("max", 1, fn_max);
-        bind_native!("merge", 1, fn_merge);
-        bind_native!("min", 1, fn_min);
-        bind_native!("not", 1, fn_not);
-        bind_native!("now", 2, fn_now);
-        bind_native!("number", 1, fn_number);
-        bind_native!("pad", 2, fn_pad);
-        bind_native!("power", 2, fn_power);
-        bind_native!("random", 0, fn_random);
-        bind_native!("reduce", 3, fn_reduce);
-        bind_native!("replace", 4, fn_replace);
-        bind_native!("reverse", 1, fn_reverse);
-        bind_native!("round", 2, fn_round);
-        bind_native!("sort", 2, fn_sort);
-        bind_native!("split", 3, fn_split);
-        bind_native!("sqrt", 1, fn_sqrt);
-        bind_native!("string", 1, fn_string);
-        bind_native!("substring", 3, fn_substring);
-        bind_native!("substringBefore", 2, fn_substring_before);
-        bind_native!("substringAfter", 2, fn_substring_after);
-        bind_native!("sum", 1, fn_sum);
-        bind_native!("trim", 1, fn_trim);
-        bind_native!("uppercase", 1, fn_uppercase);
-        bind_native!("zip", 1, fn_zip);
-        bind_native!("millis", 0, fn_millis);
-        bind_native!("uuid", 0, fn_uuid);
-
-        let chain_ast = Some(parser::parse(
-            "function($f, $g) { function($x){ $g($f($x)) } }",
-        )?);
-        let evaluator = Evaluator::new(chain_ast, self.arena, max_depth, time_limit);
-        evaluator.evaluate(&self.ast, input, &self.frame)
+    fn evaluate_with(
+        &self,
+        input: &'a Value<'a>,
+        bindings: Option<&Bindings<'a>>,
+        options: &EvaluationOptions,
+    ) -> Result<&'a Value<'a>> {
+        let input = expression::wrap_input(self.arena, input);
+        let frame = expression::evaluation_frame(&self.natives, bindings, input);
+        let evaluator = self
+            .expression
+            .evaluator(self.arena, options)
+            .with_languages(self.languages.borrow().clone());
+        let result = evaluator.evaluate(self.expression.ast(), input, &frame);
+        self.stats.set(evaluator.stats());
+        result
     }
 }
 
@@ -187,7 +215,7 @@ mod tests {
     use chrono::{DateTime, Datelike, Offset, Utc};
     use regress::Regex;
 
-    use bumpalo::collections::String as BumpString;
+    use test_case::test_case;
 
     use super::*;
 
@@ -197,11 +225,310 @@ mod tests {
         let jsonata = JsonAta::new("$test()", &arena).unwrap();
         jsonata.register_function("test", 0, |ctx, _| Ok(Value::number(ctx.arena, 1)));
 
-        let result = jsonata.evaluate(Some(r#"anything"#), None);
+        let result = jsonata.evaluate(Some(r#""anything""#), None);
 
         assert_eq!(result.unwrap(), Value::number(&arena, 1));
     }
 
+    #[test]
+    fn register_language() {
+        struct Dutch;
+
+        impl Language for Dutch {
+            fn month_name(&self, month: usize) -> &str {
+                [
+                    "Januari",
+                    "Februari",
+                    "Maart",
+                    "April",
+                    "Mei",
+                    "Juni",
+                    "Juli",
+                    "Augustus",
+                    "September",
+                    "Oktober",
+                    "November",
+                    "December",
+                ][month - 1]
+            }
+
+            fn day_name(&self, day: usize) -> &str {
+                [
+                    "Maandag",
+                    "Dinsdag",
+                    "Woensdag",
+                    "Donderdag",
+                    "Vrijdag",
+                    "Zaterdag",
+                    "Zondag",
+                ][day - 1]
+            }
+
+            fn to_words(&self, value: f64, _ordinal: bool) -> String {
+                ["nul", "een", "twee", "drie"][value as usize].to_string()
+            }
+
+            fn number_words(&self) -> &[(&str, NumberWord)] {
+                &[
+                    ("nul", NumberWord::Add(0.0)),
+                    ("een", NumberWord::Add(1.0)),
+                    ("twee", NumberWord::Add(2.0)),
+                    ("drie", NumberWord::Add(3.0)),
+                ]
+            }
+
+            fn ordinal_suffix(&self, _value: u64) -> &str {
+                "e"
+            }
+        }
+
+        let arena = Bump::new();
+        let jsonata = JsonAta::new(
+            "[$fromMillis(1520208000000, '[FNn] [D1o] [MNn]', (), 'nl-BE'), $toMillis('drie Maart 2018', '[Dw] [MNn] [Y]', 'nl')]",
+            &arena,
+        )
+        .unwrap();
+        jsonata.register_language("nl", Dutch);
+
+        let result = jsonata.evaluate(None, None).unwrap();
+
+        assert_eq!(result[0].as_str(), "Maandag 5e Maart");
+        assert_eq!(result[1].as_f64(), 1520035200000.0);
+
+        let mut expression = Expression::compile(
+            "[$fromMillis(1520208000000, '[FNn] [D1o] [MNn]', (), 'nl-BE'), $fromMillis(1520208000000, '[FNn]', (), 'xx')]",
+        )
+        .unwrap();
+        expression.register_language("nl", Dutch);
+
+        let result = expression.evaluate(&arena, None, None).unwrap();
+
+        assert_eq!(result[0].as_str(), "Maandag 5e Maart");
+        // An unknown tag falls back to English
+        assert_eq!(result[1].as_str(), "Monday");
+    }
+
+    #[test_case("$n = 0 ? $acc : $count($n - 1, $acc + 1)" ; "conditional")]
+    #[test_case("$n = 0 ? $acc : ($next := $n - 1; $count($next, $acc + 1))" ; "block")]
+    #[test_case("$n = 0 ? $acc : ($n - 1) ~> $count($acc + 1)" ; "apply")]
+    #[test_case("$n = 0 ? $acc : $acc + 1 ~> function($acc) { $count($n - 1, $acc) }" ; "apply lambda")]
+    fn deep_tail_recursion(body: &str) {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new(
+            &format!("($count := function($n, $acc) {{ {body} }}; $count(100000, 0))"),
+            &arena,
+        )
+        .unwrap();
+
+        // Tail calls don't add to the depth of the evaluation
+        let result = jsonata.evaluate_timeboxed(None, Some(50), None).unwrap();
+
+        assert_eq!(result.as_f64(), 100000.0);
+    }
+
+    #[test_case("$" => r#"{"z":1,"a":{"y":2,"b":3},"m":4}"# ; "input")]
+    #[test_case("{'q': 1, 'c': a, 'k': z}" => r#"{"q":1,"c":{"y":2,"b":3},"k":1}"# ; "constructor")]
+    #[test_case("$keys($)" => r#"["z","a","m"]"# ; "keys")]
+    #[test_case("*" => r#"[1,{"y":2,"b":3},4]"# ; "wildcard")]
+    #[test_case("$each($, function($v, $k) { $k })" => r#"["z","a","m"]"# ; "each")]
+    #[test_case("$merge([$, {'b': 5, 'z': 6}])" => r#"{"z":6,"a":{"y":2,"b":3},"m":4,"b":5}"# ; "merge")]
+    #[test_case("$ ~> |$|{'n': 7}, ['a']|" => r#"{"z":1,"m":4,"n":7}"# ; "transform")]
+    #[test_case("$.[{'k': 'x'}, {'k': 'w'}, {'k': 'x'}]{k: $count(k)}" => r#"{"x":2,"w":1}"# ; "grouping")]
+    fn object_key_order(expr: &str) -> String {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new(expr, &arena).unwrap();
+
+        let result = jsonata
+            .evaluate(Some(r#"{"z": 1, "a": {"y": 2, "b": 3}, "m": 4}"#), None)
+            .unwrap();
+
+        result.serialize(false)
+    }
+
+    #[test]
+    fn regex_cache_stats() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new(
+            r#"$map(["a1", "b2", "c3"], function($s) { [$match($s, "[0-9]"), $match($s, "[a-z]")] })"#,
+            &arena,
+        )
+        .unwrap();
+
+        let result = jsonata.evaluate(None, None).unwrap();
+
+        assert_eq!(result.members().count(), 3);
+        assert_eq!(
+            jsonata.stats(),
+            EvaluationStats {
+                regex_cache_hits: 4,
+                regex_cache_misses: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn regex_budget() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new(
+            r#"$map([1, 2, 3], function($i) { $split("a,b,c,d", /,/) })"#,
+            &arena,
+        )
+        .unwrap();
+
+        jsonata.set_regex_budget(RegexBudget {
+            max_steps: Some(33),
+            max_input_length: None,
+        });
+        assert!(jsonata.evaluate(None, None).is_ok());
+
+        jsonata.set_regex_budget(RegexBudget {
+            max_steps: Some(32),
+            max_input_length: None,
+        });
+        let err = jsonata.evaluate(None, None).unwrap_err();
+        assert_eq!(err.code(), "U1002");
+
+        jsonata.set_regex_budget(RegexBudget {
+            max_steps: None,
+            max_input_length: Some(6),
+        });
+        let err = jsonata.evaluate(None, None).unwrap_err();
+        assert_eq!(err.code(), "U1002");
+        assert_eq!(
+            err.to_string(),
+            "U1002 @ 31: Regular expression matching exceeded the input length allowed for this evaluation"
+        );
+    }
+
+    #[test]
+    fn regex_budget_exceeded_in_eval() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new(r#"$eval("$split('a,b,c,d', /,/)")"#, &arena).unwrap();
+
+        jsonata.set_regex_budget(RegexBudget {
+            max_steps: Some(10),
+            max_input_length: None,
+        });
+        let err = jsonata.evaluate(None, None).unwrap_err();
+        assert_eq!(err.code(), "U1002");
+    }
+
+    #[test]
+    fn regex_budget_exceeded_in_reduce() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new(
+            r#"$reduce(["a,b", "c,d"], function($acc, $s) { $append($acc, $split($s, /,/)) }, [])"#,
+            &arena,
+        )
+        .unwrap();
+
+        jsonata.set_regex_budget(RegexBudget {
+            max_steps: Some(1),
+            max_input_length: None,
+        });
+        let err = jsonata.evaluate(None, None).unwrap_err();
+        assert_eq!(err.code(), "U1002");
+    }
+
+    #[test]
+    fn regex_budget_stops_catastrophic_backtracking() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new(
+            r#"$match("aaaaaaaaaaaaaaaaaaaaaaaaa!", /^(a|a)*$/)"#,
+            &arena,
+        )
+        .unwrap();
+
+        jsonata.set_regex_budget(RegexBudget {
+            max_steps: Some(10),
+            max_input_length: Some(100),
+        });
+        let started_at = std::time::Instant::now();
+        let err = jsonata
+            .evaluate_timeboxed(None, None, Some(100))
+            .unwrap_err();
+        assert_eq!(err.code(), "U1002");
+        assert!(started_at.elapsed() < std::time::Duration::from_millis(100));
+
+        // With a step limit too large to reach, the search is stopped by the time limit
+        jsonata.set_regex_budget(RegexBudget {
+            max_steps: Some(usize::MAX),
+            max_input_length: None,
+        });
+        let started_at = std::time::Instant::now();
+        let err = jsonata
+            .evaluate_timeboxed(None, None, Some(100))
+            .unwrap_err();
+        assert_eq!(err.code(), "U1001");
+        assert!(started_at.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn evaluate_with_options() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new(
+            "($f := function($n) { $n = 0 ? $x : $f($n - 1) & '' }; $f(10))",
+            &arena,
+        )
+        .unwrap();
+        let bindings = Bindings::new(&arena).bind("x", "done");
+
+        let options = EvaluationOptions {
+            max_depth: Some(100),
+            time_limit: Some(1000),
+            ..Default::default()
+        };
+        let result = jsonata
+            .evaluate_with_options(Value::undefined(), Some(&bindings), &options)
+            .unwrap();
+        assert_eq!(result.as_str(), "done");
+
+        let options = EvaluationOptions {
+            max_depth: Some(10),
+            ..Default::default()
+        };
+        let err = jsonata
+            .evaluate_with_options(Value::undefined(), Some(&bindings), &options)
+            .unwrap_err();
+        assert_eq!(err.code(), "U1001");
+    }
+
+    #[test]
+    fn expression_evaluate_with_options() {
+        let expression = Expression::compile(
+            r#"$map($words, function($w) { [$match($w, "[0-9]"), $match($w, "[a-z]")] })"#,
+        )
+        .unwrap();
+
+        let arena = Bump::new();
+        let bindings = Bindings::new(&arena).bind("words", serde_json::json!(["a1", "b2", "c3"]));
+        let (result, stats) = expression.evaluate_with_options(
+            &arena,
+            Value::undefined(),
+            Some(&bindings),
+            &EvaluationOptions::default(),
+        );
+        assert_eq!(result.unwrap().members().count(), 3);
+        assert_eq!(
+            stats,
+            EvaluationStats {
+                regex_cache_hits: 4,
+                regex_cache_misses: 2,
+            }
+        );
+
+        let options = EvaluationOptions {
+            regex_budget: RegexBudget {
+                max_steps: Some(10),
+                max_input_length: None,
+            },
+            ..Default::default()
+        };
+        let (result, _) =
+            expression.evaluate_with_options(&arena, Value::undefined(), Some(&bindings), &options);
+        assert_eq!(result.unwrap_err().code(), "U1002");
+    }
+
     #[test]
     fn register_function_override_now() {
         let arena = Bump::new();
@@ -224,7 +551,7 @@ mod tests {
             Ok(Value::number(ctx.arena, (num.as_f64()).sqrt()))
         });
 
-        let result = jsonata.evaluate(Some(r#"anything"#), None);
+        let result = jsonata.evaluate(Some(r#""anything""#), None);
 
         assert_eq!(
             result
@@ -245,7 +572,7 @@ mod tests {
             Ok(Value::bool((num.as_f64()) % 2.0 == 0.0))
         });
 
-        let result = jsonata.evaluate(Some(r#"anything"#), None);
+        let result = jsonata.evaluate(Some(r#""anything""#), None);
 
         assert_eq!(
             result
@@ -257,6 +584,144 @@ mod tests {
         );
     }
 
+    #[test]
+    fn register_function_with_signature_context() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new("[1, 2, 3].$double()", &arena).unwrap();
+        jsonata
+            .register_function_with_signature("double", "<n-:n>", |ctx, args| {
+                Ok(Value::number(ctx.arena, args[0].as_f64() * 2.0))
+            })
+            .unwrap();
+
+        let result = jsonata.evaluate(None, None);
+
+        assert_eq!(
+            result
+                .unwrap()
+                .members()
+                .map(|v| v.as_f64())
+                .collect::<Vec<f64>>(),
+            vec![2.0, 4.0, 6.0]
+        );
+    }
+
+    #[test]
+    fn register_function_with_signature_wraps_arrays() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new("$len($x)", &arena).unwrap();
+        jsonata.assign_var("x", Value::number(&arena, 1));
+        jsonata
+            .register_function_with_signature("len", "<a<n>:n>", |ctx, args| {
+                Ok(Value::number(ctx.arena, args[0].len() as f64))
+            })
+            .unwrap();
+
+        let result = jsonata.evaluate(None, None);
+
+        assert_eq!(result.unwrap().as_f64(), 1.0);
+    }
+
+    #[test]
+    fn register_function_with_signature_mismatch() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new(r#"$add(1, "2")"#, &arena).unwrap();
+        jsonata
+            .register_function_with_signature("add", "<nn:n>", |ctx, args| {
+                Ok(Value::number(
+                    ctx.arena,
+                    args[0].as_f64() + args[1].as_f64(),
+                ))
+            })
+            .unwrap();
+
+        let result = jsonata.evaluate(None, None);
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "T0410 @ 0: Argument 2 of function add does not match function signature"
+        );
+    }
+
+    #[test]
+    fn register_function_with_signature_as_callback() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new("$map(['a', 'b'], $repeat)", &arena).unwrap();
+        jsonata
+            .register_function_with_signature("repeat", "<s-n?:s>", |ctx, args| {
+                let times = match args.get(1) {
+                    Some(n) if n.is_number() => n.as_usize(),
+                    _ => 2,
+                };
+                Ok(Value::string(ctx.arena, &args[0].as_str().repeat(times)))
+            })
+            .unwrap();
+
+        let result = jsonata.evaluate(None, None).unwrap();
+
+        assert_eq!(result.serialize(false), r#"["aa","bb"]"#);
+    }
+
+    #[test]
+    fn register_function_with_invalid_signature() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new("$f()", &arena).unwrap();
+
+        let result = jsonata
+            .register_function_with_signature("f", "<n<n>>", |_ctx, _args| Ok(Value::undefined()));
+
+        assert_eq!(result.unwrap_err().code(), "S0401");
+    }
+
+    #[test]
+    fn expression_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Expression>();
+    }
+
+    #[test]
+    fn expression_shared_across_threads() {
+        let expression = Expression::compile("$sum(items.(price * $rate))").unwrap();
+        let rate = serde_json::json!(2);
+
+        std::thread::scope(|scope| {
+            let handles = (0..4)
+                .map(|i| {
+                    let expression = &expression;
+                    let rate = &rate;
+                    scope.spawn(move || {
+                        let arena = Bump::new();
+                        let input = format!(r#"{{"items": [{{"price": {i}}}, {{"price": 1}}]}}"#);
+                        let bindings = Bindings::new(&arena).bind("rate", rate);
+
+                        let result = expression
+                            .evaluate(&arena, Some(&input), Some(&bindings))
+                            .unwrap();
+
+                        result.as_f64()
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let results = handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![2.0, 4.0, 6.0, 8.0]);
+        });
+    }
+
+    #[test]
+    fn expression_evaluate_timeboxed() {
+        let expression =
+            Expression::compile("($f := function($n) { $f($n + 1) + 1 }; $f(0))").unwrap();
+        let arena = Bump::new();
+
+        let result = expression.evaluate_timeboxed(&arena, None, Some(50), None);
+
+        assert_eq!(result.unwrap_err().code(), "U1001");
+    }
+
     #[test]
     fn evaluate_with_bindings_simple() {
         let arena = Bump::new();
@@ -300,6 +765,237 @@ mod tests {
         assert_eq!(result.unwrap().as_f64(), 3.0);
     }
 
+    #[test]
+    fn evaluate_with_bindings_builder() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new("[$n, $s, $b, $j.k, $v]", &arena).unwrap();
+
+        let bindings = Bindings::new(&arena)
+            .bind("n", 1)
+            .bind("s", "two")
+            .bind("b", true)
+            .bind("j", serde_json::json!({"k": 3.5}))
+            .bind("v", Value::number(&arena, 4))
+            .bind("n", 1.5);
+        let result = jsonata.evaluate_with_bindings(None, &bindings).unwrap();
+
+        assert_eq!(result.serialize(false), r#"[1.5,"two",true,3.5,4]"#);
+    }
+
+    #[test]
+    fn evaluate_bindings_are_not_kept() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new("$exists($a)", &arena).unwrap();
+
+        let a = serde_json::json!(1);
+        let bindings = HashMap::from([("a", &a)]);
+        assert!(jsonata.evaluate(None, Some(&bindings)).unwrap().as_bool());
+        assert!(!jsonata.evaluate(None, None).unwrap().as_bool());
+
+        let bindings = Bindings::new(&arena).bind("a", 1);
+        assert!(jsonata
+            .evaluate_with_bindings(None, &bindings)
+            .unwrap()
+            .as_bool());
+        assert!(!jsonata.evaluate(None, None).unwrap().as_bool());
+    }
+
+    #[test]
+    fn evaluate_assignments_are_not_kept() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new("$x := $exists($x)", &arena).unwrap();
+
+        assert!(!jsonata.evaluate(None, None).unwrap().as_bool());
+        assert!(!jsonata.evaluate(None, None).unwrap().as_bool());
+    }
+
+    #[test]
+    fn evaluate_value_shared_between_evaluations() {
+        let arena = Bump::new();
+        let input = Value::from_json(
+            &arena,
+            &serde_json::json!({"order": {"items": [{"price": 2}, {"price": 3}]}}),
+        );
+
+        let total = JsonAta::new("$sum(order.items.price)", &arena).unwrap();
+        let discounted = JsonAta::new("order ~> |items|{'price': price / 2}|", &arena).unwrap();
+
+        assert_eq!(total.evaluate_value(input, None).unwrap().as_f64(), 5.0);
+        assert_eq!(
+            discounted
+                .evaluate_value(input, None)
+                .unwrap()
+                .serialize(false),
+            r#"{"items":[{"price":1},{"price":1.5}]}"#
+        );
+        assert_eq!(total.evaluate_value(input, None).unwrap().as_f64(), 5.0);
+        assert_eq!(
+            input.serialize(false),
+            r#"{"order":{"items":[{"price":2},{"price":3}]}}"#
+        );
+    }
+
+    #[test_case("$"; "context")]
+    #[test_case("$[0]"; "index")]
+    #[test_case("$count($)"; "count")]
+    #[test_case("a"; "path")]
+    fn evaluate_value_matches_parsed_input(expr: &str) {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new(expr, &arena).unwrap();
+        let json = r#"[{"a": 1}, {"a": 2}]"#;
+
+        let parsed = jsonata.evaluate(Some(json), None).unwrap();
+        let input = Value::from_json(&arena, &serde_json::from_str(json).unwrap());
+        let value = jsonata.evaluate_value(input, None).unwrap();
+
+        assert_eq!(value.serialize(false), parsed.serialize(false));
+    }
+
+    #[test]
+    fn from_json_keeps_key_order() {
+        let arena = Bump::new();
+        let json = serde_json::json!({"z": 1, "a": {"y": 2, "b": 3}});
+
+        let input = Value::from_json(&arena, &json);
+        let result = JsonAta::new("$keys($)", &arena)
+            .unwrap()
+            .evaluate_value(input, None)
+            .unwrap();
+        assert_eq!(result.serialize(false), r#"["z","a"]"#);
+
+        let jsonata = JsonAta::new("$json", &arena).unwrap();
+        let bindings = HashMap::from([("json", &json)]);
+        let result = jsonata.evaluate(None, Some(&bindings)).unwrap();
+        assert_eq!(result.serialize(false), r#"{"z":1,"a":{"y":2,"b":3}}"#);
+
+        let bindings = Bindings::new(&arena).bind("json", &json);
+        let result = jsonata
+            .evaluate_value(Value::undefined(), Some(&bindings))
+            .unwrap();
+        assert_eq!(result.serialize(false), r#"{"z":1,"a":{"y":2,"b":3}}"#);
+    }
+
+    #[test]
+    fn evaluate_value_with_bindings() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new("$prefix & name", &arena).unwrap();
+        let input = Value::from_json(&arena, &serde_json::json!({"name": "world"}));
+
+        let bindings = Bindings::new(&arena).bind("prefix", "Hello, ");
+        let result = jsonata.evaluate_value(input, Some(&bindings)).unwrap();
+
+        assert_eq!(result.as_str(), "Hello, world");
+    }
+
+    #[test]
+    fn expression_evaluate_value() {
+        let arena = Bump::new();
+        let input = Value::from_json(&arena, &serde_json::json!({"a": [1, 2, 3]}));
+
+        let sum = Expression::compile("$sum(a)").unwrap();
+        let count = Expression::compile("$count(a)").unwrap();
+
+        assert_eq!(
+            sum.evaluate_value(&arena, input, None).unwrap().as_f64(),
+            6.0
+        );
+        assert_eq!(
+            count
+                .evaluate_value_timeboxed(&arena, input, Some(50), Some(1000))
+                .unwrap()
+                .as_f64(),
+            3.0
+        );
+    }
+
+    #[derive(serde::Serialize)]
+    enum Status {
+        Open,
+        Closed { reason: String },
+        Moved(u32, u32),
+        Renamed(String),
+    }
+
+    #[derive(serde::Serialize)]
+    struct Ticket {
+        id: u64,
+        title: &'static str,
+        assignee: Option<&'static str>,
+        tags: Vec<&'static str>,
+        status: Vec<Status>,
+        scores: std::collections::BTreeMap<u32, f64>,
+        flagged: bool,
+        unit: (),
+    }
+
+    #[test]
+    fn value_from_serialize() {
+        let arena = Bump::new();
+        let ticket = Ticket {
+            id: 7,
+            title: "Broken",
+            assignee: None,
+            tags: vec!["bug", "ui"],
+            status: vec![
+                Status::Open,
+                Status::Closed {
+                    reason: "fixed".to_string(),
+                },
+                Status::Moved(1, 2),
+                Status::Renamed("Fixed".to_string()),
+            ],
+            scores: std::collections::BTreeMap::from([(1, 0.5), (2, f64::NAN)]),
+            flagged: true,
+            unit: (),
+        };
+
+        let value = Value::from_serialize(&arena, &ticket).unwrap();
+
+        assert_eq!(
+            value.serialize(false),
+            concat!(
+                r#"{"id":7,"title":"Broken","assignee":null,"tags":["bug","ui"],"#,
+                r#""status":["Open",{"Closed":{"reason":"fixed"}},{"Moved":[1,2]},{"Renamed":"Fixed"}],"#,
+                r#""scores":{"1":0.5,"2":null},"flagged":true,"unit":null}"#
+            )
+        );
+        assert_eq!(
+            *value,
+            *Value::from_json(&arena, &serde_json::to_value(&ticket).unwrap())
+        );
+    }
+
+    #[test]
+    fn value_from_serialize_non_string_key() {
+        let arena = Bump::new();
+        let map = HashMap::from([((1, 2), "point")]);
+
+        let result = Value::from_serialize(&arena, &map);
+
+        assert_eq!(result.unwrap_err().code(), "I0101");
+    }
+
+    #[test]
+    fn evaluate_rejects_invalid_json_input() {
+        let arena = Bump::new();
+        let jsonata = JsonAta::new("a", &arena).unwrap();
+
+        let error = jsonata.evaluate(Some(r#"{"a": 1+1}"#), None).unwrap_err();
+
+        assert_eq!(
+            error,
+            Error::I0201UnexpectedCharacter(
+                InputPosition {
+                    offset: 7,
+                    line: 1,
+                    column: 8
+                },
+                "',' or '}'".to_string(),
+                '+'
+            )
+        );
+    }
+
     #[test]
     fn evaluate_with_random() {
         let arena = Bump::new();
@@ -393,7 +1089,7 @@ mod tests {
             // We expect a T0410ArgumentNotValid error
             assert_eq!(
                 err.to_string(),
-                "T0410 @ 2: Argument 1 of function now does not match function signature"
+                "T0410 @ 0: Argument 2 of function now does not match function signature"
             );
         }
     }
@@ -418,8 +1114,8 @@ mod tests {
     fn test_now_with_too_many_arguments() {
         let arena = Bump::new();
 
-        // Call $now() with more than two arguments (this should cause an error due to max_args! constraint)
-        let jsonata = JsonAta::new("$now('', '-0500', 'extra')", &arena).unwrap();
+        // Call $now() with more than three arguments (this should cause an error due to max_args! constraint)
+        let jsonata = JsonAta::new("$now('', '-0500', 'en', 'extra')", &arena).unwrap();
         let result = jsonata.evaluate(None, None);
 
         // Ensure that an error is returned for too many arguments
@@ -504,7 +1200,7 @@ mod tests {
         let error = result.unwrap_err();
         assert_eq!(
             error.to_string(),
-            "T0410 @ 2: Argument 1 of function now does not match function signature"
+            "T0410 @ 0: Argument 2 of function now does not match function signature"
         );
     }
 
@@ -521,7 +1217,7 @@ mod tests {
         if let Err(err) = result {
             assert_eq!(
                 err.to_string(),
-                "T0410 @ 2: Argument 1 of function now does not match function signature"
+                "T0410 @ 0: Argument 2 of function now does not match function signature"
             );
         }
     }
@@ -539,7 +1235,7 @@ mod tests {
         if let Err(err) = result {
             assert_eq!(
                 err.to_string(),
-                "T0410 @ 2: Argument 1 of function now does not match function signature"
+                "T0410 @ 0: Argument 2 of function now does not match function signature"
             );
         }
     }
@@ -731,12 +1427,11 @@ mod tests {
             ArrayFlags::empty(),
         ));
 
-        let mut match_obj = hashbrown::HashMap::with_capacity_in(3, &arena);
-        match_obj.insert(BumpString::from_str_in("match", &arena), match_value);
-        match_obj.insert(BumpString::from_str_in("index", &arena), index_value);
-        match_obj.insert(BumpString::from_str_in("groups", &arena), groups_array);
-
-        let expected_match: &Value = &*arena.alloc(Value::Object(match_obj));
+        let expected_match = Value::object_with_capacity(&arena, 3);
+        expected_match.insert("match", match_value);
+        expected_match.insert("index", index_value);
+        expected_match.insert("groups", groups_array);
+        let expected_match: &Value = expected_match;
 
         assert_eq!(
             result,
@@ -1,6 +1,8 @@
 // Re-export for use in evaluator.
 pub use super::expressions::RegexLiteral;
 
+use crate::evaluator::signature::Signature;
+
 // Object constructor, represented by tuples of (key, value)
 pub type Object = Vec<(Ast, Ast)>;
 
@@ -86,7 +88,9 @@ pub enum AstKind {
     Block(Vec<Ast>),
     Wildcard,
     Descendent,
-    Parent,
+
+    /// The parent operator `%`, holding the label its ancestor is bound to in the tuple stream
+    Parent(String),
     Function {
         name: String,
         proc: Box<Ast>,
@@ -98,6 +102,7 @@ pub enum AstKind {
         name: String,
         args: Vec<Ast>,
         body: Box<Ast>,
+        signature: Option<Signature>,
         thunk: bool,
     },
     Ternary {
@@ -148,6 +153,14 @@ pub struct Ast {
 
     // A variable to bind the context of a step to
     pub focus: Option<String>,
+
+    /// Labels to bind the input of a step to, for parent operators that resolve to it as their
+    /// ancestor.
+    pub ancestors: Vec<String>,
+
+    /// Indexes of the parent operators within this node that are still seeking their ancestor.
+    /// Only used while processing the AST.
+    pub seeking_parent: Vec<usize>,
 }
 
 impl Default for Ast {
@@ -170,6 +183,8 @@ impl Ast {
             tuple: false,
             index: None,
             focus: None,
+            ancestors: Vec::new(),
+            seeking_parent: Vec::new(),
         }
     }
 }
@@ -0,0 +1,1085 @@
+//! A backtracking matcher for regular expressions which counts the steps it takes, so that a
+//! search can be stopped part of the way through.
+//!
+//! `regress` can't be interrupted mid-search, so searches which have to stay within a budget run
+//! here instead. Patterns have already been validated by `regress`, and only their structure is
+//! taken apart here: each atom which matches a single character, such as a class, an escape or a
+//! case-insensitive character, is still tested by `regress`, so atoms behave exactly as they do
+//! there.
+
+use regress::Regex;
+use std::ops::Range;
+
+use super::expressions::RegexMatch;
+
+/// The number of steps taken between the reports to the caller of a search.
+const STEP_CHUNK: usize = 1024;
+
+/// A pattern compiled into instructions for the backtracking matcher.
+#[derive(Debug, Clone)]
+pub struct Program {
+    insns: Vec<Insn>,
+    atoms: Vec<Atom>,
+    /// The atom `\w`, used by word boundary assertions.
+    word: Option<Atom>,
+    /// The names of the capture groups, with `None` for unnamed groups.
+    group_names: Vec<Option<String>>,
+    loops: usize,
+    case_insensitive: bool,
+    multi_line: bool,
+}
+
+impl Program {
+    /// Compile a pattern which has already been accepted by `regress` with the same flags.
+    pub fn compile(
+        pattern: &str,
+        case_insensitive: bool,
+        multi_line: bool,
+    ) -> Result<Program, String> {
+        let mut parser = PatternParser {
+            pattern,
+            position: 0,
+            case_insensitive,
+            atoms: Vec::new(),
+            word: None,
+            group_names: Vec::new(),
+        };
+        let node = parser.parse_alternation()?;
+        if parser.position < pattern.len() {
+            return Err(format!("Unexpected ')' at offset {}", parser.position));
+        }
+
+        let mut compiler = Compiler {
+            insns: Vec::new(),
+            loops: 0,
+            group_names: &parser.group_names,
+        };
+        compiler.compile(&node, false)?;
+        compiler.insns.push(Insn::Succeed);
+
+        Ok(Program {
+            insns: compiler.insns,
+            loops: compiler.loops,
+            atoms: parser.atoms,
+            word: parser.word,
+            group_names: parser.group_names,
+            case_insensitive,
+            multi_line,
+        })
+    }
+
+    /// Find up to `limit` matches in `text`, reporting the number of steps taken to `charge` as
+    /// the search goes. The search stops at the first error returned by `charge`.
+    pub fn find_charged<E>(
+        &self,
+        text: &str,
+        limit: usize,
+        charge: &mut dyn FnMut(usize) -> Result<(), E>,
+    ) -> Result<Vec<RegexMatch>, E> {
+        let mut vm = Vm {
+            program: self,
+            text,
+            slots: vec![None; self.group_names.len() * 2],
+            loops: vec![(0, 0); self.loops],
+            stack: Vec::new(),
+            steps: 0,
+            charge,
+        };
+
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while matches.len() < limit {
+            let Some(found) = vm.search(start)? else {
+                break;
+            };
+
+            // An empty match has to be followed by a search from the next character
+            start = if found.range.is_empty() {
+                match text[found.range.end..].chars().next() {
+                    Some(c) => found.range.end + c.len_utf8(),
+                    None => text.len() + 1,
+                }
+            } else {
+                found.range.end
+            };
+            matches.push(found);
+
+            if start > text.len() {
+                break;
+            }
+        }
+
+        (vm.charge)(vm.steps)?;
+        Ok(matches)
+    }
+}
+
+/// An atom which matches a single character, tested by `regress`.
+#[derive(Debug, Clone)]
+struct Atom {
+    regex: Regex,
+    /// Which ASCII characters the atom matches, so that `regress` is only needed for the others.
+    ascii: u128,
+}
+
+impl Atom {
+    fn new(source: &str, case_insensitive: bool) -> Result<Atom, String> {
+        let flags = if case_insensitive { "i" } else { "" };
+        let regex = Regex::with_flags(&format!("^(?:{})$", source), flags)
+            .map_err(|err| err.to_string())?;
+
+        let mut ascii = 0;
+        let mut buffer = [0; 4];
+        for c in 0..128u8 {
+            if regex.find((c as char).encode_utf8(&mut buffer)).is_some() {
+                ascii |= 1 << c;
+            }
+        }
+
+        Ok(Atom { regex, ascii })
+    }
+
+    fn matches(&self, c: char) -> bool {
+        if c.is_ascii() {
+            self.ascii & (1 << c as u32) != 0
+        } else {
+            self.regex.find(c.encode_utf8(&mut [0; 4])).is_some()
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Empty,
+    Char(char),
+    Atom(usize),
+    LineStart,
+    LineEnd,
+    WordBoundary {
+        negated: bool,
+    },
+    BackRef(usize),
+    NamedBackRef(String),
+    Group {
+        index: Option<usize>,
+        node: Box<Node>,
+    },
+    Look {
+        behind: bool,
+        negated: bool,
+        node: Box<Node>,
+    },
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+        /// The capture groups inside the repeated node, which are reset on each iteration.
+        groups: Range<usize>,
+    },
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+}
+
+struct PatternParser<'p> {
+    pattern: &'p str,
+    position: usize,
+    case_insensitive: bool,
+    atoms: Vec<Atom>,
+    word: Option<Atom>,
+    group_names: Vec<Option<String>>,
+}
+
+impl PatternParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.pattern[self.position..].chars().next()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.pattern[self.position..].chars().nth(offset)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_alternation(&mut self) -> Result<Node, String> {
+        let mut alternatives = vec![self.parse_sequence()?];
+        while self.eat('|') {
+            alternatives.push(self.parse_sequence()?);
+        }
+
+        Ok(if alternatives.len() == 1 {
+            alternatives.remove(0)
+        } else {
+            Node::Alternation(alternatives)
+        })
+    }
+
+    fn parse_sequence(&mut self) -> Result<Node, String> {
+        let mut terms = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let groups_before = self.group_names.len();
+            let term = self.parse_term()?;
+            terms.push(self.parse_quantifier(term, groups_before));
+        }
+
+        Ok(match terms.len() {
+            0 => Node::Empty,
+            1 => terms.remove(0),
+            _ => Node::Concat(terms),
+        })
+    }
+
+    fn parse_term(&mut self) -> Result<Node, String> {
+        let start = self.position;
+        let c = self.peek().unwrap_or_default();
+        self.position += c.len_utf8();
+
+        match c {
+            '^' => Ok(Node::LineStart),
+            '$' => Ok(Node::LineEnd),
+            '(' => self.parse_group(),
+            '[' => {
+                while let Some(c) = self.peek() {
+                    self.position += c.len_utf8();
+                    match c {
+                        ']' => return self.atom(start),
+                        '\\' => self.skip_char(),
+                        _ => {}
+                    }
+                }
+                Err("Unterminated character class".to_string())
+            }
+            '\\' => self.parse_escape(start),
+            '.' => self.atom(start),
+            _ if self.case_insensitive => self.atom(start),
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    fn parse_group(&mut self) -> Result<Node, String> {
+        let node = if self.eat('?') {
+            let behind = self.eat('<');
+            match self.peek() {
+                Some(':') if !behind => {
+                    self.position += 1;
+                    Node::Group {
+                        index: None,
+                        node: Box::new(self.parse_alternation()?),
+                    }
+                }
+                Some(c @ ('=' | '!')) => {
+                    self.position += 1;
+                    Node::Look {
+                        behind,
+                        negated: c == '!',
+                        node: Box::new(self.parse_alternation()?),
+                    }
+                }
+                _ if behind => {
+                    let name = self.parse_name()?;
+                    self.capture(Some(name))?
+                }
+                _ => return Err(format!("Invalid group at offset {}", self.position)),
+            }
+        } else {
+            self.capture(None)?
+        };
+
+        if !self.eat(')') {
+            return Err("Unbalanced parenthesis".to_string());
+        }
+        Ok(node)
+    }
+
+    fn capture(&mut self, name: Option<String>) -> Result<Node, String> {
+        self.group_names.push(name);
+        let index = self.group_names.len();
+        Ok(Node::Group {
+            index: Some(index),
+            node: Box::new(self.parse_alternation()?),
+        })
+    }
+
+    /// Parse the name of a group or a named back-reference, after its `<`.
+    fn parse_name(&mut self) -> Result<String, String> {
+        let end = self.pattern[self.position..]
+            .find('>')
+            .ok_or_else(|| "Unterminated group name".to_string())?;
+        let name = self.pattern[self.position..self.position + end].to_string();
+        self.position += end + 1;
+        Ok(name)
+    }
+
+    fn parse_escape(&mut self, start: usize) -> Result<Node, String> {
+        let c = self
+            .peek()
+            .ok_or_else(|| "Pattern ends with \\".to_string())?;
+        match c {
+            'b' | 'B' => {
+                self.position += 1;
+                if self.word.is_none() {
+                    self.word = Some(Atom::new("\\w", self.case_insensitive)?);
+                }
+                Ok(Node::WordBoundary { negated: c == 'B' })
+            }
+            '1'..='9' => {
+                let digits = self.pattern[self.position..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(self.pattern.len() - self.position);
+                let index = self.pattern[self.position..self.position + digits]
+                    .parse()
+                    .map_err(|_| "Invalid back-reference".to_string())?;
+                self.position += digits;
+                Ok(Node::BackRef(index))
+            }
+            'k' if self.peek_at(1) == Some('<') => {
+                self.position += 2;
+                Ok(Node::NamedBackRef(self.parse_name()?))
+            }
+            'u' => {
+                self.position += 1;
+                if self.peek() == Some('{') {
+                    self.skip_past('}');
+                } else if let Some(high) = self.parse_hex(4) {
+                    // A surrogate pair written as two escapes is a single character
+                    let rest = &self.pattern[self.position..];
+                    if (0xD800..0xDC00).contains(&high) && rest.starts_with("\\u") {
+                        let low = rest.get(2..6).and_then(|s| u32::from_str_radix(s, 16).ok());
+                        if low.is_some_and(|low| (0xDC00..0xE000).contains(&low)) {
+                            self.position += 6;
+                        }
+                    }
+                }
+                self.atom(start)
+            }
+            'p' | 'P' => {
+                self.position += 1;
+                if self.peek() == Some('{') {
+                    self.skip_past('}');
+                }
+                self.atom(start)
+            }
+            'x' => {
+                self.position += 1;
+                self.parse_hex(2);
+                self.atom(start)
+            }
+            'c' => {
+                self.position += 1;
+                self.skip_char();
+                self.atom(start)
+            }
+            _ => {
+                self.skip_char();
+                self.atom(start)
+            }
+        }
+    }
+
+    fn parse_hex(&mut self, digits: usize) -> Option<u32> {
+        let hex = self.pattern.get(self.position..self.position + digits)?;
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        self.position += digits;
+        Some(value)
+    }
+
+    fn skip_char(&mut self) {
+        if let Some(c) = self.peek() {
+            self.position += c.len_utf8();
+        }
+    }
+
+    fn skip_past(&mut self, end: char) {
+        match self.pattern[self.position..].find(end) {
+            Some(offset) => self.position += offset + end.len_utf8(),
+            None => self.position = self.pattern.len(),
+        }
+    }
+
+    /// The atom from `start` to the current position.
+    fn atom(&mut self, start: usize) -> Result<Node, String> {
+        let atom = Atom::new(&self.pattern[start..self.position], self.case_insensitive)?;
+        self.atoms.push(atom);
+        Ok(Node::Atom(self.atoms.len() - 1))
+    }
+
+    fn parse_quantifier(&mut self, term: Node, groups_before: usize) -> Node {
+        let (min, max) = match self.peek() {
+            Some('{') => match self.parse_braces() {
+                Some(bounds) => bounds,
+                None => return term,
+            },
+            Some(c @ ('*' | '+' | '?')) => {
+                self.position += 1;
+                match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            }
+            _ => return term,
+        };
+        let greedy = !self.eat('?');
+
+        Node::Repeat {
+            node: Box::new(term),
+            min,
+            max,
+            greedy,
+            groups: groups_before..self.group_names.len(),
+        }
+    }
+
+    /// Parse a `{n}`, `{n,}` or `{n,m}` quantifier, leaving the position after it. Braces which
+    /// aren't a quantifier are left to be parsed as a term.
+    fn parse_braces(&mut self) -> Option<(usize, Option<usize>)> {
+        let rest = &self.pattern[self.position + 1..];
+        let end = rest.find('}')?;
+        let (min, max) = match rest[..end].split_once(',') {
+            Some((min, "")) => (min, None),
+            Some((min, max)) => (min, Some(max)),
+            None => (&rest[..end], Some(&rest[..end])),
+        };
+
+        // Bounds too large for a usize can't be reached anyway
+        let bound = |digits: &str| {
+            (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+                .then(|| digits.parse().unwrap_or(usize::MAX))
+        };
+        let min = bound(min)?;
+        let max = match max {
+            Some(max) => Some(bound(max)?),
+            None => None,
+        };
+
+        self.position += end + 2;
+        Some((min, max))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Insn {
+    Char {
+        c: char,
+        backward: bool,
+    },
+    Atom {
+        atom: usize,
+        backward: bool,
+    },
+    LineStart,
+    LineEnd,
+    WordBoundary {
+        negated: bool,
+    },
+    BackRef {
+        group: usize,
+        backward: bool,
+    },
+    /// Record the position in a capture slot.
+    Save(usize),
+    /// Continue at the first instruction, backtracking to the second.
+    Split(usize, usize),
+    Jump(usize),
+    LoopEnter(usize),
+    LoopHead {
+        id: usize,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+        exit: usize,
+    },
+    /// Start an iteration of a loop, which resets the capture groups in its body.
+    LoopBody {
+        id: usize,
+        groups: Range<usize>,
+    },
+    LoopTail {
+        id: usize,
+        min: usize,
+        head: usize,
+    },
+    /// A lookaround assertion, whose body follows it and ends with `Succeed`.
+    Look {
+        negated: bool,
+        next: usize,
+    },
+    Succeed,
+}
+
+struct Compiler<'n> {
+    insns: Vec<Insn>,
+    loops: usize,
+    group_names: &'n [Option<String>],
+}
+
+impl Compiler<'_> {
+    fn emit(&mut self, insn: Insn) -> usize {
+        self.insns.push(insn);
+        self.insns.len() - 1
+    }
+
+    /// Compile a node. Nodes inside a lookbehind are compiled `backward`, to match from right to
+    /// left as they do in JavaScript.
+    fn compile(&mut self, node: &Node, backward: bool) -> Result<(), String> {
+        match *node {
+            Node::Empty => {}
+            Node::Char(c) => {
+                self.emit(Insn::Char { c, backward });
+            }
+            Node::Atom(atom) => {
+                self.emit(Insn::Atom { atom, backward });
+            }
+            Node::LineStart => {
+                self.emit(Insn::LineStart);
+            }
+            Node::LineEnd => {
+                self.emit(Insn::LineEnd);
+            }
+            Node::WordBoundary { negated } => {
+                self.emit(Insn::WordBoundary { negated });
+            }
+            Node::BackRef(index) => {
+                if index == 0 || index > self.group_names.len() {
+                    return Err(format!("Invalid back-reference \\{}", index));
+                }
+                self.emit(Insn::BackRef {
+                    group: index - 1,
+                    backward,
+                });
+            }
+            Node::NamedBackRef(ref name) => {
+                let group = self
+                    .group_names
+                    .iter()
+                    .position(|group| group.as_deref() == Some(name))
+                    .ok_or_else(|| format!("Invalid named back-reference {}", name))?;
+                self.emit(Insn::BackRef { group, backward });
+            }
+            Node::Group { index, ref node } => match index {
+                Some(index) => {
+                    let (first, second) = ((index - 1) * 2, (index - 1) * 2 + 1);
+                    let (first, second) = if backward {
+                        (second, first)
+                    } else {
+                        (first, second)
+                    };
+                    self.emit(Insn::Save(first));
+                    self.compile(node, backward)?;
+                    self.emit(Insn::Save(second));
+                }
+                None => self.compile(node, backward)?,
+            },
+            Node::Look {
+                behind,
+                negated,
+                ref node,
+            } => {
+                let look = self.emit(Insn::Look { negated, next: 0 });
+                self.compile(node, behind)?;
+                self.emit(Insn::Succeed);
+                self.insns[look] = Insn::Look {
+                    negated,
+                    next: self.insns.len(),
+                };
+            }
+            Node::Repeat {
+                ref node,
+                min,
+                max,
+                greedy,
+                ref groups,
+            } => {
+                let id = self.loops;
+                self.loops += 1;
+
+                self.emit(Insn::LoopEnter(id));
+                let head = self.emit(Insn::Jump(0));
+                self.emit(Insn::LoopBody {
+                    id,
+                    groups: groups.clone(),
+                });
+                self.compile(node, backward)?;
+                self.emit(Insn::LoopTail { id, min, head });
+                self.insns[head] = Insn::LoopHead {
+                    id,
+                    min,
+                    max,
+                    greedy,
+                    exit: self.insns.len(),
+                };
+            }
+            Node::Concat(ref nodes) => {
+                if backward {
+                    for node in nodes.iter().rev() {
+                        self.compile(node, backward)?;
+                    }
+                } else {
+                    for node in nodes {
+                        self.compile(node, backward)?;
+                    }
+                }
+            }
+            Node::Alternation(ref alternatives) => {
+                let mut jumps = Vec::new();
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    let split = (i + 1 < alternatives.len()).then(|| self.emit(Insn::Jump(0)));
+                    self.compile(alternative, backward)?;
+                    if let Some(split) = split {
+                        jumps.push(self.emit(Insn::Jump(0)));
+                        self.insns[split] = Insn::Split(split + 1, self.insns.len());
+                    }
+                }
+                let end = self.insns.len();
+                for jump in jumps {
+                    self.insns[jump] = Insn::Jump(end);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The state restored when backtracking.
+enum Backtrack {
+    /// Resume matching at an instruction and position.
+    Resume {
+        ip: usize,
+        position: usize,
+    },
+    Slot {
+        slot: usize,
+        value: Option<usize>,
+    },
+    Loop {
+        id: usize,
+        state: (usize, usize),
+    },
+}
+
+struct Vm<'p, 't, 'c, E> {
+    program: &'p Program,
+    text: &'t str,
+    /// The start and end of each capture group.
+    slots: Vec<Option<usize>>,
+    /// The number of iterations of each loop, and the position its current iteration started at.
+    loops: Vec<(usize, usize)>,
+    stack: Vec<Backtrack>,
+    /// The steps taken since the last report to `charge`.
+    steps: usize,
+    charge: &'c mut dyn FnMut(usize) -> Result<(), E>,
+}
+
+impl<E> Vm<'_, '_, '_, E> {
+    /// Find the first match starting at or after `start`.
+    fn search(&mut self, mut start: usize) -> Result<Option<RegexMatch>, E> {
+        loop {
+            self.slots.iter_mut().for_each(|slot| *slot = None);
+            self.stack.clear();
+
+            if let Some(end) = self.run(0, start)? {
+                let captures = self
+                    .slots
+                    .chunks(2)
+                    .map(|slots| match *slots {
+                        [Some(start), Some(end)] => Some(start..end),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                let named_groups = self
+                    .program
+                    .group_names
+                    .iter()
+                    .zip(&captures)
+                    .filter_map(|(name, range)| Some((name.clone()?, range.clone())))
+                    .collect();
+
+                return Ok(Some(RegexMatch {
+                    range: start..end,
+                    captures,
+                    named_groups,
+                }));
+            }
+
+            match self.text[start..].chars().next() {
+                Some(c) => start += c.len_utf8(),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn step(&mut self) -> Result<(), E> {
+        self.steps += 1;
+        if self.steps == STEP_CHUNK {
+            self.steps = 0;
+            (self.charge)(STEP_CHUNK)?;
+        }
+        Ok(())
+    }
+
+    fn set_slot(&mut self, slot: usize, value: Option<usize>) {
+        self.stack.push(Backtrack::Slot {
+            slot,
+            value: self.slots[slot],
+        });
+        self.slots[slot] = value;
+    }
+
+    fn set_loop(&mut self, id: usize, state: (usize, usize)) {
+        self.stack.push(Backtrack::Loop {
+            id,
+            state: self.loops[id],
+        });
+        self.loops[id] = state;
+    }
+
+    /// Run the instructions from `ip` at `position` until `Succeed`, returning the position it
+    /// was reached at, or `None` if there's no match. Backtracking never goes below the stack as
+    /// it was when this was called.
+    fn run(&mut self, mut ip: usize, mut position: usize) -> Result<Option<usize>, E> {
+        let base = self.stack.len();
+
+        loop {
+            self.step()?;
+
+            let matched = match self.program.insns[ip] {
+                Insn::Char { c, backward } => match self.next_char(position, backward) {
+                    Some((next, found)) if found == c => {
+                        position = next;
+                        ip += 1;
+                        true
+                    }
+                    _ => false,
+                },
+                Insn::Atom { atom, backward } => match self.next_char(position, backward) {
+                    Some((next, found)) if self.program.atoms[atom].matches(found) => {
+                        position = next;
+                        ip += 1;
+                        true
+                    }
+                    _ => false,
+                },
+                Insn::LineStart => {
+                    ip += 1;
+                    position == 0
+                        || (self.program.multi_line
+                            && self.text[..position]
+                                .chars()
+                                .next_back()
+                                .is_some_and(is_line_terminator))
+                }
+                Insn::LineEnd => {
+                    ip += 1;
+                    position == self.text.len()
+                        || (self.program.multi_line
+                            && self.text[position..]
+                                .chars()
+                                .next()
+                                .is_some_and(is_line_terminator))
+                }
+                Insn::WordBoundary { negated } => {
+                    ip += 1;
+                    let is_word = |c: Option<char>| match (c, &self.program.word) {
+                        (Some(c), Some(word)) => word.matches(c),
+                        _ => false,
+                    };
+                    let before = is_word(self.text[..position].chars().next_back());
+                    let after = is_word(self.text[position..].chars().next());
+                    (before != after) != negated
+                }
+                Insn::BackRef { group, backward } => {
+                    ip += 1;
+                    match (self.slots[group * 2], self.slots[group * 2 + 1]) {
+                        (Some(start), Some(end)) => {
+                            match self.back_ref(start..end, position, backward) {
+                                Some(next) => {
+                                    position = next;
+                                    true
+                                }
+                                None => false,
+                            }
+                        }
+                        // A group which hasn't matched matches the empty string
+                        _ => true,
+                    }
+                }
+                Insn::Save(slot) => {
+                    self.set_slot(slot, Some(position));
+                    ip += 1;
+                    true
+                }
+                Insn::Split(first, second) => {
+                    self.stack.push(Backtrack::Resume {
+                        ip: second,
+                        position,
+                    });
+                    ip = first;
+                    true
+                }
+                Insn::Jump(target) => {
+                    ip = target;
+                    true
+                }
+                Insn::LoopEnter(id) => {
+                    self.set_loop(id, (0, position));
+                    ip += 1;
+                    true
+                }
+                Insn::LoopHead {
+                    id,
+                    min,
+                    max,
+                    greedy,
+                    exit,
+                } => {
+                    let iterations = self.loops[id].0;
+                    let can_enter = max.is_none_or(|max| iterations < max);
+                    let can_exit = iterations >= min;
+                    match (can_enter, can_exit) {
+                        (true, true) => {
+                            let (preferred, other) = if greedy {
+                                (ip + 1, exit)
+                            } else {
+                                (exit, ip + 1)
+                            };
+                            self.stack.push(Backtrack::Resume {
+                                ip: other,
+                                position,
+                            });
+                            ip = preferred;
+                            true
+                        }
+                        (true, false) => {
+                            ip += 1;
+                            true
+                        }
+                        (false, true) => {
+                            ip = exit;
+                            true
+                        }
+                        (false, false) => false,
+                    }
+                }
+                Insn::LoopBody { id, ref groups } => {
+                    let iterations = self.loops[id].0;
+                    self.set_loop(id, (iterations, position));
+                    for slot in groups.start * 2..groups.end * 2 {
+                        if self.slots[slot].is_some() {
+                            self.set_slot(slot, None);
+                        }
+                    }
+                    ip += 1;
+                    true
+                }
+                Insn::LoopTail { id, min, head } => {
+                    let (iterations, entry) = self.loops[id];
+                    // An iteration beyond the minimum which matches the empty string fails
+                    if iterations + 1 > min && entry == position {
+                        false
+                    } else {
+                        self.set_loop(id, (iterations + 1, entry));
+                        ip = head;
+                        true
+                    }
+                }
+                Insn::Look { negated, next } => {
+                    let look_base = self.stack.len();
+                    let matched = self.run(ip + 1, position)?.is_some();
+                    if matched && !negated {
+                        // Captures made inside the assertion are kept, but it can't be
+                        // backtracked into
+                        let kept = self
+                            .stack
+                            .drain(look_base..)
+                            .filter(|entry| !matches!(entry, Backtrack::Resume { .. }))
+                            .collect::<Vec<_>>();
+                        self.stack.extend(kept);
+                    } else if matched {
+                        self.unwind(look_base);
+                    }
+                    ip = next;
+                    matched != negated
+                }
+                Insn::Succeed => return Ok(Some(position)),
+            };
+
+            if !matched {
+                match self.backtrack(base) {
+                    Some((resume_ip, resume_position)) => {
+                        ip = resume_ip;
+                        position = resume_position;
+                    }
+                    None => return Ok(None),
+                }
+            }
+        }
+    }
+
+    /// Restore the state until the most recent point to resume from above `base`.
+    fn backtrack(&mut self, base: usize) -> Option<(usize, usize)> {
+        while self.stack.len() > base {
+            match self.stack.pop()? {
+                Backtrack::Resume { ip, position } => return Some((ip, position)),
+                Backtrack::Slot { slot, value } => self.slots[slot] = value,
+                Backtrack::Loop { id, state } => self.loops[id] = state,
+            }
+        }
+        None
+    }
+
+    /// Restore all the state above `base`.
+    fn unwind(&mut self, base: usize) {
+        while self.backtrack(base).is_some() {}
+    }
+
+    /// The position after the character at `position`, or before it if matching `backward`, and
+    /// the character.
+    fn next_char(&self, position: usize, backward: bool) -> Option<(usize, char)> {
+        if backward {
+            let c = self.text[..position].chars().next_back()?;
+            Some((position - c.len_utf8(), c))
+        } else {
+            let c = self.text[position..].chars().next()?;
+            Some((position + c.len_utf8(), c))
+        }
+    }
+
+    /// Match the text captured in `captured` at `position`, returning the position after it.
+    fn back_ref(&self, captured: Range<usize>, position: usize, backward: bool) -> Option<usize> {
+        let captured = &self.text[captured];
+        if !self.program.case_insensitive {
+            return if backward {
+                self.text[..position]
+                    .ends_with(captured)
+                    .then(|| position - captured.len())
+            } else {
+                self.text[position..]
+                    .starts_with(captured)
+                    .then(|| position + captured.len())
+            };
+        }
+
+        let mut position = position;
+        let mut compare = |expected: char| {
+            let (next, found) = self.next_char(position, backward)?;
+            position = next;
+            (fold_case(found) == fold_case(expected)).then_some(())
+        };
+        if backward {
+            captured.chars().rev().try_for_each(&mut compare)?;
+        } else {
+            captured.chars().try_for_each(&mut compare)?;
+        }
+        Some(position)
+    }
+}
+
+fn is_line_terminator(c: char) -> bool {
+    matches!(c, '\n' | '\r' | '\u{2028}' | '\u{2029}')
+}
+
+/// The character `c` is compared as by a case-insensitive back-reference.
+fn fold_case(c: char) -> char {
+    fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+
+    let upper = single(c.to_uppercase()).unwrap_or(c);
+    single(upper.to_lowercase()).unwrap_or(upper)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use test_case::test_case;
+
+    fn find(pattern: &str, flags: &str, text: &str) -> Vec<RegexMatch> {
+        let program = Program::compile(pattern, flags.contains('i'), flags.contains('m')).unwrap();
+        program
+            .find_charged(text, usize::MAX, &mut |_| Ok::<(), Infallible>(()))
+            .unwrap()
+    }
+
+    #[test_case("a|ab|abc", "", "xabcabx")]
+    #[test_case("(a|ab)(c|bcd)(d*)", "", "abcd")]
+    #[test_case("a*?b+?c{2,3}", "", "aabbccccbcc")]
+    #[test_case("x*", "", "axxb€x")]
+    #[test_case("(a)|b", "", "ab")]
+    #[test_case("(z)((a+)?(b+)?(c))*", "", "zaacbbbcac")]
+    #[test_case("(a*)*b", "", "aaab")]
+    #[test_case("(a*)+?", "", "aa")]
+    #[test_case("(?:a|b)*?c", "", "ababc")]
+    #[test_case("^\\w+$|^$", "m", "one\ntwo three\n\nfour")]
+    #[test_case("\\bfoo\\B.", "", "foo foox foo.")]
+    #[test_case("(\\w)\\1", "", "hello moon")]
+    #[test_case("(?<first>\\w)(?<second>\\w)\\k<second>\\k<first>", "", "xabba")]
+    #[test_case("(a)?\\1b", "", "ab b")]
+    #[test_case("q(?=u(i))", "", "quit qu")]
+    #[test_case("q(?!u)", "", "quit qat")]
+    #[test_case("(?<=\\$)\\d+(\\.\\d+)?", "", "cost $10.50 or 20")]
+    #[test_case("(?<=(\\d+)(\\d+))$", "", "1053")]
+    #[test_case("(?<!\\d)\\d{2}(?!\\d)", "", "1 22 333 44")]
+    #[test_case("[a-c\\d]+|[^\\s]", "", "ab1 Z\tc")]
+    #[test_case("straße", "i", "STRASSE Straße STRAẞE" ; "case folding")]
+    #[test_case("[k-m]+", "i", "KLM\u{212A}x")]
+    #[test_case("(é)\\1", "i", "éÉ" ; "case insensitive back reference")]
+    #[test_case("\\u{1F600}|\\uD83D\\uDE01|\\x41|\\cJ", "", "A\u{1F600}\n\u{1F601}")]
+    #[test_case("\\p{Lu}\\P{Lu}", "", "aBcDE")]
+    #[test_case(".+", "", "a\r\nb\u{2028}c")]
+    #[test_case("a{2}|b{1,}|c{,2}", "", "aaabbc{,2}")]
+    #[test_case("", "", "ab")]
+    fn matches_regress(pattern: &str, flags: &str, text: &str) {
+        let regex = Regex::with_flags(pattern, flags).unwrap();
+        let expected = regex
+            .find_iter(text)
+            .map(|m| (m.range(), m.captures.clone()))
+            .collect::<Vec<_>>();
+        let found = find(pattern, flags, text)
+            .into_iter()
+            .map(|m| (m.range, m.captures))
+            .collect::<Vec<_>>();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn named_groups() {
+        let found = find("(?<year>\\d+)-(\\d+)", "", "2018-03");
+        assert_eq!(
+            found[0].named_groups,
+            vec![("year".to_string(), Some(0..4))]
+        );
+    }
+
+    #[test]
+    fn stops_a_pathological_search() {
+        let program = Program::compile("^(a|a)*$", false, false).unwrap();
+        let mut charged = 0;
+        let result = program.find_charged(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa!",
+            usize::MAX,
+            &mut |steps| {
+                charged += steps;
+                if charged > 10_000 {
+                    Err(charged)
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        assert_eq!(result.unwrap_err(), 10_240);
+    }
+}
@@ -1,6 +1,9 @@
 use regress::Regex;
 use std::hash::{Hash, Hasher};
-use std::ops::Deref;
+use std::ops::{Deref, Range};
+use std::sync::OnceLock;
+
+use super::backtrack::Program;
 
 pub fn check_balanced_brackets(expr: &str) -> Result<(), String> {
     let mut bracket_count = 0;
@@ -46,6 +49,21 @@ pub fn check_balanced_brackets(expr: &str) -> Result<(), String> {
 pub struct RegexLiteral {
     regex: Regex,
     pattern: String, // Store the original pattern string for comparisons
+    case_insensitive: bool,
+    multi_line: bool,
+    /// The pattern compiled for the backtracking matcher, which is only needed by searches with
+    /// a budget.
+    program: OnceLock<Result<Program, String>>,
+}
+
+/// A match of a [`RegexLiteral`], with byte ranges into the text searched.
+#[derive(Debug, Clone)]
+pub struct RegexMatch {
+    pub range: Range<usize>,
+    /// The capture groups, with `None` for groups which did not participate in the match.
+    pub captures: Vec<Option<Range<usize>>>,
+    /// The named capture groups, in the order they appear in the pattern.
+    pub named_groups: Vec<(String, Option<Range<usize>>)>,
 }
 
 impl RegexLiteral {
@@ -67,6 +85,9 @@ impl RegexLiteral {
         Ok(Self {
             regex,
             pattern: pattern.to_string(),
+            case_insensitive,
+            multi_line,
+            program: OnceLock::new(),
         })
     }
 
@@ -84,6 +105,40 @@ impl RegexLiteral {
     pub fn get_regex(&self) -> &Regex {
         &self.regex
     }
+
+    /// Find up to `limit` matches in `text`.
+    pub fn find_all(&self, text: &str, limit: usize) -> Vec<RegexMatch> {
+        self.regex
+            .find_iter(text)
+            .take(limit)
+            .map(|m| RegexMatch {
+                named_groups: m
+                    .named_groups()
+                    .map(|(name, range)| (name.to_string(), range))
+                    .collect(),
+                range: m.range,
+                captures: m.captures,
+            })
+            .collect()
+    }
+
+    /// Find up to `limit` matches in `text` with a matcher which can be stopped part of the way
+    /// through a search. The number of steps taken is passed to `charge` as the search goes, and
+    /// the search stops at the first error it returns. Fails with a message if the pattern can't
+    /// be compiled for this matcher.
+    pub fn find_charged<E>(
+        &self,
+        text: &str,
+        limit: usize,
+        mut charge: impl FnMut(usize) -> Result<(), E>,
+    ) -> Result<Result<Vec<RegexMatch>, E>, String> {
+        let program = self
+            .program
+            .get_or_init(|| Program::compile(&self.pattern, self.case_insensitive, self.multi_line))
+            .as_ref()
+            .map_err(Clone::clone)?;
+        Ok(program.find_charged(text, limit, &mut charge))
+    }
 }
 
 impl Deref for RegexLiteral {
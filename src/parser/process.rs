@@ -5,418 +5,615 @@ use super::*;
 
 impl Ast {
     pub fn process(self) -> Result<Ast> {
-        process_ast(self)
-    }
-}
+        let ast = Processor::default().process_ast(self)?;
 
-pub fn process_ast(node: Ast) -> Result<Ast> {
-    let mut node = node;
-    let keep_array = node.keep_array;
-
-    let mut result = match node.kind {
-        AstKind::Name(..) => process_name(node)?,
-        AstKind::Block(..) => process_block(node)?,
-        AstKind::Unary(..) => process_unary(node)?,
-        AstKind::Binary(..) => process_binary(node)?,
-        AstKind::GroupBy(ref mut lhs, ref mut rhs) => process_group_by(node.char_index, lhs, rhs)?,
-        AstKind::OrderBy(ref mut lhs, ref mut rhs) => process_order_by(node.char_index, lhs, rhs)?,
-        AstKind::Function {
-            ref mut proc,
-            ref mut args,
-            ..
-        } => {
-            process_function(proc, args)?;
-            node
-        }
-        AstKind::Lambda { ref mut body, .. } => {
-            process_lambda(body)?;
-            node
+        // Any parent reference still seeking its ancestor at the top level can't be resolved
+        if !ast.seeking_parent.is_empty() {
+            return Err(Error::S0217CannotDeriveParent(ast.char_index));
         }
-        AstKind::Ternary { .. } => process_ternary(node)?,
-        AstKind::Transform { .. } => process_transform(node)?,
-        AstKind::Parent => unimplemented!("Parent not yet implemented"),
-        _ => node,
-    };
 
-    if keep_array {
-        result.keep_array = true;
+        Ok(ast)
     }
+}
 
-    Ok(result)
+/// A parent operator that is seeking its ancestor, `level` steps further up the path.
+///
+/// Slots are shared by every node that carries the parent operator upwards, so they're owned by
+/// the `Processor` and nodes refer to them by index in `seeking_parent`.
+struct Slot {
+    label: String,
+    level: usize,
 }
 
-// Turn a Name into a Path with a single step
-fn process_name(node: Ast) -> Result<Ast> {
-    let char_index = node.char_index;
-    let keep_singleton_array = node.keep_array;
-    let mut result = Ast::new(AstKind::Path(vec![node]), char_index);
-    result.keep_singleton_array = keep_singleton_array;
-    Ok(result)
+#[derive(Default)]
+struct Processor {
+    slots: Vec<Slot>,
 }
 
-// Process each expression in a block
-fn process_block(node: Ast) -> Result<Ast> {
-    let mut node = node;
-    if let AstKind::Block(ref mut exprs) = node.kind {
-        for expr in exprs {
-            *expr = process_ast(take(expr))?;
+impl Processor {
+    fn process_ast(&mut self, node: Ast) -> Result<Ast> {
+        let mut node = node;
+        let keep_array = node.keep_array;
+
+        let mut result = match node.kind {
+            AstKind::Name(..) => self.process_name(node)?,
+            AstKind::Block(..) => self.process_block(node)?,
+            AstKind::Unary(..) => self.process_unary(node)?,
+            AstKind::Binary(..) => self.process_binary(node)?,
+            AstKind::GroupBy(ref mut lhs, ref mut rhs) => {
+                self.process_group_by(node.char_index, lhs, rhs)?
+            }
+            AstKind::OrderBy(ref mut lhs, ref mut rhs) => {
+                self.process_order_by(node.char_index, lhs, rhs)?
+            }
+            AstKind::Function {
+                ref mut proc,
+                ref mut args,
+                ..
+            } => {
+                self.process_function(proc, args)?;
+                for arg in args.iter() {
+                    push_ancestry(&mut node.seeking_parent, arg);
+                }
+                node
+            }
+            AstKind::Lambda { ref mut body, .. } => {
+                self.process_lambda(body)?;
+                node
+            }
+            AstKind::Ternary { .. } => self.process_ternary(node)?,
+            AstKind::Transform { .. } => self.process_transform(node)?,
+            AstKind::Parent(ref mut label) => {
+                let slot = self.slots.len();
+                *label = format!("!{}", slot);
+                self.slots.push(Slot {
+                    label: label.clone(),
+                    level: 1,
+                });
+                node.seeking_parent.push(slot);
+                node
+            }
+            _ => node,
+        };
+
+        if keep_array {
+            result.keep_array = true;
         }
+
+        Ok(result)
     }
-    Ok(node)
-}
 
-fn process_ternary(node: Ast) -> Result<Ast> {
-    let mut node = node;
-    if let AstKind::Ternary {
-        ref mut cond,
-        ref mut truthy,
-        ref mut falsy,
-    } = node.kind
-    {
-        *cond = Box::new(process_ast(take(cond))?);
-        *truthy = Box::new(process_ast(take(truthy))?);
-        if let Some(ref mut falsy) = falsy {
-            *falsy = Box::new(process_ast(take(falsy))?);
-        }
-    } else {
-        unreachable!()
+    // Turn a Name into a Path with a single step
+    fn process_name(&mut self, node: Ast) -> Result<Ast> {
+        let char_index = node.char_index;
+        let keep_singleton_array = node.keep_array;
+        let mut result = Ast::new(AstKind::Path(vec![node]), char_index);
+        result.keep_singleton_array = keep_singleton_array;
+        Ok(result)
     }
 
-    Ok(node)
-}
+    // Process each expression in a block
+    fn process_block(&mut self, node: Ast) -> Result<Ast> {
+        let mut node = node;
+        if let AstKind::Block(ref mut exprs) = node.kind {
+            for expr in exprs {
+                *expr = self.process_ast(take(expr))?;
+                push_ancestry(&mut node.seeking_parent, expr);
+            }
+        }
+        Ok(node)
+    }
 
-fn process_transform(node: Ast) -> Result<Ast> {
-    let mut node = node;
-    if let AstKind::Transform {
-        ref mut pattern,
-        ref mut update,
-        ref mut delete,
-    } = node.kind
-    {
-        *pattern = Box::new(process_ast(take(pattern))?);
-        *update = Box::new(process_ast(take(update))?);
-        if let Some(ref mut delete) = delete {
-            *delete = Box::new(process_ast(take(delete))?);
+    fn process_ternary(&mut self, node: Ast) -> Result<Ast> {
+        let mut node = node;
+        if let AstKind::Ternary {
+            ref mut cond,
+            ref mut truthy,
+            ref mut falsy,
+        } = node.kind
+        {
+            **cond = self.process_ast(take(cond))?;
+            push_ancestry(&mut node.seeking_parent, cond);
+            **truthy = self.process_ast(take(truthy))?;
+            push_ancestry(&mut node.seeking_parent, truthy);
+            if let Some(ref mut falsy) = falsy {
+                **falsy = self.process_ast(take(falsy))?;
+                push_ancestry(&mut node.seeking_parent, falsy);
+            }
+        } else {
+            unreachable!()
         }
+
+        Ok(node)
     }
 
-    Ok(node)
-}
+    fn process_transform(&mut self, node: Ast) -> Result<Ast> {
+        let mut node = node;
+        if let AstKind::Transform {
+            ref mut pattern,
+            ref mut update,
+            ref mut delete,
+        } = node.kind
+        {
+            **pattern = self.process_ast(take(pattern))?;
+            **update = self.process_ast(take(update))?;
+            if let Some(ref mut delete) = delete {
+                **delete = self.process_ast(take(delete))?;
+            }
+        }
+
+        Ok(node)
+    }
 
-fn process_unary(node: Ast) -> Result<Ast> {
-    let mut node = node;
-
-    match node.kind {
-        // Pre-process negative numbers
-        AstKind::Unary(UnaryOp::Minus(value)) => {
-            let mut result = process_ast(*value)?;
-            match result.kind {
-                AstKind::Number(ref mut v) => {
-                    *v = -*v;
-                    Ok(result)
+    fn process_unary(&mut self, node: Ast) -> Result<Ast> {
+        let mut node = node;
+
+        match node.kind {
+            // Pre-process negative numbers
+            AstKind::Unary(UnaryOp::Minus(value)) => {
+                let mut result = self.process_ast(*value)?;
+                match result.kind {
+                    AstKind::Number(ref mut v) => {
+                        *v = -*v;
+                        Ok(result)
+                    }
+                    _ => {
+                        let mut seeking_parent = Vec::new();
+                        push_ancestry(&mut seeking_parent, &result);
+                        let mut node = Ast::new(
+                            AstKind::Unary(UnaryOp::Minus(Box::new(result))),
+                            node.char_index,
+                        );
+                        node.seeking_parent = seeking_parent;
+                        Ok(node)
+                    }
                 }
-                _ => Ok(Ast::new(
-                    AstKind::Unary(UnaryOp::Minus(Box::new(result))),
-                    node.char_index,
-                )),
             }
-        }
 
-        // Process all of the expressions in an array constructor
-        AstKind::Unary(UnaryOp::ArrayConstructor(ref mut exprs)) => {
-            for expr in exprs {
-                *expr = process_ast(take(expr))?;
+            // Process all of the expressions in an array constructor
+            AstKind::Unary(UnaryOp::ArrayConstructor(ref mut exprs)) => {
+                for expr in exprs {
+                    *expr = self.process_ast(take(expr))?;
+                    push_ancestry(&mut node.seeking_parent, expr);
+                }
+                Ok(node)
             }
-            Ok(node)
-        }
 
-        // Process all the keys and values in an object constructor
-        AstKind::Unary(UnaryOp::ObjectConstructor(ref mut object)) => {
-            for pair in object {
-                let key = take(&mut pair.0);
-                let value = take(&mut pair.1);
-                *pair = (process_ast(key)?, process_ast(value)?);
+            // Process all the keys and values in an object constructor
+            AstKind::Unary(UnaryOp::ObjectConstructor(ref mut object)) => {
+                for pair in object {
+                    let key = self.process_ast(take(&mut pair.0))?;
+                    push_ancestry(&mut node.seeking_parent, &key);
+                    let value = self.process_ast(take(&mut pair.1))?;
+                    push_ancestry(&mut node.seeking_parent, &value);
+                    *pair = (key, value);
+                }
+                Ok(node)
             }
-            Ok(node)
-        }
 
-        _ => unreachable!(),
+            _ => unreachable!(),
+        }
     }
-}
 
-fn process_binary(node: Ast) -> Result<Ast> {
-    let mut node = node;
+    fn process_binary(&mut self, node: Ast) -> Result<Ast> {
+        let mut node = node;
 
-    match node.kind {
-        AstKind::Binary(BinaryOp::Map, ref mut lhs, ref mut rhs) => {
-            process_path(node.char_index, lhs, rhs)
-        }
-        AstKind::Binary(BinaryOp::Predicate, ref mut lhs, ref mut rhs) => {
-            process_predicate(node.char_index, lhs, rhs)
-        }
-        AstKind::Binary(BinaryOp::FocusBind, ref mut lhs, ref mut rhs) => {
-            process_focus_bind(node.char_index, node.keep_array, lhs, rhs)
-        }
-        AstKind::Binary(BinaryOp::IndexBind, ref mut lhs, ref mut rhs) => {
-            process_index_bind(node.char_index, lhs, rhs)
-        }
-        AstKind::Binary(_, ref mut lhs, ref mut rhs) => {
-            *lhs = Box::new(process_ast(take(lhs))?);
-            *rhs = Box::new(process_ast(take(rhs))?);
-            Ok(node)
+        match node.kind {
+            AstKind::Binary(BinaryOp::Map, ref mut lhs, ref mut rhs) => {
+                self.process_path(node.char_index, lhs, rhs)
+            }
+            AstKind::Binary(BinaryOp::Predicate, ref mut lhs, ref mut rhs) => {
+                self.process_predicate(node.char_index, lhs, rhs)
+            }
+            AstKind::Binary(BinaryOp::FocusBind, ref mut lhs, ref mut rhs) => {
+                self.process_focus_bind(node.char_index, node.keep_array, lhs, rhs)
+            }
+            AstKind::Binary(BinaryOp::IndexBind, ref mut lhs, ref mut rhs) => {
+                self.process_index_bind(node.char_index, lhs, rhs)
+            }
+            AstKind::Binary(ref op, ref mut lhs, ref mut rhs) => {
+                **lhs = self.process_ast(take(lhs))?;
+                **rhs = self.process_ast(take(rhs))?;
+
+                // Variable binds only look for parents on the right, and function application
+                // doesn't look for parents at all
+                match op {
+                    BinaryOp::Bind => push_ancestry(&mut node.seeking_parent, rhs),
+                    BinaryOp::Apply => (),
+                    _ => {
+                        push_ancestry(&mut node.seeking_parent, lhs);
+                        push_ancestry(&mut node.seeking_parent, rhs);
+                    }
+                }
+
+                Ok(node)
+            }
+            _ => unreachable!(),
         }
-        _ => unreachable!(),
     }
-}
 
-fn process_path(char_index: usize, lhs: &mut Box<Ast>, rhs: &mut Box<Ast>) -> Result<Ast> {
-    let left_step = process_ast(take(lhs))?;
-    let mut rest = process_ast(take(rhs))?;
+    fn process_path(
+        &mut self,
+        char_index: usize,
+        lhs: &mut Box<Ast>,
+        rhs: &mut Box<Ast>,
+    ) -> Result<Ast> {
+        let left_step = self.process_ast(take(lhs))?;
+
+        // If the left_step is a path itself, start with that. Otherwise, start a new path
+        let mut result = if matches!(left_step.kind, AstKind::Path(_)) {
+            left_step
+        } else {
+            let mut result = Ast::new(AstKind::Path(vec![]), char_index);
 
-    // If the left_step is a path itself, start with that. Otherwise, start a new path
-    let mut result = if matches!(left_step.kind, AstKind::Path(_)) {
-        left_step
-    } else {
-        Ast::new(AstKind::Path(vec![left_step]), char_index)
-    };
+            // If the left_step is a parent, the path is seeking the parent's ancestor
+            if let AstKind::Parent(..) = left_step.kind {
+                result.seeking_parent = left_step.seeking_parent.clone();
+            }
 
-    // TODO: If the lhs is a Parent (parser.js:997)
-    // TODO: If the rhs is a Function (parser.js:1001)
+            if let AstKind::Path(ref mut steps) = result.kind {
+                steps.push(left_step);
+            }
+            result
+        };
 
-    if let AstKind::Path(ref mut steps) = result.kind {
-        if let AstKind::Path(ref mut rest_steps) = rest.kind {
-            // If the rest is a path, merge in the steps
-            steps.append(rest_steps);
-        } else {
-            // If there are predicates on the rest, they become stages of the step
-            rest.stages = rest.predicates.take();
-            steps.push(rest);
-        }
+        let mut rest = self.process_ast(take(rhs))?;
 
-        let mut keep_singleton_array = false;
-        let last_index = steps.len() - 1;
+        // TODO: If the rhs is a Function (parser.js:1001)
 
-        for (step_index, step) in steps.iter_mut().enumerate() {
-            match step.kind {
-                // Steps can't be literal values other than strings
-                AstKind::Number(..) | AstKind::Bool(..) | AstKind::Null => {
-                    return Err(Error::S0213InvalidStep(step.char_index, "TODO".to_string()));
-                }
+        if let AstKind::Path(ref mut steps) = result.kind {
+            if let AstKind::Path(ref mut rest_steps) = rest.kind {
+                // If the rest is a path, merge in the steps
+                steps.append(rest_steps);
+            } else {
+                // If there are predicates on the rest, they become stages of the step
+                rest.stages = rest.predicates.take();
+                steps.push(rest);
+            }
 
-                // Steps that are string literals should become Names
-                AstKind::String(ref s) => {
-                    step.kind = AstKind::Name(s.clone());
-                }
+            let mut keep_singleton_array = false;
+            let last_index = steps.len() - 1;
+
+            for (step_index, step) in steps.iter_mut().enumerate() {
+                match step.kind {
+                    // Steps can't be literal values other than strings
+                    AstKind::Number(..) | AstKind::Bool(..) | AstKind::Null => {
+                        return Err(Error::S0213InvalidStep(step.char_index, "TODO".to_string()));
+                    }
 
-                // If the first or last step is an array constructor, it shouldn't be flattened
-                AstKind::Unary(UnaryOp::ArrayConstructor(..)) => {
-                    if step_index == 0 || step_index == last_index {
+                    // Steps that are string literals should become Names
+                    AstKind::String(ref s) => {
+                        step.kind = AstKind::Name(s.clone());
+                    }
+
+                    // If the first or last step is an array constructor, it shouldn't be flattened
+                    AstKind::Unary(UnaryOp::ArrayConstructor(..))
+                        if step_index == 0 || step_index == last_index =>
+                    {
                         step.cons_array = true;
                     }
+
+                    _ => (),
                 }
 
-                _ => (),
+                // Any step that signals keeping a singleton array should be plagged on the path
+                keep_singleton_array = keep_singleton_array || step.keep_array;
             }
 
-            // Any step that signals keeping a singleton array should be plagged on the path
-            keep_singleton_array = keep_singleton_array || step.keep_array;
+            result.keep_singleton_array = keep_singleton_array;
         }
 
-        result.keep_singleton_array = keep_singleton_array;
-    }
+        self.resolve_ancestry(&mut result)?;
 
-    Ok(result)
-}
+        Ok(result)
+    }
 
-fn process_predicate(char_index: usize, lhs: &mut Box<Ast>, rhs: &mut Box<Ast>) -> Result<Ast> {
-    let mut result = process_ast(take(lhs))?;
-    let mut in_path = false;
+    fn process_predicate(
+        &mut self,
+        char_index: usize,
+        lhs: &mut Box<Ast>,
+        rhs: &mut Box<Ast>,
+    ) -> Result<Ast> {
+        let mut result = self.process_ast(take(lhs))?;
+        let mut in_path = false;
+
+        let node = if let AstKind::Path(ref mut steps) = result.kind {
+            in_path = true;
+            let last_index = steps.len() - 1;
+            &mut steps[last_index]
+        } else {
+            &mut result
+        };
 
-    let node = if let AstKind::Path(ref mut steps) = result.kind {
-        in_path = true;
-        let last_index = steps.len() - 1;
-        &mut steps[last_index]
-    } else {
-        &mut result
-    };
+        // Predicates can't follow group-by
+        if node.group_by.is_some() {
+            return Err(Error::S0209InvalidPredicate(char_index));
+        }
 
-    // Predicates can't follow group-by
-    if node.group_by.is_some() {
-        return Err(Error::S0209InvalidPredicate(char_index));
-    }
+        let predicate = self.process_ast(take(rhs))?;
 
-    let filter = Ast::new(
-        AstKind::Filter(Box::new(process_ast(take(rhs))?)),
-        char_index,
-    );
+        // Parents referenced by the predicate are relative to the node being filtered
+        for &slot in predicate.seeking_parent.iter() {
+            if self.slots[slot].level == 1 {
+                self.seek_parent(node, slot)?;
+            } else {
+                self.slots[slot].level -= 1;
+            }
+        }
+        push_ancestry(&mut node.seeking_parent, &predicate);
 
-    // TODO: seekingParent (parser.js:1074)
+        let filter = Ast::new(AstKind::Filter(Box::new(predicate)), char_index);
 
-    // Add the filter to the node. If it's a step in a path, it goes in stages, otherwise in predicates
-    if in_path {
-        match node.stages {
-            None => node.stages = Some(vec![filter]),
-            Some(ref mut stages) => {
-                stages.push(filter);
+        // Add the filter to the node. If it's a step in a path, it goes in stages, otherwise in predicates
+        if in_path {
+            match node.stages {
+                None => node.stages = Some(vec![filter]),
+                Some(ref mut stages) => {
+                    stages.push(filter);
+                }
             }
-        }
-    } else {
-        match node.predicates {
-            None => node.predicates = Some(vec![filter]),
-            Some(ref mut predicates) => {
-                predicates.push(filter);
+        } else {
+            match node.predicates {
+                None => node.predicates = Some(vec![filter]),
+                Some(ref mut predicates) => {
+                    predicates.push(filter);
+                }
             }
         }
+
+        Ok(result)
     }
 
-    Ok(result)
-}
+    fn process_focus_bind(
+        &mut self,
+        char_index: usize,
+        keep_array: bool,
+        lhs: &mut Box<Ast>,
+        rhs: &mut Box<Ast>,
+    ) -> Result<Ast> {
+        let mut result = self.process_ast(take(lhs))?;
+        let step = if let AstKind::Path(ref mut steps) = result.kind {
+            // Left hand side was a path, so we want to operate on the last step
+            let last_index = steps.len() - 1;
+            &mut steps[last_index]
+        } else {
+            &mut result
+        };
 
-fn process_focus_bind(
-    char_index: usize,
-    keep_array: bool,
-    lhs: &mut Box<Ast>,
-    rhs: &mut Box<Ast>,
-) -> Result<Ast> {
-    let mut result = process_ast(take(lhs))?;
-    let step = if let AstKind::Path(ref mut steps) = result.kind {
-        // Left hand side was a path, so we want to operate on the last step
-        let last_index = steps.len() - 1;
-        &mut steps[last_index]
-    } else {
-        &mut result
-    };
+        if step.stages.is_some() || step.predicates.is_some() {
+            return Err(Error::S0215BindingAfterPredicates(char_index));
+        }
 
-    if step.stages.is_some() || step.predicates.is_some() {
-        return Err(Error::S0215BindingAfterPredicates(char_index));
-    }
+        if let AstKind::Sort(..) = step.kind {
+            return Err(Error::S0216BindingAfterSort(char_index));
+        }
 
-    if let AstKind::Sort(..) = step.kind {
-        return Err(Error::S0216BindingAfterSort(char_index));
-    }
+        if keep_array {
+            step.keep_array = true;
+        }
+
+        let focus = if let AstKind::Var(ref var) = rhs.kind {
+            var.clone()
+        } else {
+            unreachable!()
+        };
+        step.focus = Some(focus);
+
+        step.tuple = true;
 
-    if keep_array {
-        step.keep_array = true;
+        Ok(result)
     }
 
-    let focus = if let AstKind::Var(ref var) = rhs.kind {
-        var.clone()
-    } else {
-        unreachable!()
-    };
-    step.focus = Some(focus);
+    fn process_index_bind(
+        &mut self,
+        char_index: usize,
+        lhs: &mut Box<Ast>,
+        rhs: &mut Box<Ast>,
+    ) -> Result<Ast> {
+        let mut result = self.process_ast(take(lhs))?;
+        let mut is_path = false;
+
+        let step = if let AstKind::Path(ref mut steps) = result.kind {
+            // Left hand side was a path, so we want to operate on the last step
+            is_path = true;
+            let last_index = steps.len() - 1;
+            &mut steps[last_index]
+        } else {
+            // Convert predicates to stages as this will become the first step in a new path
+            if result.predicates.is_some() {
+                result.stages = result.predicates.take();
+            }
+            &mut result
+        };
 
-    step.tuple = true;
+        step.tuple = true;
 
-    Ok(result)
-}
+        let index = if let AstKind::Var(ref var) = rhs.kind {
+            var.clone()
+        } else {
+            unreachable!()
+        };
 
-fn process_index_bind(char_index: usize, lhs: &mut Box<Ast>, rhs: &mut Box<Ast>) -> Result<Ast> {
-    let mut result = process_ast(take(lhs))?;
-    let mut is_path = false;
-
-    let step = if let AstKind::Path(ref mut steps) = result.kind {
-        // Left hand side was a path, so we want to operate on the last step
-        is_path = true;
-        let last_index = steps.len() - 1;
-        &mut steps[last_index]
-    } else {
-        // Convert predicates to stages as this will become the first step in a new path
-        if result.predicates.is_some() {
-            result.stages = result.predicates.take();
+        match step.stages {
+            None => step.index = Some(index),
+            Some(ref mut stages) => {
+                let index = Ast::new(AstKind::Index(index), char_index);
+                stages.push(index);
+            }
         }
-        &mut result
-    };
 
-    step.tuple = true;
-
-    let index = if let AstKind::Var(ref var) = rhs.kind {
-        var.clone()
-    } else {
-        unreachable!()
-    };
+        // Turn it into a path
+        Ok(if !is_path {
+            Ast::new(AstKind::Path(vec![result]), char_index)
+        } else {
+            result
+        })
+    }
 
-    match step.stages {
-        None => step.index = Some(index),
-        Some(ref mut stages) => {
-            let index = Ast::new(AstKind::Index(index), char_index);
-            stages.push(index);
+    fn process_group_by(
+        &mut self,
+        char_index: usize,
+        lhs: &mut Box<Ast>,
+        rhs: &mut Object,
+    ) -> Result<Ast> {
+        let mut result = self.process_ast(take(lhs))?;
+
+        // Can only have a single grouping expression
+        if result.group_by.is_some() {
+            return Err(Error::S0210MultipleGroupBy(char_index));
         }
-    }
 
-    // Turn it into a path
-    Ok(if !is_path {
-        Ast::new(AstKind::Path(vec![result]), char_index)
-    } else {
-        result
-    })
-}
+        // Process all the key, value pairs
+        for pair in rhs.iter_mut() {
+            let key = take(&mut pair.0);
+            let value = take(&mut pair.1);
+            *pair = (self.process_ast(key)?, self.process_ast(value)?);
+        }
 
-fn process_group_by(char_index: usize, lhs: &mut Box<Ast>, rhs: &mut Object) -> Result<Ast> {
-    let mut result = process_ast(take(lhs))?;
+        result.group_by = Some((char_index, take(rhs)));
 
-    // Can only have a single grouping expression
-    if result.group_by.is_some() {
-        return Err(Error::S0210MultipleGroupBy(char_index));
+        Ok(result)
     }
 
-    // Process all the key, value pairs
-    for pair in rhs.iter_mut() {
-        let key = take(&mut pair.0);
-        let value = take(&mut pair.1);
-        *pair = (process_ast(key)?, process_ast(value)?);
-    }
+    fn process_order_by(
+        &mut self,
+        char_index: usize,
+        lhs: &mut Box<Ast>,
+        rhs: &mut SortTerms,
+    ) -> Result<Ast> {
+        let lhs = self.process_ast(take(lhs))?;
+
+        // If the left hand side is not a path, make it one
+        let mut result = if matches!(lhs.kind, AstKind::Path(_)) {
+            lhs
+        } else {
+            Ast::new(AstKind::Path(vec![lhs]), char_index)
+        };
+
+        // Process all the sort terms
+        let mut seeking_parent = Vec::new();
+        for pair in rhs.iter_mut() {
+            *pair = (self.process_ast(take(&mut pair.0))?, pair.1);
+            push_ancestry(&mut seeking_parent, &pair.0);
+        }
 
-    result.group_by = Some((char_index, take(rhs)));
+        if let AstKind::Path(ref mut steps) = result.kind {
+            let mut sort = Ast::new(AstKind::Sort(take(rhs)), char_index);
+            sort.seeking_parent = seeking_parent;
+            steps.push(sort);
+        }
 
-    Ok(result)
-}
+        self.resolve_ancestry(&mut result)?;
 
-fn process_order_by(char_index: usize, lhs: &mut Box<Ast>, rhs: &mut SortTerms) -> Result<Ast> {
-    let lhs = process_ast(take(lhs))?;
+        Ok(result)
+    }
 
-    // If the left hand side is not a path, make it one
-    let mut result = if matches!(lhs.kind, AstKind::Path(_)) {
-        lhs
-    } else {
-        Ast::new(AstKind::Path(vec![lhs]), char_index)
-    };
+    fn process_function(&mut self, proc: &mut Box<Ast>, args: &mut [Ast]) -> Result<()> {
+        **proc = self.process_ast(take(&mut *proc))?;
+        for arg in args.iter_mut() {
+            *arg = self.process_ast(take(arg))?;
+        }
+        Ok(())
+    }
 
-    // Process all the sort terms
-    for pair in rhs.iter_mut() {
-        *pair = (process_ast(take(&mut pair.0))?, pair.1);
+    fn process_lambda(&mut self, body: &mut Box<Ast>) -> Result<()> {
+        let new_body = self.process_ast(take(body))?;
+        let new_body = tail_call_optimize(new_body)?;
+        **body = new_body;
+        Ok(())
     }
 
-    if let AstKind::Path(ref mut steps) = result.kind {
-        steps.push(Ast::new(AstKind::Sort(take(rhs)), char_index));
+    /// Walks back up the steps of a path on behalf of any parent references in the last step, for
+    /// as many levels as each reference needs. References that climb off the start of the path
+    /// are left seeking on the path itself.
+    fn resolve_ancestry(&mut self, path: &mut Ast) -> Result<()> {
+        let AstKind::Path(ref mut steps) = path.kind else {
+            unreachable!()
+        };
+
+        let slots = steps[steps.len() - 1].seeking_parent.clone();
+
+        for slot in slots {
+            let mut index = steps.len() - 1;
+            while self.slots[slot].level > 0 {
+                if index == 0 {
+                    path.seeking_parent.push(slot);
+                    break;
+                }
+
+                // Try the previous step, skipping over multiple contiguous steps that bind the focus
+                index -= 1;
+                while index > 0 && steps[index].focus.is_some() && steps[index - 1].focus.is_some()
+                {
+                    index -= 1;
+                }
+                self.seek_parent(&mut steps[index], slot)?;
+            }
+        }
+
+        Ok(())
     }
 
-    Ok(result)
-}
+    /// Looks for the ancestor of a parent reference within the given node, marking the node whose
+    /// input is the ancestor so that it's bound into the tuple stream when evaluated.
+    fn seek_parent(&mut self, node: &mut Ast, slot: usize) -> Result<()> {
+        match node.kind {
+            AstKind::Name(..) | AstKind::Wildcard => {
+                let slot = &mut self.slots[slot];
+                slot.level -= 1;
+                if slot.level == 0 {
+                    node.ancestors.push(slot.label.clone());
+                    node.tuple = true;
+                }
+            }
+            AstKind::Parent(..) => self.slots[slot].level += 1,
+            AstKind::Block(ref mut exprs) => {
+                // Look in the last expression of the block
+                if let Some(last) = exprs.last_mut() {
+                    node.tuple = true;
+                    self.seek_parent(last, slot)?;
+                }
+            }
+            AstKind::Path(ref mut steps) => {
+                // Look in the last step of the path, then work back through the previous steps
+                node.tuple = true;
+                for step in steps.iter_mut().rev() {
+                    self.seek_parent(step, slot)?;
+                    if self.slots[slot].level == 0 {
+                        break;
+                    }
+                }
+            }
+            _ => return Err(Error::S0217CannotDeriveParent(node.char_index)),
+        }
 
-fn process_function(proc: &mut Box<Ast>, args: &mut [Ast]) -> Result<()> {
-    *proc = Box::new(process_ast(take(&mut *proc))?);
-    for arg in args.iter_mut() {
-        *arg = process_ast(take(arg))?;
+        Ok(())
     }
-    Ok(())
 }
 
-fn process_lambda(body: &mut Box<Ast>) -> Result<()> {
-    let new_body = process_ast(take(body))?;
-    let new_body = tail_call_optimize(new_body)?;
-    *body = Box::new(new_body);
-    Ok(())
+/// Carries any parent references that `value` is seeking up to its enclosing node.
+fn push_ancestry(seeking_parent: &mut Vec<usize>, value: &Ast) {
+    seeking_parent.extend_from_slice(&value.seeking_parent);
 }
 
+/// Wraps function calls in tail position, including `~>` applications, in thunks which the
+/// evaluator trampolines so that tail recursion doesn't grow the stack. Tail position extends
+/// into both branches of a conditional and the last expression of a block.
 fn tail_call_optimize(mut expr: Ast) -> Result<Ast> {
+    let is_tail_call = match expr.kind {
+        AstKind::Function { is_partial, .. } => !is_partial,
+        AstKind::Binary(BinaryOp::Apply, ..) => true,
+        _ => false,
+    };
+
     match &mut expr.kind {
-        AstKind::Function { .. } if expr.predicates.is_none() => {
+        _ if is_tail_call && expr.predicates.is_none() && !expr.keep_array => {
             let char_index = expr.char_index;
             let thunk = Ast::new(
                 AstKind::Lambda {
                     name: String::from("thunk"),
                     args: vec![],
+                    signature: None,
                     thunk: true,
                     body: Box::new(expr),
                 },
@@ -426,7 +623,7 @@ fn tail_call_optimize(mut expr: Ast) -> Result<Ast> {
             Ok(thunk)
         }
         AstKind::Ternary { truthy, falsy, .. } => {
-            *truthy = Box::new(tail_call_optimize(take(truthy))?);
+            **truthy = tail_call_optimize(take(truthy))?;
             if let Some(inner) = falsy {
                 *falsy = Some(Box::new(tail_call_optimize(take(inner))?));
             }
@@ -449,4 +646,6 @@ fn tail_call_optimize(mut expr: Ast) -> Result<Ast> {
     cons_array is for special handling of paths that start or end with an array constructor
     predicates is used on individual nodes
     stages are used in steps in a Path
+    seeking_parent is used during processing to carry parent references up to their ancestors
+    ancestors is used on steps whose input is the ancestor of a parent reference
 */
@@ -1,5 +1,7 @@
 use crate::{Error, Result};
 
+use crate::evaluator::signature::Signature;
+
 use super::ast::*;
 use super::tokenizer::{Token, TokenKind};
 use super::Parser;
@@ -51,7 +53,7 @@ impl Symbol for Token {
             )),
             TokenKind::Asterisk => Ok(Ast::new(AstKind::Wildcard, self.char_index)),
             TokenKind::Descendent => Ok(Ast::new(AstKind::Descendent, self.char_index)),
-            TokenKind::PercentSign => Ok(Ast::new(AstKind::Parent, self.char_index)),
+            TokenKind::PercentSign => Ok(Ast::new(AstKind::Parent(String::new()), self.char_index)),
 
             // Block of expressions
             TokenKind::LeftParen => {
@@ -139,6 +141,8 @@ impl Symbol for Token {
                 ))
             }
 
+            TokenKind::End => Err(Error::S0207UnexpectedEndOfExpression(self.char_index)),
+
             _ => Err(Error::S0211InvalidUnary(
                 self.char_index,
                 self.kind.to_string(),
@@ -224,12 +228,23 @@ impl Symbol for Token {
                         name.clone()
                     }
                     AstKind::Var(ref name) => name.clone(),
-                    _ => unreachable!(),
+                    // Any other expression can be invoked, e.g. `%(1)`, and raises T1006 when
+                    // it's evaluated if it isn't a function. Such a procedure has no name.
+                    _ => String::new(),
                 };
 
                 let func: Ast;
 
                 if is_lambda {
+                    let signature = if parser.token().kind == TokenKind::LeftAngleBracket {
+                        let start = parser.token().clone();
+                        let signature = parser.tokenizer.scan_signature(&start)?;
+                        parser.next_token()?;
+                        Some(Signature::parse(&signature, start.char_index)?)
+                    } else {
+                        None
+                    };
+
                     parser.expect(TokenKind::LeftBrace)?;
                     let body = Box::new(parser.expression(0)?);
                     func = Ast::new(
@@ -237,6 +252,7 @@ impl Symbol for Token {
                             name,
                             args,
                             body,
+                            signature,
                             thunk: false,
                         },
                         self.char_index,
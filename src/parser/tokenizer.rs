@@ -658,6 +658,30 @@ impl<'a> Tokenizer<'a> {
         Ok(token)
     }
 
+    /// Scans the rest of a function signature, once the `<` that opens it has been tokenized,
+    /// up to and including the matching `>`. Signatures are not made up of regular tokens, so
+    /// the parser hands over to this when it sees a `<` following a lambda's parameters.
+    pub fn scan_signature(&mut self, start: &Token) -> Result<String> {
+        let mut depth = 1;
+
+        while depth > 0 {
+            if self.eof() {
+                return Err(Error::S0203ExpectedTokenBeforeEnd(
+                    self.byte_index,
+                    ">".to_string(),
+                ));
+            }
+
+            match self.bump() {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                _ => {}
+            }
+        }
+
+        Ok(String::from(&self.input[start.byte_index..self.byte_index]))
+    }
+
     fn scan_number(&mut self) -> Result<TokenKind> {
         loop {
             match self.peek() {
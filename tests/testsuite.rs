@@ -133,6 +133,7 @@ fn test_case(resource: &str) {
                             &case["code"]
                         };
                         assert_eq!(*expected_code, error.code());
+                        assert_error_details(&case["error"], &error);
                     }
                 }
             }
@@ -144,7 +145,29 @@ fn test_case(resource: &str) {
                     &case["code"]
                 };
                 assert_eq!(*code, error.code());
+                assert_error_details(&case["error"], &error);
             }
         }
     }
 }
+
+// The position and message of an error are only checked when a case gives them
+fn assert_error_details(expected: &Value, error: &jsonata_rs::Error) {
+    let display = error.to_string();
+
+    if expected["position"].is_integer() {
+        let prefix = format!("{} @ {}:", error.code(), expected["position"].as_usize());
+        assert!(
+            display.starts_with(&prefix),
+            "{display:?} is not at {prefix:?}"
+        );
+    }
+
+    if expected["message"].is_string() {
+        let message = expected["message"].as_str();
+        assert!(
+            display.ends_with(&*message),
+            "{display:?} doesn't end with {message:?}"
+        );
+    }
+}